@@ -0,0 +1,7 @@
+
+-- migrations/20240102000000_add_user_profile_fields.sql
+ALTER TABLE users
+    ADD COLUMN name VARCHAR(100),
+    ADD COLUMN locale VARCHAR(35),
+    ADD COLUMN timezone VARCHAR(64),
+    ADD COLUMN avatar_url VARCHAR(2048);
@@ -0,0 +1,3 @@
+
+-- migrations/20240103000000_add_email_verification.sql
+ALTER TABLE users ADD COLUMN email_verified_at TIMESTAMPTZ;
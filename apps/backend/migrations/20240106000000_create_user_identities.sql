@@ -0,0 +1,15 @@
+-- migrations/20240106000000_create_user_identities.sql
+CREATE TABLE user_identities (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- Name of the provider in the configuration, e.g. "google"
+    provider VARCHAR(50) NOT NULL,
+    -- Stable identifier of the user at the provider (`sub` claim)
+    subject VARCHAR(255) NOT NULL,
+    email VARCHAR(255),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_used_at TIMESTAMPTZ,
+    UNIQUE (provider, subject)
+);
+
+CREATE INDEX idx_user_identities_user_id ON user_identities(user_id);
@@ -0,0 +1,29 @@
+-- migrations/20240109000000_create_service_accounts.sql
+-- Non-human principals owned by an organization, for machine-to-machine access
+CREATE TABLE service_accounts (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    organization_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    name VARCHAR(100) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_service_accounts_organization_id ON service_accounts(organization_id);
+
+CREATE TABLE api_keys (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    service_account_id UUID NOT NULL REFERENCES service_accounts(id) ON DELETE CASCADE,
+    name VARCHAR(100) NOT NULL,
+    -- SHA-256 of the key, which is only shown once at creation
+    key_hash VARCHAR(64) UNIQUE NOT NULL,
+    key_prefix VARCHAR(16) NOT NULL,
+    scopes TEXT[] NOT NULL,
+    -- Networks in CIDR notation the key may be used from, any when empty
+    allowed_ips TEXT[] NOT NULL DEFAULT '{}',
+    -- Key this one replaced, which stays valid until its own expiration
+    rotated_from UUID REFERENCES api_keys(id) ON DELETE SET NULL,
+    expires_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_used_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_api_keys_service_account_id ON api_keys(service_account_id);
@@ -0,0 +1,10 @@
+-- migrations/20240110000000_add_personal_organizations.sql
+-- Every user owns a personal organization, created along with the account
+ALTER TABLE organizations
+    ADD COLUMN personal_user_id UUID UNIQUE REFERENCES users(id) ON DELETE CASCADE;
+
+INSERT INTO organizations (name, personal_user_id)
+SELECT 'Personal', id FROM users;
+
+INSERT INTO memberships (organization_id, user_id, role)
+SELECT id, personal_user_id, 'owner' FROM organizations WHERE personal_user_id IS NOT NULL;
@@ -0,0 +1,18 @@
+-- migrations/20240111000000_create_invitations.sql
+CREATE TABLE invitations (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    organization_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    email VARCHAR(255) NOT NULL,
+    role VARCHAR(20) NOT NULL CHECK (role IN ('owner', 'admin', 'member', 'billing')),
+    -- SHA-256 of the token sent by email, replaced when the invitation is resent
+    token_hash VARCHAR(64) UNIQUE NOT NULL,
+    invited_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- One pending invitation per address and organization
+CREATE UNIQUE INDEX idx_invitations_organization_email ON invitations(organization_id, LOWER(email));
+
+-- Accounts verifying an address at this domain join the organization
+ALTER TABLE organizations ADD COLUMN auto_join_domain VARCHAR(255) UNIQUE;
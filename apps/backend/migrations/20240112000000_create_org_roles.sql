@@ -0,0 +1,15 @@
+-- migrations/20240112000000_create_org_roles.sql
+-- Roles defined by an organization, granting permissions on top of the
+-- built-in role of the members they are assigned to
+CREATE TABLE org_roles (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    organization_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    name VARCHAR(50) NOT NULL,
+    permissions TEXT[] NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (organization_id, name)
+);
+
+ALTER TABLE memberships
+    ADD COLUMN custom_role_id UUID REFERENCES org_roles(id) ON DELETE SET NULL;
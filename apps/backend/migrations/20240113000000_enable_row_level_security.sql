@@ -0,0 +1,42 @@
+-- migrations/20240113000000_enable_row_level_security.sql
+-- Tenant isolation enforced by Postgres. Transactions started with
+-- `db::begin_for_org` set `app.current_org`, rows of other organizations are
+-- then neither visible nor writable. Without it, e.g. when listing the
+-- organizations of a user, the policies let everything through.
+-- Superusers and roles with BYPASSRLS skip the policies, the application has
+-- to connect with a regular role for them to apply.
+CREATE FUNCTION current_org_id() RETURNS UUID AS $$
+    SELECT NULLIF(current_setting('app.current_org', TRUE), '')::UUID
+$$ LANGUAGE SQL STABLE;
+
+ALTER TABLE organizations ENABLE ROW LEVEL SECURITY;
+ALTER TABLE organizations FORCE ROW LEVEL SECURITY;
+CREATE POLICY tenant_isolation ON organizations
+    USING (current_org_id() IS NULL OR id = current_org_id());
+
+ALTER TABLE memberships ENABLE ROW LEVEL SECURITY;
+ALTER TABLE memberships FORCE ROW LEVEL SECURITY;
+CREATE POLICY tenant_isolation ON memberships
+    USING (current_org_id() IS NULL OR organization_id = current_org_id());
+
+ALTER TABLE service_accounts ENABLE ROW LEVEL SECURITY;
+ALTER TABLE service_accounts FORCE ROW LEVEL SECURITY;
+CREATE POLICY tenant_isolation ON service_accounts
+    USING (current_org_id() IS NULL OR organization_id = current_org_id());
+
+ALTER TABLE api_keys ENABLE ROW LEVEL SECURITY;
+ALTER TABLE api_keys FORCE ROW LEVEL SECURITY;
+CREATE POLICY tenant_isolation ON api_keys
+    USING (current_org_id() IS NULL OR service_account_id IN (
+        SELECT id FROM service_accounts WHERE organization_id = current_org_id()
+    ));
+
+ALTER TABLE invitations ENABLE ROW LEVEL SECURITY;
+ALTER TABLE invitations FORCE ROW LEVEL SECURITY;
+CREATE POLICY tenant_isolation ON invitations
+    USING (current_org_id() IS NULL OR organization_id = current_org_id());
+
+ALTER TABLE org_roles ENABLE ROW LEVEL SECURITY;
+ALTER TABLE org_roles FORCE ROW LEVEL SECURITY;
+CREATE POLICY tenant_isolation ON org_roles
+    USING (current_org_id() IS NULL OR organization_id = current_org_id());
@@ -0,0 +1,24 @@
+-- migrations/20240114000000_add_user_administration.sql
+-- Platform staff manage accounts through /admin, granted with
+-- `backend grant-admin <email>`
+ALTER TABLE users
+    ADD COLUMN is_platform_admin BOOLEAN NOT NULL DEFAULT FALSE,
+    -- Suspended accounts cannot sign in nor use their tokens
+    ADD COLUMN suspended_at TIMESTAMPTZ,
+    -- Set by staff, the password cannot sign in until it is reset
+    ADD COLUMN password_reset_required BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- What staff did to which account. No foreign keys, the trail outlives the
+-- accounts it mentions.
+CREATE TABLE admin_audit_log (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    -- NULL for command line operations
+    actor_id UUID,
+    action VARCHAR(50) NOT NULL,
+    target_user_id UUID,
+    details JSONB NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_admin_audit_log_target_user_id ON admin_audit_log(target_user_id);
+CREATE INDEX idx_admin_audit_log_created_at ON admin_audit_log(created_at);
@@ -0,0 +1,47 @@
+-- migrations/20240115000000_close_row_level_security.sql
+-- Tenant rows are only visible in transactions scoped to their organization
+-- with `db::begin_for_org`, anything else sees none of them. What spans
+-- organizations (authentication, a user's memberships, platform staff) says
+-- so with `db::begin_cross_tenant`, which switches to saas_cross_tenant.
+--
+-- The application connects as saas_app, created here without a login when
+-- it does not exist yet. Give it one before pointing DATABASE__USERNAME at it:
+--   ALTER ROLE saas_app WITH LOGIN PASSWORD '...';
+DO $$
+BEGIN
+    CREATE ROLE saas_app NOLOGIN;
+EXCEPTION WHEN duplicate_object THEN NULL;
+END
+$$;
+
+DO $$
+BEGIN
+    CREATE ROLE saas_cross_tenant NOLOGIN BYPASSRLS;
+EXCEPTION WHEN duplicate_object THEN NULL;
+END
+$$;
+
+GRANT saas_cross_tenant TO saas_app;
+
+GRANT SELECT, INSERT, UPDATE, DELETE ON ALL TABLES IN SCHEMA public TO saas_app, saas_cross_tenant;
+GRANT USAGE, SELECT ON ALL SEQUENCES IN SCHEMA public TO saas_app, saas_cross_tenant;
+-- Tables of later migrations
+ALTER DEFAULT PRIVILEGES IN SCHEMA public
+    GRANT SELECT, INSERT, UPDATE, DELETE ON TABLES TO saas_app, saas_cross_tenant;
+ALTER DEFAULT PRIVILEGES IN SCHEMA public
+    GRANT USAGE, SELECT ON SEQUENCES TO saas_app, saas_cross_tenant;
+
+ALTER POLICY tenant_isolation ON organizations
+    USING (id = current_org_id());
+ALTER POLICY tenant_isolation ON memberships
+    USING (organization_id = current_org_id());
+ALTER POLICY tenant_isolation ON service_accounts
+    USING (organization_id = current_org_id());
+ALTER POLICY tenant_isolation ON api_keys
+    USING (service_account_id IN (
+        SELECT id FROM service_accounts WHERE organization_id = current_org_id()
+    ));
+ALTER POLICY tenant_isolation ON invitations
+    USING (organization_id = current_org_id());
+ALTER POLICY tenant_isolation ON org_roles
+    USING (organization_id = current_org_id());
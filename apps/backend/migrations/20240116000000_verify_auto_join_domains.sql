@@ -0,0 +1,17 @@
+-- migrations/20240116000000_verify_auto_join_domains.sql
+-- An address at a domain does not make one its owner. Auto-join only applies
+-- once the organization has published its token in a DNS TXT record of the
+-- domain, and claiming a domain no longer keeps others from doing so.
+ALTER TABLE organizations DROP CONSTRAINT organizations_auto_join_domain_key;
+
+ALTER TABLE organizations
+    ADD COLUMN auto_join_domain_token VARCHAR(64),
+    ADD COLUMN auto_join_domain_verified_at TIMESTAMPTZ;
+
+-- Domains claimed so far have to be proven as well
+UPDATE organizations SET auto_join_domain_token = REPLACE(uuid_generate_v4()::TEXT, '-', '')
+WHERE auto_join_domain IS NOT NULL;
+
+-- A domain only enrolls into one organization
+CREATE UNIQUE INDEX idx_organizations_verified_auto_join_domain ON organizations(auto_join_domain)
+    WHERE auto_join_domain_verified_at IS NOT NULL;
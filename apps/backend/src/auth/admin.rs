@@ -0,0 +1,67 @@
+use axum::{
+    extract::{FromRequestParts, Request, State},
+    middleware::Next,
+    response::Response,
+};
+use sqlx::PgExecutor;
+use uuid::Uuid;
+
+use crate::{auth::extractor::AuthUser, errors::AppError, models::user::User, state::AppState};
+
+// Platform staff, as opposed to organization admins, and the trail of what
+// they do to accounts.
+
+pub const SUSPEND: &str = "suspend";
+pub const UNSUSPEND: &str = "unsuspend";
+pub const FORCE_PASSWORD_RESET: &str = "force_password_reset";
+pub const FORCE_LOGOUT: &str = "force_logout";
+pub const DELETE_USER: &str = "delete_user";
+pub const GRANT_ADMIN: &str = "grant_admin";
+pub const REVOKE_ADMIN: &str = "revoke_admin";
+
+/// Suspended accounts are turned away wherever they authenticate.
+pub fn ensure_active(user: &User) -> Result<(), AppError> {
+    if user.suspended_at.is_some() {
+        return Err(AppError::Forbidden("Account suspended".to_string()));
+    }
+    Ok(())
+}
+
+/// Like `require_auth`, for platform admins in an interactive session.
+pub async fn require_platform_admin(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let (mut parts, body) = request.into_parts();
+    let auth = AuthUser::from_request_parts(&mut parts, &state).await?;
+    auth.require_session()?;
+    if !auth.user.is_platform_admin {
+        return Err(AppError::Forbidden(
+            "Requires the platform admin role".to_string(),
+        ));
+    }
+    Ok(next.run(Request::from_parts(parts, body)).await)
+}
+
+/// Appends to the audit trail, in the transaction of the action when there
+/// is one so that both happen or neither does.
+pub async fn record(
+    executor: impl PgExecutor<'_>,
+    actor_id: Option<Uuid>,
+    action: &str,
+    target_user_id: Uuid,
+    details: serde_json::Value,
+) -> Result<(), AppError> {
+    sqlx::query!(
+        r#"INSERT INTO admin_audit_log (actor_id, action, target_user_id, details)
+        VALUES ($1, $2, $3, $4)"#,
+        actor_id,
+        action,
+        target_user_id,
+        details
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,87 @@
+use std::net::IpAddr;
+
+use sqlx::PgExecutor;
+use uuid::Uuid;
+
+use crate::{
+    errors::AppError,
+    models::service_account::{ApiKey, ServiceAccount},
+    utils::token::{generate_prefixed_token, hash_token, PrefixedToken},
+};
+
+// Credentials of service accounts, limited to their scopes and networks.
+// Scopes are the permissions of `auth::permission`.
+
+pub const PREFIX: &str = "sk_";
+
+pub fn generate() -> PrefixedToken {
+    generate_prefixed_token(PREFIX)
+}
+
+pub fn is_api_key(token: &str) -> bool {
+    token.starts_with(PREFIX)
+}
+
+/// A network in CIDR notation, or a single address.
+fn parse_network(network: &str) -> Option<(IpAddr, u32)> {
+    let (addr, len) = match network.split_once('/') {
+        Some((addr, len)) => (addr.parse::<IpAddr>().ok()?, Some(len.parse().ok()?)),
+        None => (network.parse::<IpAddr>().ok()?, None),
+    };
+    let max = if addr.is_ipv4() { 32 } else { 128 };
+    let len = len.unwrap_or(max);
+    (len <= max).then_some((addr, len))
+}
+
+pub fn is_valid_network(network: &str) -> bool {
+    parse_network(network).is_some()
+}
+
+fn network_contains(network: &str, ip: IpAddr) -> bool {
+    let Some((network, len)) = parse_network(network) else {
+        return false;
+    };
+    match (network, ip.to_canonical()) {
+        (IpAddr::V4(network), IpAddr::V4(ip)) => {
+            let mask = u32::MAX.checked_shl(32 - len).unwrap_or(0);
+            u32::from(network) & mask == u32::from(ip) & mask
+        }
+        (IpAddr::V6(network), IpAddr::V6(ip)) => {
+            let mask = u128::MAX.checked_shl(128 - len).unwrap_or(0);
+            u128::from(network) & mask == u128::from(ip) & mask
+        }
+        _ => false,
+    }
+}
+
+/// Keys without restrictions work from anywhere, the others only from their
+/// networks.
+pub fn is_allowed_from(key: &ApiKey, ip: Option<IpAddr>) -> bool {
+    key.allowed_ips.is_empty()
+        || ip.is_some_and(|ip| key.allowed_ips.iter().any(|n| network_contains(n, ip)))
+}
+
+/// The live key matching `key`.
+pub async fn find(executor: impl PgExecutor<'_>, key: &str) -> Result<Option<ApiKey>, AppError> {
+    Ok(sqlx::query_as!(
+        ApiKey,
+        "SELECT * FROM api_keys WHERE key_hash = $1 AND (expires_at IS NULL OR expires_at > NOW())",
+        hash_token(key)
+    )
+    .fetch_optional(executor)
+    .await?)
+}
+
+/// Records the use of a key and returns the service account it belongs to.
+pub async fn record_use(executor: impl PgExecutor<'_>, key_id: Uuid) -> Result<ServiceAccount, AppError> {
+    Ok(sqlx::query_as!(
+        ServiceAccount,
+        r#"WITH used AS (
+            UPDATE api_keys SET last_used_at = NOW() WHERE id = $1 RETURNING service_account_id
+        )
+        SELECT service_accounts.* FROM service_accounts JOIN used ON used.service_account_id = service_accounts.id"#,
+        key_id
+    )
+    .fetch_one(executor)
+    .await?)
+}
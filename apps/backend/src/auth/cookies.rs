@@ -0,0 +1,100 @@
+use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
+
+use crate::config::{CookieConfig, CookieSameSite};
+
+/// A cookie set by the API. Name, attributes and prefix all come from here
+/// and the `cookie` configuration, so that setting, reading and clearing a
+/// cookie always agree.
+pub struct CookieSpec {
+    name: &'static str,
+    http_only: bool,
+    // Browser flows scope their cookie to their own routes and need it sent
+    // on the top-level navigation coming back from the email or provider.
+    flow_path: Option<&'static str>,
+}
+
+pub const ACCESS_TOKEN: CookieSpec = CookieSpec {
+    name: "access_token",
+    http_only: true,
+    flow_path: None,
+};
+
+pub const REFRESH_TOKEN: CookieSpec = CookieSpec {
+    name: "refresh_token",
+    http_only: true,
+    flow_path: None,
+};
+
+// Readable by scripts, which echo it in the X-CSRF-Token header.
+pub const CSRF_TOKEN: CookieSpec = CookieSpec {
+    name: "csrf_token",
+    http_only: false,
+    flow_path: None,
+};
+
+pub const OAUTH_STATE: CookieSpec = CookieSpec {
+    name: "oauth_state",
+    http_only: true,
+    flow_path: Some("/auth/oauth"),
+};
+
+pub const MAGIC_LINK: CookieSpec = CookieSpec {
+    name: "magic_link",
+    http_only: true,
+    flow_path: Some("/auth/magic-link"),
+};
+
+impl CookieSpec {
+    /// `__Host-` needs the root path, so cookies scoped to a flow get the
+    /// weaker `__Secure-` prefix instead.
+    pub fn name(&self, config: &CookieConfig) -> String {
+        match (config.host_prefix, self.flow_path) {
+            (false, _) => self.name.to_string(),
+            (true, None) => format!("__Host-{}", self.name),
+            (true, Some(_)) => format!("__Secure-{}", self.name),
+        }
+    }
+
+    fn builder(&self, config: &CookieConfig, value: String) -> Cookie<'static> {
+        let same_site = match (self.flow_path, config.same_site) {
+            (Some(_), _) | (None, CookieSameSite::Lax) => SameSite::Lax,
+            (None, CookieSameSite::Strict) => SameSite::Strict,
+            (None, CookieSameSite::None) => SameSite::None,
+        };
+        let mut cookie = Cookie::build((self.name(config), value))
+            .path(self.flow_path.unwrap_or(&config.path).to_string())
+            .http_only(self.http_only)
+            .same_site(same_site)
+            .secure(config.secure)
+            .build();
+        if let Some(domain) = &config.domain {
+            cookie.set_domain(domain.clone());
+        }
+        cookie
+    }
+
+    /// The cookie holding `value`, dropped by the browser after `max_age`.
+    pub fn build(
+        &self,
+        config: &CookieConfig,
+        value: String,
+        max_age: std::time::Duration,
+    ) -> Cookie<'static> {
+        let mut cookie = self.builder(config, value);
+        cookie.set_max_age(time::Duration::try_from(max_age).ok());
+        cookie
+    }
+
+    /// A cookie telling the browser to delete this one right away.
+    pub fn expired(&self, config: &CookieConfig) -> Cookie<'static> {
+        let mut cookie = self.builder(config, String::new());
+        cookie.make_removal();
+        cookie
+    }
+
+    pub fn get(&self, config: &CookieConfig, jar: &CookieJar) -> Option<String> {
+        jar.get(&self.name(config))
+            .map(|c| c.value().to_string())
+            .filter(|v| !v.is_empty())
+    }
+}
@@ -0,0 +1,52 @@
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Nonce,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use secrecy::ExposeSecret;
+
+use crate::{config::AuthConfig, errors::AppError};
+
+// AES-256-GCM for secrets that must be readable again (TOTP seeds). The
+// stored value is base64(nonce || ciphertext).
+
+const NONCE_LEN: usize = 12;
+
+fn cipher(config: &AuthConfig) -> Result<Aes256Gcm, AppError> {
+    let key = config.encryption_key.as_ref().ok_or_else(|| {
+        tracing::error!("AUTH__ENCRYPTION_KEY is not configured");
+        AppError::InternalServerError
+    })?;
+    let key = STANDARD.decode(key.expose_secret()).map_err(|_| {
+        tracing::error!("AUTH__ENCRYPTION_KEY is not valid base64");
+        AppError::InternalServerError
+    })?;
+    Aes256Gcm::new_from_slice(&key).map_err(|_| {
+        tracing::error!("AUTH__ENCRYPTION_KEY must be 32 bytes long");
+        AppError::InternalServerError
+    })
+}
+
+pub fn encrypt(config: &AuthConfig, plaintext: &[u8]) -> Result<String, AppError> {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher(config)?
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| AppError::InternalServerError)?;
+
+    let mut out = nonce.to_vec();
+    out.extend_from_slice(&ciphertext);
+    Ok(STANDARD.encode(out))
+}
+
+pub fn decrypt(config: &AuthConfig, encoded: &str) -> Result<Vec<u8>, AppError> {
+    let data = STANDARD
+        .decode(encoded)
+        .map_err(|_| AppError::InternalServerError)?;
+    if data.len() < NONCE_LEN {
+        return Err(AppError::InternalServerError);
+    }
+    let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+    cipher(config)?
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| AppError::InternalServerError)
+}
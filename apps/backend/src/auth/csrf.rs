@@ -0,0 +1,118 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, Method},
+    middleware::Next,
+    response::Response,
+};
+use axum_extra::extract::cookie::CookieJar;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use secrecy::ExposeSecret;
+use sha2::Sha256;
+use uuid::Uuid;
+
+use crate::{
+    auth::{cookies, jwt, session},
+    config::AuthConfig,
+    errors::AppError,
+    state::AppState,
+};
+
+// Cookie-authenticated requests that change state must echo the `csrf_token`
+// cookie in the `X-CSRF-Token` header. The token is bound to the session, so
+// a sibling subdomain able to plant cookies still cannot forge one.
+
+pub const HEADER: &str = "x-csrf-token";
+
+// Endpoints that start a session rather than act within one.
+const EXEMPT: &[&str] = &[
+    "/auth/register",
+    "/auth/login",
+    "/auth/verify-email",
+    "/auth/resend-verification",
+    "/auth/password/forgot",
+    "/auth/password/reset",
+    "/auth/email/confirm",
+    "/auth/magic-link",
+    "/auth/mfa/verify",
+    "/auth/mfa/webauthn/start",
+    "/auth/webauthn/login/start",
+    "/auth/webauthn/login/finish",
+];
+
+fn mac(config: &AuthConfig, session_id: Uuid) -> Hmac<Sha256> {
+    let key = config.jwt_refresh_secret.expose_secret().as_bytes();
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(format!("csrf:{}", session_id).as_bytes());
+    mac
+}
+
+/// CSRF token of a session.
+pub fn token(config: &AuthConfig, session_id: Uuid) -> String {
+    URL_SAFE_NO_PAD.encode(mac(config, session_id).finalize().into_bytes())
+}
+
+// Constant-time comparison.
+fn verify(config: &AuthConfig, session_id: Uuid, candidate: &str) -> bool {
+    URL_SAFE_NO_PAD
+        .decode(candidate)
+        .is_ok_and(|candidate| mac(config, session_id).verify_slice(&candidate).is_ok())
+}
+
+/// Sessions the cookies of a request act for.
+fn sessions(state: &AppState, jar: &CookieJar) -> Vec<Uuid> {
+    let config = &state.config.cookie;
+    let access = cookies::ACCESS_TOKEN.get(config, jar).and_then(|token| {
+        jwt::validate_token(&token, &state.jwt, jwt::TokenType::Access)
+            .ok()?
+            .sid
+    });
+    let refresh = cookies::REFRESH_TOKEN
+        .get(config, jar)
+        .and_then(|token| session::session_of(&state.config.auth, &token));
+    access.into_iter().chain(refresh).collect()
+}
+
+/// Rejects state-changing requests authenticated by cookies without the
+/// session's CSRF token. Bearer requests carry no ambient credentials and
+/// requests without a session cookie have nothing to abuse, both go through.
+/// Other `Authorization` schemes do not count, the access token cookie is
+/// used along with them.
+pub async fn protect(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let safe = matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE
+    );
+    let bearer = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("Bearer "));
+    if safe || bearer || EXEMPT.contains(&request.uri().path())
+    {
+        return Ok(next.run(request).await);
+    }
+
+    let sessions = sessions(&state, &CookieJar::from_headers(request.headers()));
+    if sessions.is_empty() {
+        return Ok(next.run(request).await);
+    }
+
+    let candidate = request
+        .headers()
+        .get(HEADER)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    if !sessions
+        .into_iter()
+        .any(|session_id| verify(&state.config.auth, session_id, candidate))
+    {
+        return Err(AppError::Forbidden("Invalid CSRF token".to_string()));
+    }
+
+    Ok(next.run(request).await)
+}
@@ -0,0 +1,28 @@
+use chrono::Utc;
+use redis::aio::MultiplexedConnection;
+use redis::AsyncCommands;
+use uuid::Uuid;
+
+use crate::errors::AppError;
+
+// Access tokens are stateless and stay valid until they expire. Revoking one
+// before that puts its `jti` here for the rest of its lifetime, and the auth
+// extractor rejects it.
+
+fn key(jti: &Uuid) -> String {
+    format!("denied_jti:{}", jti)
+}
+
+/// Denies the token `jti` until `exp`. Tokens already expired are skipped.
+pub async fn deny(conn: &mut MultiplexedConnection, jti: Uuid, exp: i64) -> Result<(), AppError> {
+    let remaining = exp - Utc::now().timestamp();
+    if remaining > 0 {
+        conn.set_ex::<_, _, ()>(key(&jti), 1, remaining as u64)
+            .await?;
+    }
+    Ok(())
+}
+
+pub async fn is_denied(conn: &mut MultiplexedConnection, jti: &Uuid) -> Result<bool, AppError> {
+    Ok(conn.exists(key(jti)).await?)
+}
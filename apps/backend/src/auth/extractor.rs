@@ -0,0 +1,206 @@
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts, Request, State},
+    http::{header, request::Parts},
+    middleware::Next,
+    response::Response,
+};
+use axum_extra::extract::cookie::CookieJar;
+use uuid::Uuid;
+
+use crate::{
+    auth::{
+        admin, cookies, denylist,
+        jwt::{self, Claims},
+        personal_token,
+    },
+    errors::AppError,
+    models::user::User,
+    state::AppState,
+};
+
+/// The user behind the access token of the current request, taken from the
+/// `Authorization: Bearer` header or the `access_token` cookie.
+///
+/// Personal access tokens are refused, handlers open to them take
+/// `ScopedAuth` instead.
+#[derive(Debug, Clone)]
+pub struct AuthUser {
+    pub user: User,
+    pub credential: Credential,
+}
+
+/// Like `AuthUser`, but also lets personal access tokens through. The user is
+/// only handed out by `require_scope`, so a handler cannot forget to check
+/// the scopes of the token.
+#[derive(Debug, Clone)]
+pub struct ScopedAuth(AuthUser);
+
+#[derive(Debug, Clone)]
+pub enum Credential {
+    // Interactive session, allowed everything the user is.
+    Session(Claims),
+    // Personal access token, limited to its scopes.
+    PersonalToken { id: Uuid, scopes: Vec<String> },
+}
+
+impl ScopedAuth {
+    /// Lets sessions and personal access tokens holding `scope` through.
+    pub fn require_scope(self, scope: &str) -> Result<AuthUser, AppError> {
+        self.0.require_scope(scope)?;
+        Ok(self.0)
+    }
+}
+
+impl AuthUser {
+    /// Lets sessions and personal access tokens holding `scope` through.
+    pub fn require_scope(&self, scope: &str) -> Result<(), AppError> {
+        match &self.credential {
+            Credential::Session(_) => Ok(()),
+            Credential::PersonalToken { scopes, .. } if scopes.iter().any(|s| s == scope) => Ok(()),
+            Credential::PersonalToken { .. } => Err(AppError::Forbidden(format!(
+                "Token is missing the {} scope",
+                scope
+            ))),
+        }
+    }
+
+    /// Account security is managed from an interactive session only.
+    pub fn require_session(&self) -> Result<&Claims, AppError> {
+        match &self.credential {
+            Credential::Session(claims) => Ok(claims),
+            Credential::PersonalToken { .. } => Err(AppError::Forbidden(
+                "Personal access tokens cannot be used for this action".to_string(),
+            )),
+        }
+    }
+}
+
+fn bearer_token(parts: &Parts) -> Option<String> {
+    parts
+        .headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(|v| v.trim().to_string())
+}
+
+async fn authenticate_personal_token(state: &AppState, token: &str) -> Result<AuthUser, AppError> {
+    let invalid = || AppError::Unauthorized("Invalid personal access token".to_string());
+    let record = personal_token::authenticate(&state.pool, token)
+        .await?
+        .ok_or_else(invalid)?;
+
+    let user = sqlx::query_as!(
+        User,
+        "SELECT * FROM users WHERE id = $1",
+        record.user_id
+    )
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or_else(invalid)?;
+    admin::ensure_active(&user)?;
+
+    Ok(AuthUser {
+        user,
+        credential: Credential::PersonalToken {
+            id: record.id,
+            scopes: record.scopes,
+        },
+    })
+}
+
+async fn authenticate(parts: &Parts, state: &AppState) -> Result<AuthUser, AppError> {
+    let bearer = bearer_token(parts);
+    if let Some(token) = bearer.as_deref().filter(|t| personal_token::is_personal_token(t)) {
+        return authenticate_personal_token(state, token).await;
+    }
+
+    let jar = CookieJar::from_headers(&parts.headers);
+    let token = bearer
+        .or_else(|| cookies::ACCESS_TOKEN.get(&state.config.cookie, &jar))
+        .ok_or_else(|| AppError::Unauthorized("Missing access token".to_string()))?;
+    let claims = jwt::validate_token(&token, &state.jwt, jwt::TokenType::Access)
+        .map_err(|_| AppError::Unauthorized("Invalid access token".to_string()))?;
+
+    let mut redis_conn = state
+        .redis
+        .get_multiplexed_async_connection()
+        .await
+        .map_err(AppError::Redis)?;
+    if denylist::is_denied(&mut redis_conn, &claims.jti).await? {
+        return Err(AppError::Unauthorized("Access token revoked".to_string()));
+    }
+
+    let user = sqlx::query_as!(
+        User,
+        "SELECT * FROM users WHERE id = $1",
+        claims.sub
+    )
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or_else(|| AppError::Unauthorized("Invalid access token".to_string()))?;
+    admin::ensure_active(&user)?;
+
+    Ok(AuthUser {
+        user,
+        credential: Credential::Session(claims),
+    })
+}
+
+/// The user behind any credential of the request, personal access tokens
+/// included.
+pub(crate) async fn authenticated<S>(parts: &mut Parts, state: &S) -> Result<AuthUser, AppError>
+where
+    AppState: FromRef<S>,
+{
+    // Already resolved by `require_auth`, no need to hit the database twice.
+    if let Some(auth_user) = parts.extensions.get::<AuthUser>() {
+        return Ok(auth_user.clone());
+    }
+
+    let state = AppState::from_ref(state);
+    let auth_user = authenticate(parts, &state).await?;
+    parts.extensions.insert(auth_user.clone());
+    Ok(auth_user)
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AuthUser
+where
+    AppState: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let auth_user = authenticated(parts, state).await?;
+        auth_user.require_session()?;
+        Ok(auth_user)
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ScopedAuth
+where
+    AppState: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        Ok(ScopedAuth(authenticated(parts, state).await?))
+    }
+}
+
+/// Rejects unauthenticated requests before they reach the handler. Use it with
+/// `middleware::from_fn_with_state(state.clone(), require_auth)` as a route layer.
+pub async fn require_auth(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let (mut parts, body) = request.into_parts();
+    authenticated(&mut parts, &state).await?;
+    Ok(next.run(Request::from_parts(parts, body)).await)
+}
@@ -0,0 +1,56 @@
+use sqlx::PgConnection;
+
+use crate::{
+    errors::AppError,
+    models::{
+        organization::{Invitation, Membership, Role},
+        user::User,
+    },
+    utils::token::hash_token,
+};
+
+// Invitations to join an organization, accepted with the token sent by
+// email, from an existing account or along with a sign-up.
+
+/// Consumes the invitation behind `token` for `user`, who must be the
+/// invitee. Members already in the organization keep their role. The
+/// transaction must be cross-tenant, the token tells the organization.
+pub async fn accept(
+    conn: &mut PgConnection,
+    token: &str,
+    user: &User,
+) -> Result<Membership, AppError> {
+    let invitation = sqlx::query_as!(
+        Invitation,
+        r#"SELECT id, organization_id, email, role AS "role: Role", token_hash, invited_by, expires_at, created_at
+        FROM invitations WHERE token_hash = $1 AND expires_at > NOW()
+        FOR UPDATE"#,
+        hash_token(token)
+    )
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or_else(|| AppError::BadRequest("Invalid or expired invitation".to_string()))?;
+
+    // The token may have been forwarded, it only works for the invited address.
+    if !invitation.email.eq_ignore_ascii_case(&user.email) {
+        return Err(AppError::Forbidden(
+            "This invitation was sent to another email address".to_string(),
+        ));
+    }
+
+    sqlx::query!("DELETE FROM invitations WHERE id = $1", invitation.id)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(sqlx::query_as!(
+        Membership,
+        r#"INSERT INTO memberships (organization_id, user_id, role) VALUES ($1, $2, $3)
+        ON CONFLICT (organization_id, user_id) DO UPDATE SET role = memberships.role
+        RETURNING organization_id, user_id, role AS "role: Role", custom_role_id, created_at"#,
+        invitation.organization_id,
+        user.id,
+        invitation.role as Role
+    )
+    .fetch_one(&mut *conn)
+    .await?)
+}
@@ -1,46 +1,420 @@
-
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use chrono::{Duration, Utc};
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use jsonwebtoken::jwk::{
+    AlgorithmParameters, CommonParameters, EllipticCurve, EllipticCurveKeyParameters, Jwk,
+    JwkSet, KeyAlgorithm, OctetKeyPairParameters, PublicKeyUse, RSAKeyParameters,
+};
+use jsonwebtoken::{
+    decode, decode_header, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
+use openssl::bn::{BigNum, BigNumContext};
+use openssl::ec::{EcGroup, EcKey};
+use openssl::nid::Nid;
+use openssl::pkey::{HasPublic, Id, PKey};
+use openssl::rsa::Rsa;
+use secrecy::ExposeSecret;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use uuid::Uuid;
-use crate::errors::AppError;
+use crate::{
+    config::{AuthConfig, JwtKeyConfig},
+    errors::AppError,
+    utils::token::generate_opaque_token,
+};
+
 
+/// What a token was minted for. Checked on every validation so that a token
+/// issued for one purpose is never accepted for another.
+///
+/// Only access and refresh tokens are JWTs. MFA challenges and email links
+/// (verification, password reset, ...) use opaque single-use tokens kept in
+/// Redis, see `mfa` and `single_use`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenType {
+    Access,
+    Refresh,
+}
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
+    pub iss: String,
+    pub aud: String,
     pub sub: Uuid,
     pub exp: i64,
     pub iat: i64,
     pub nbf: i64,
+    pub jti: Uuid,
+    pub typ: TokenType,
+    // Session (refresh token family) an access token was issued for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<Uuid>,
+    // Organization the token acts on, and the roles held in it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub org: Option<Uuid>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub roles: Vec<String>,
+}
+
+/// A key verifying tokens, and signing them when its private part is known.
+/// Keys are told apart by the `kid` header.
+#[derive(Clone)]
+pub struct JwtKey {
+    pub kid: String,
+    algorithm: Algorithm,
+    encoding: Option<EncodingKey>,
+    decoding: DecodingKey,
+    // Public part, absent for shared secrets which must never be published.
+    jwk: Option<Jwk>,
+}
+
+fn invalid_key(msg: impl std::fmt::Display) -> AppError {
+    AppError::Config(config::ConfigError::Message(format!("Invalid JWT key: {}", msg)))
+}
+
+pub fn is_hmac(algorithm: Algorithm) -> bool {
+    matches!(
+        algorithm,
+        Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+    )
+}
+
+/// Decoding key and JWK parameters of the public part of a key.
+fn public_parts<T: HasPublic>(
+    algorithm: Algorithm,
+    pkey: &PKey<T>,
+) -> Result<(DecodingKey, AlgorithmParameters), AppError> {
+    match (algorithm, pkey.id()) {
+        (
+            Algorithm::RS256
+            | Algorithm::RS384
+            | Algorithm::RS512
+            | Algorithm::PS256
+            | Algorithm::PS384
+            | Algorithm::PS512,
+            Id::RSA,
+        ) => {
+            let rsa = pkey.rsa().map_err(invalid_key)?;
+            let (n, e) = (rsa.n().to_vec(), rsa.e().to_vec());
+            Ok((
+                DecodingKey::from_rsa_raw_components(&n, &e),
+                AlgorithmParameters::RSA(RSAKeyParameters {
+                    n: URL_SAFE_NO_PAD.encode(n),
+                    e: URL_SAFE_NO_PAD.encode(e),
+                    ..Default::default()
+                }),
+            ))
+        }
+        (Algorithm::ES256 | Algorithm::ES384, Id::EC) => {
+            let (nid, curve, size) = match algorithm {
+                Algorithm::ES256 => (Nid::X9_62_PRIME256V1, EllipticCurve::P256, 32),
+                _ => (Nid::SECP384R1, EllipticCurve::P384, 48),
+            };
+            let ec = pkey.ec_key().map_err(invalid_key)?;
+            if ec.group().curve_name() != Some(nid) {
+                return Err(invalid_key(format!("{:?} needs a {:?} key", algorithm, curve)));
+            }
+            let (mut x, mut y) = (BigNum::new().map_err(invalid_key)?, BigNum::new().map_err(invalid_key)?);
+            let mut ctx = BigNumContext::new().map_err(invalid_key)?;
+            ec.public_key()
+                .affine_coordinates(ec.group(), &mut x, &mut y, &mut ctx)
+                .map_err(invalid_key)?;
+            let x = URL_SAFE_NO_PAD.encode(x.to_vec_padded(size).map_err(invalid_key)?);
+            let y = URL_SAFE_NO_PAD.encode(y.to_vec_padded(size).map_err(invalid_key)?);
+            Ok((
+                DecodingKey::from_ec_components(&x, &y).map_err(invalid_key)?,
+                AlgorithmParameters::EllipticCurve(EllipticCurveKeyParameters {
+                    curve,
+                    x,
+                    y,
+                    ..Default::default()
+                }),
+            ))
+        }
+        (Algorithm::EdDSA, Id::ED25519) => {
+            let x = URL_SAFE_NO_PAD.encode(pkey.raw_public_key().map_err(invalid_key)?);
+            Ok((
+                DecodingKey::from_ed_components(&x).map_err(invalid_key)?,
+                AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                    curve: EllipticCurve::Ed25519,
+                    x,
+                    ..Default::default()
+                }),
+            ))
+        }
+        _ => Err(invalid_key(format!(
+            "the key type does not match {:?}",
+            algorithm
+        ))),
+    }
+}
+
+impl JwtKey {
+    pub fn hmac(algorithm: Algorithm, secret: &str) -> Self {
+        // The kid only has to tell keys apart, it reveals nothing of the secret.
+        let kid = URL_SAFE_NO_PAD.encode(&Sha256::digest(secret.as_bytes())[..12]);
+        Self {
+            kid,
+            algorithm,
+            encoding: Some(EncodingKey::from_secret(secret.as_bytes())),
+            decoding: DecodingKey::from_secret(secret.as_bytes()),
+            jwk: None,
+        }
+    }
+
+    /// Loads a PEM key for an asymmetric algorithm. A private key (PKCS#1,
+    /// SEC1 or PKCS#8) can sign, a public key (SPKI) only verifies. The kid
+    /// is the RFC 7638 thumbprint of the public key either way.
+    pub fn from_pem(algorithm: Algorithm, pem: &[u8]) -> Result<Self, AppError> {
+        let (encoding, (decoding, params)) = match PKey::private_key_from_pem(pem) {
+            Ok(pkey) => {
+                let parts = public_parts(algorithm, &pkey)?;
+                // jsonwebtoken only reads PKCS#8 for EC and Ed25519 keys.
+                let pkcs8 = pkey.private_key_to_pem_pkcs8().map_err(invalid_key)?;
+                let encoding = match pkey.id() {
+                    Id::RSA => EncodingKey::from_rsa_pem(&pkcs8),
+                    Id::EC => EncodingKey::from_ec_pem(&pkcs8),
+                    _ => EncodingKey::from_ed_pem(&pkcs8),
+                }
+                .map_err(invalid_key)?;
+                (Some(encoding), parts)
+            }
+            Err(_) => {
+                let pkey = PKey::public_key_from_pem(pem).map_err(invalid_key)?;
+                (None, public_parts(algorithm, &pkey)?)
+            }
+        };
+
+        let kid = thumbprint(&params);
+        let jwk = Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(
+                    format!("{:?}", algorithm).parse::<KeyAlgorithm>().map_err(invalid_key)?,
+                ),
+                key_id: Some(kid.clone()),
+                ..Default::default()
+            },
+            algorithm: params,
+        };
+
+        Ok(Self {
+            kid,
+            algorithm,
+            encoding,
+            decoding,
+            jwk: Some(jwk),
+        })
+    }
+
+    pub fn from_config(config: &JwtKeyConfig) -> Result<Self, AppError> {
+        let key = match (&config.key, &config.key_file) {
+            (Some(key), _) => key.expose_secret().clone(),
+            (None, Some(path)) => std::fs::read_to_string(path)
+                .map_err(|e| invalid_key(format!("cannot read {}: {}", path, e)))?,
+            (None, None) => return Err(invalid_key("neither key nor key_file is set")),
+        };
+        if is_hmac(config.algorithm) {
+            return Ok(Self::hmac(config.algorithm, key.trim()));
+        }
+        Self::from_pem(config.algorithm, key.as_bytes())
+    }
+
+    /// Generates a new key, returned with its PEM (or secret for HMAC).
+    pub fn generate(algorithm: Algorithm) -> Result<(Self, String), AppError> {
+        if is_hmac(algorithm) {
+            let secret = generate_opaque_token();
+            return Ok((Self::hmac(algorithm, &secret), secret));
+        }
+
+        let pkey = match algorithm {
+            Algorithm::ES256 | Algorithm::ES384 => {
+                let nid = match algorithm {
+                    Algorithm::ES256 => Nid::X9_62_PRIME256V1,
+                    _ => Nid::SECP384R1,
+                };
+                let group = EcGroup::from_curve_name(nid).map_err(invalid_key)?;
+                EcKey::generate(&group).and_then(PKey::from_ec_key)
+            }
+            Algorithm::EdDSA => PKey::generate_ed25519(),
+            _ => Rsa::generate(2048).and_then(PKey::from_rsa),
+        }
+        .map_err(invalid_key)?;
+
+        let pem = String::from_utf8(pkey.private_key_to_pem_pkcs8().map_err(invalid_key)?)
+            .map_err(invalid_key)?;
+        Ok((Self::from_pem(algorithm, pem.as_bytes())?, pem))
+    }
+}
+
+/// The active key signing new tokens, and the keys still accepted when
+/// verifying them. During a rotation the previous key stays here until the
+/// tokens it signed have expired, and the next one can be published before
+/// it starts signing.
+#[derive(Clone)]
+pub struct KeyRing {
+    active: JwtKey,
+    verification: Vec<JwtKey>,
+    issuer: String,
+    audience: String,
+}
+
+impl KeyRing {
+    pub fn new(
+        config: &AuthConfig,
+        active: JwtKey,
+        verification: Vec<JwtKey>,
+    ) -> Result<Self, AppError> {
+        if active.encoding.is_none() {
+            return Err(invalid_key("the active key must be a private key"));
+        }
+        Ok(Self {
+            active,
+            verification,
+            issuer: config.jwt_issuer.clone(),
+            audience: config.jwt_audience.clone(),
+        })
+    }
+
+    /// The access token keys described by the configuration.
+    pub fn from_config(config: &AuthConfig) -> Result<Self, AppError> {
+        let active = if is_hmac(config.jwt_algorithm) {
+            JwtKey::hmac(config.jwt_algorithm, config.jwt_access_secret.expose_secret())
+        } else {
+            JwtKey::from_config(&JwtKeyConfig {
+                algorithm: config.jwt_algorithm,
+                key: config.jwt_private_key.clone(),
+                key_file: config.jwt_private_key_file.clone(),
+            })?
+        };
+
+        let verification = config
+            .jwt_verification_keys
+            .values()
+            .map(JwtKey::from_config)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Self::new(config, active, verification)
+    }
+
+    pub fn active(&self) -> &JwtKey {
+        &self.active
+    }
+
+    fn find(&self, kid: Option<&str>) -> Option<&JwtKey> {
+        match kid {
+            Some(kid) => std::iter::once(&self.active)
+                .chain(&self.verification)
+                .find(|key| key.kid == kid),
+            // Tokens issued before kids were introduced.
+            None => Some(&self.active),
+        }
+    }
+
+    /// Public keys to publish at `/.well-known/jwks.json`.
+    pub fn jwks(&self) -> JwkSet {
+        JwkSet {
+            keys: std::iter::once(&self.active)
+                .chain(&self.verification)
+                .filter_map(|key| key.jwk.clone())
+                .collect(),
+        }
+    }
+}
+
+// RFC 7638: SHA-256 of the required members, in lexicographic order.
+fn thumbprint(params: &AlgorithmParameters) -> String {
+    let canonical = match params {
+        AlgorithmParameters::RSA(p) => format!(r#"{{"e":"{}","kty":"RSA","n":"{}"}}"#, p.e, p.n),
+        AlgorithmParameters::EllipticCurve(p) => format!(
+            r#"{{"crv":"{}","kty":"EC","x":"{}","y":"{}"}}"#,
+            match p.curve {
+                EllipticCurve::P384 => "P-384",
+                _ => "P-256",
+            },
+            p.x,
+            p.y
+        ),
+        AlgorithmParameters::OctetKeyPair(p) => {
+            format!(r#"{{"crv":"Ed25519","kty":"OKP","x":"{}"}}"#, p.x)
+        }
+        AlgorithmParameters::OctetKey(p) => format!(r#"{{"k":"{}","kty":"oct"}}"#, p.value),
+    };
+    URL_SAFE_NO_PAD.encode(Sha256::digest(canonical.as_bytes()))
+}
+
+impl Claims {
+    /// Fresh claims for `sub`, without session nor organization.
+    pub fn new(
+        keys: &KeyRing,
+        typ: TokenType,
+        sub: Uuid,
+        expires_in: &str,
+    ) -> Result<Self, AppError> {
+        let now = Utc::now();
+        let expiration = Duration::from_std(duration_str::parse(expires_in)?)
+            .map_err(|_| AppError::InternalServerError)?;
+        Ok(Self {
+            iss: keys.issuer.clone(),
+            aud: keys.audience.clone(),
+            sub,
+            exp: (now + expiration).timestamp(),
+            iat: now.timestamp(),
+            nbf: now.timestamp(),
+            jti: Uuid::new_v4(),
+            typ,
+            sid: None,
+            org: None,
+            roles: Vec::new(),
+        })
+    }
 }
 
 pub fn create_token(
+    keys: &KeyRing,
+    typ: TokenType,
     user_id: Uuid,
-    secret: &str,
     expires_in: &str,
 ) -> Result<String, AppError> {
-    let now = Utc::now();
-    let expiration = Duration::from_std(duration_str::parse(expires_in)?).map_err(|_| AppError::InternalServerError)?;
-    let claims = Claims {
-        sub: user_id,
-        exp: (now + expiration).timestamp(),
-        iat: now.timestamp(),
-        nbf: now.timestamp(),
-    };
-    let header = Header::new(jsonwebtoken::Algorithm::HS256);
-    encode(&header, &claims, &EncodingKey::from_secret(secret.as_ref()))
+    sign(keys, &Claims::new(keys, typ, user_id, expires_in)?)
+}
+
+/// Signs `claims` with the active key.
+pub fn sign(keys: &KeyRing, claims: &Claims) -> Result<String, AppError> {
+    let key = &keys.active;
+    let mut header = Header::new(key.algorithm);
+    header.kid = Some(key.kid.clone());
+    // KeyRing::new guarantees the active key can sign.
+    let encoding = key.encoding.as_ref().ok_or(AppError::InternalServerError)?;
+    encode(&header, claims, encoding)
         .map_err(AppError::Jwt)
 }
 
+/// Verifies a token with the key named by its `kid`, and that it was issued
+/// by this service, for this audience and for `typ`. The algorithm comes
+/// from the key, never from the token header.
 pub fn validate_token(
     token: &str,
-    secret: &str,
+    keys: &KeyRing,
+    typ: TokenType,
 ) -> Result<Claims, jsonwebtoken::errors::Error> {
-    let validation = Validation::new(jsonwebtoken::Algorithm::HS256);
-    decode::<Claims>(
+    let header = decode_header(token)?;
+    let key = keys
+        .find(header.kid.as_deref())
+        .ok_or(jsonwebtoken::errors::ErrorKind::InvalidToken)?;
+    let mut validation = Validation::new(key.algorithm);
+    validation.set_issuer(&[&keys.issuer]);
+    validation.set_audience(&[&keys.audience]);
+    validation.set_required_spec_claims(&["exp", "nbf", "iss", "aud", "sub"]);
+    validation.validate_nbf = true;
+    let claims = decode::<Claims>(
         token,
-        &DecodingKey::from_secret(secret.as_ref()),
+        &key.decoding,
         &validation,
-    )
-    .map(|data| data.claims)
+    )?
+    .claims;
+    if claims.typ != typ {
+        return Err(jsonwebtoken::errors::ErrorKind::InvalidToken.into());
+    }
+    Ok(claims)
 }
@@ -0,0 +1,196 @@
+use sqlx::{PgConnection, PgExecutor, PgPool};
+use uuid::Uuid;
+
+use crate::{
+    db,
+    errors::AppError,
+    models::organization::{Membership, Role},
+};
+
+// Who belongs to which organization, and which one a session acts on.
+
+pub async fn find(
+    executor: impl PgExecutor<'_>,
+    org_id: Uuid,
+    user_id: Uuid,
+) -> Result<Option<Membership>, AppError> {
+    Ok(sqlx::query_as!(
+        Membership,
+        r#"SELECT organization_id, user_id, role AS "role: Role", custom_role_id, created_at
+        FROM memberships WHERE organization_id = $1 AND user_id = $2"#,
+        org_id,
+        user_id
+    )
+    .fetch_optional(executor)
+    .await?)
+}
+
+/// The organization new sessions act on: the personal one, else the oldest.
+pub async fn default_for(pool: &PgPool, user_id: Uuid) -> Result<Option<Membership>, AppError> {
+    let mut tx = db::begin_cross_tenant(pool).await?;
+    let membership = oldest(&mut tx, user_id).await?;
+    tx.commit().await?;
+    Ok(membership)
+}
+
+async fn oldest(conn: &mut PgConnection, user_id: Uuid) -> Result<Option<Membership>, AppError> {
+    Ok(sqlx::query_as!(
+        Membership,
+        r#"SELECT m.organization_id, m.user_id, m.role AS "role: Role", m.custom_role_id, m.created_at
+        FROM memberships m JOIN organizations o ON o.id = m.organization_id
+        WHERE m.user_id = $1
+        ORDER BY o.personal_user_id IS NULL, m.created_at
+        LIMIT 1"#,
+        user_id
+    )
+    .fetch_optional(conn)
+    .await?)
+}
+
+/// `preferred` while the user still belongs to it, else the default one.
+pub async fn resolve_active(
+    pool: &PgPool,
+    user_id: Uuid,
+    preferred: Option<Uuid>,
+) -> Result<Option<Membership>, AppError> {
+    let mut tx = db::begin_cross_tenant(pool).await?;
+    let mut membership = None;
+    if let Some(org_id) = preferred {
+        membership = find(&mut *tx, org_id, user_id).await?;
+    }
+    if membership.is_none() {
+        membership = oldest(&mut tx, user_id).await?;
+    }
+    tx.commit().await?;
+    Ok(membership)
+}
+
+/// Creates the personal organization of a new account, in its transaction,
+/// which must be cross-tenant.
+pub async fn create_personal(conn: &mut PgConnection, user_id: Uuid) -> Result<(), AppError> {
+    sqlx::query!(
+        r#"WITH org AS (
+            INSERT INTO organizations (name, personal_user_id) VALUES ('Personal', $1) RETURNING id
+        )
+        INSERT INTO memberships (organization_id, user_id, role) SELECT id, $1, $2 FROM org"#,
+        user_id,
+        Role::Owner as Role
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+// Anyone can get an address there, they cannot vouch for an organization.
+const PUBLIC_EMAIL_DOMAINS: &[&str] = &[
+    "gmail.com",
+    "googlemail.com",
+    "outlook.com",
+    "hotmail.com",
+    "live.com",
+    "msn.com",
+    "yahoo.com",
+    "icloud.com",
+    "me.com",
+    "aol.com",
+    "proton.me",
+    "protonmail.com",
+    "gmx.com",
+    "yandex.com",
+];
+
+pub fn email_domain(email: &str) -> Option<String> {
+    email
+        .rsplit_once('@')
+        .map(|(_, domain)| domain.trim().to_ascii_lowercase())
+        .filter(|domain| !domain.is_empty())
+}
+
+pub fn is_public_email_domain(domain: &str) -> bool {
+    PUBLIC_EMAIL_DOMAINS.contains(&domain)
+}
+
+// TXT record of a domain proving that an organization may auto-join it.
+const VERIFICATION_RECORD_PREFIX: &str = "_saas-verification";
+
+pub struct DomainRecord {
+    pub name: String,
+    pub value: String,
+}
+
+pub fn verification_record(domain: &str, token: &str) -> DomainRecord {
+    DomainRecord {
+        name: format!("{}.{}", VERIFICATION_RECORD_PREFIX, domain),
+        value: format!("saas-verification={}", token),
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct DnsResponse {
+    #[serde(rename = "Answer", default)]
+    answer: Vec<DnsAnswer>,
+}
+
+#[derive(serde::Deserialize)]
+struct DnsAnswer {
+    data: String,
+}
+
+/// Whether the domain publishes `record`, asked to the DNS-over-HTTPS
+/// resolver rather than the system one, which may cache a missing record.
+pub async fn has_record(
+    http: &reqwest::Client,
+    resolver_url: &str,
+    record: &DomainRecord,
+) -> Result<bool, AppError> {
+    let response: DnsResponse = http
+        .get(resolver_url)
+        .query(&[("name", record.name.as_str()), ("type", "TXT")])
+        .header(reqwest::header::ACCEPT, "application/dns-json")
+        .send()
+        .await
+        .and_then(|res| res.error_for_status())
+        .map_err(dns_error)?
+        .json()
+        .await
+        .map_err(dns_error)?;
+
+    Ok(response
+        .answer
+        .iter()
+        .any(|answer| txt_value(&answer.data) == record.value))
+}
+
+// Resolvers quote the value, and split long ones into several strings.
+fn txt_value(data: &str) -> String {
+    if !data.contains('"') {
+        return data.to_string();
+    }
+    data.split('"').skip(1).step_by(2).collect()
+}
+
+fn dns_error(err: reqwest::Error) -> AppError {
+    tracing::error!("DNS lookup failed: {:?}", err);
+    AppError::InternalServerError
+}
+
+/// Adds the user to the organizations auto-joining the domain of their
+/// address, once it is verified. Called when an address gets verified, so
+/// that leaving such an organization sticks.
+pub async fn auto_join(pool: &PgPool, user_id: Uuid) -> Result<(), AppError> {
+    let mut tx = db::begin_cross_tenant(pool).await?;
+    sqlx::query!(
+        r#"INSERT INTO memberships (organization_id, user_id, role)
+        SELECT o.id, u.id, $2 FROM organizations o JOIN users u ON u.id = $1
+        WHERE u.email_verified_at IS NOT NULL
+            AND o.auto_join_domain = LOWER(SPLIT_PART(u.email, '@', 2))
+            AND o.auto_join_domain_verified_at IS NOT NULL
+        ON CONFLICT (organization_id, user_id) DO NOTHING"#,
+        user_id,
+        Role::Member as Role
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(())
+}
@@ -0,0 +1,192 @@
+use rand::{distributions::Uniform, Rng, RngCore};
+use redis::aio::MultiplexedConnection;
+use redis::AsyncCommands;
+use sqlx::PgPool;
+use totp_rs::{Algorithm, TOTP};
+use uuid::Uuid;
+
+use crate::{
+    auth::{crypto, single_use, webauthn},
+    config::AuthConfig,
+    errors::AppError,
+    models::mfa::MfaTotp,
+    utils::token::{generate_opaque_token, hash_token},
+};
+
+const TOTP_DIGITS: usize = 6;
+const TOTP_STEP: u64 = 30;
+const RECOVERY_CODE_COUNT: usize = 10;
+const CHALLENGE_TTL: u64 = 5 * 60;
+const CHALLENGE_MAX_ATTEMPTS: i64 = 5;
+
+pub fn new_totp_secret() -> Vec<u8> {
+    let mut secret = vec![0u8; 20];
+    rand::thread_rng().fill_bytes(&mut secret);
+    secret
+}
+
+pub fn totp(config: &AuthConfig, secret: Vec<u8>, account: &str) -> Result<TOTP, AppError> {
+    TOTP::new(
+        Algorithm::SHA1,
+        TOTP_DIGITS,
+        1,
+        TOTP_STEP,
+        secret,
+        Some(config.mfa_issuer.clone()),
+        account.to_string(),
+    )
+    .map_err(|e| {
+        tracing::error!("Invalid TOTP parameters: {:?}", e);
+        AppError::InternalServerError
+    })
+}
+
+pub async fn find_totp(pool: &PgPool, user_id: Uuid) -> Result<Option<MfaTotp>, AppError> {
+    Ok(sqlx::query_as!(
+        MfaTotp,
+        "SELECT * FROM mfa_totp WHERE user_id = $1",
+        user_id
+    )
+    .fetch_optional(pool)
+    .await?)
+}
+
+pub async fn totp_enabled(pool: &PgPool, user_id: Uuid) -> Result<bool, AppError> {
+    Ok(find_totp(pool, user_id)
+        .await?
+        .is_some_and(|totp| totp.confirmed_at.is_some()))
+}
+
+/// Second factors available to a user, empty when a password is enough.
+pub async fn methods(pool: &PgPool, user_id: Uuid) -> Result<Vec<&'static str>, AppError> {
+    let mut methods = Vec::new();
+    if totp_enabled(pool, user_id).await? {
+        methods.extend(["totp", "recovery_code"]);
+    }
+    if !webauthn::credentials_for(pool, user_id).await?.is_empty() {
+        methods.push("webauthn");
+    }
+    Ok(methods)
+}
+
+/// Checks a code against the stored secret. A code is accepted only once so
+/// that an observed code cannot be replayed within its validity window.
+pub async fn check_code(
+    conn: &mut MultiplexedConnection,
+    config: &AuthConfig,
+    record: &MfaTotp,
+    account: &str,
+    code: &str,
+) -> Result<bool, AppError> {
+    let secret = crypto::decrypt(config, &record.secret_encrypted)?;
+    let valid = totp(config, secret, account)?
+        .check_current(code.trim())
+        .map_err(|_| AppError::InternalServerError)?;
+    if !valid {
+        return Ok(false);
+    }
+
+    let key = format!("totp_used:{}:{}", record.user_id, code.trim());
+    single_use::mark(conn, &key, TOTP_STEP * 3).await
+}
+
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// Replaces the recovery codes of a user and returns the new ones in clear,
+/// they are never shown again.
+pub async fn regenerate_recovery_codes(pool: &PgPool, user_id: Uuid) -> Result<Vec<String>, AppError> {
+    let alphabet = b"abcdefghjkmnpqrstuvwxyz23456789";
+    let dist = Uniform::from(0..alphabet.len());
+    let codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let raw: String = rand::thread_rng()
+                .sample_iter(&dist)
+                .take(10)
+                .map(|i| alphabet[i] as char)
+                .collect();
+            format!("{}-{}", &raw[..5], &raw[5..])
+        })
+        .collect();
+    let hashes: Vec<String> = codes
+        .iter()
+        .map(|code| hash_token(&normalize_recovery_code(code)))
+        .collect();
+
+    let mut tx = pool.begin().await?;
+    sqlx::query!("DELETE FROM mfa_recovery_codes WHERE user_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!(
+        "INSERT INTO mfa_recovery_codes (user_id, code_hash) SELECT $1, UNNEST($2::VARCHAR[])",
+        user_id,
+        &hashes
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(codes)
+}
+
+pub async fn use_recovery_code(pool: &PgPool, user_id: Uuid, code: &str) -> Result<bool, AppError> {
+    let used = sqlx::query!(
+        "UPDATE mfa_recovery_codes SET used_at = NOW() WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL",
+        user_id,
+        hash_token(&normalize_recovery_code(code))
+    )
+    .execute(pool)
+    .await?;
+    Ok(used.rows_affected() > 0)
+}
+
+// Between a successful password check and the second factor, the client only
+// holds a challenge token. It survives a few wrong codes, not more.
+
+fn challenge_key(token: &str) -> String {
+    format!("mfa_challenge:{}", hash_token(token))
+}
+
+fn attempts_key(token: &str) -> String {
+    format!("mfa_challenge_attempts:{}", hash_token(token))
+}
+
+pub async fn start_challenge(
+    conn: &mut MultiplexedConnection,
+    user_id: Uuid,
+) -> Result<String, AppError> {
+    let token = generate_opaque_token();
+    conn.set_ex::<_, _, ()>(challenge_key(&token), user_id.to_string(), CHALLENGE_TTL)
+        .await?;
+    Ok(token)
+}
+
+pub async fn challenge_user(
+    conn: &mut MultiplexedConnection,
+    token: &str,
+) -> Result<Uuid, AppError> {
+    let user_id: Option<String> = conn.get(challenge_key(token)).await?;
+    Ok(user_id
+        .ok_or_else(|| AppError::Unauthorized("Invalid or expired MFA challenge".to_string()))?
+        .parse()?)
+}
+
+pub async fn fail_challenge(conn: &mut MultiplexedConnection, token: &str) -> Result<(), AppError> {
+    let attempts: i64 = conn.incr(attempts_key(token), 1).await?;
+    conn.expire::<_, ()>(attempts_key(token), CHALLENGE_TTL as i64)
+        .await?;
+    if attempts >= CHALLENGE_MAX_ATTEMPTS {
+        finish_challenge(conn, token).await?;
+    }
+    Ok(())
+}
+
+pub async fn finish_challenge(conn: &mut MultiplexedConnection, token: &str) -> Result<(), AppError> {
+    conn.del::<_, ()>(&[challenge_key(token), attempts_key(token)])
+        .await?;
+    Ok(())
+}
@@ -1,3 +1,20 @@
 
+pub mod admin;
+pub mod api_key;
+pub mod cookies;
+pub mod crypto;
+pub mod csrf;
+pub mod denylist;
+pub mod extractor;
+pub mod invitation;
 pub mod jwt;
+pub mod membership;
+pub mod mfa;
+pub mod oauth;
 pub mod password;
+pub mod permission;
+pub mod personal_token;
+pub mod principal;
+pub mod session;
+pub mod single_use;
+pub mod webauthn;
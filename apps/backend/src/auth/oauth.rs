@@ -0,0 +1,350 @@
+use reqwest::{header, Url};
+use secrecy::{ExposeSecret, Secret};
+use serde::Deserialize;
+use serde_json::Value;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    auth::{membership, password},
+    config::{OAuthProviderConfig, OAuthProviderKind},
+    db,
+    errors::AppError,
+    models::user::User,
+    utils::token::{generate_opaque_token, hash_token},
+};
+
+const GITHUB_AUTHORIZATION_ENDPOINT: &str = "https://github.com/login/oauth/authorize";
+const GITHUB_TOKEN_ENDPOINT: &str = "https://github.com/login/oauth/access_token";
+const GITHUB_USERINFO_ENDPOINT: &str = "https://api.github.com/user";
+
+pub struct Endpoints {
+    pub authorization: String,
+    pub token: String,
+    pub userinfo: String,
+}
+
+#[derive(Deserialize)]
+struct Discovery {
+    authorization_endpoint: String,
+    token_endpoint: String,
+    userinfo_endpoint: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+}
+
+#[derive(Deserialize)]
+struct GithubEmail {
+    email: String,
+    primary: bool,
+    verified: bool,
+}
+
+/// The user as described by the provider.
+pub struct ExternalIdentity {
+    pub subject: String,
+    pub email: Option<String>,
+    pub email_verified: bool,
+    pub name: Option<String>,
+}
+
+fn provider_error(err: reqwest::Error) -> AppError {
+    AppError::Provider(err.to_string())
+}
+
+/// Resolves the endpoints of a provider, explicit settings taking precedence
+/// over the OIDC discovery document.
+pub async fn endpoints(
+    http: &reqwest::Client,
+    provider: &OAuthProviderConfig,
+) -> Result<Endpoints, AppError> {
+    let (authorization, token, userinfo) = match provider.kind {
+        OAuthProviderKind::Github => (
+            Some(GITHUB_AUTHORIZATION_ENDPOINT.to_string()),
+            Some(GITHUB_TOKEN_ENDPOINT.to_string()),
+            Some(GITHUB_USERINFO_ENDPOINT.to_string()),
+        ),
+        OAuthProviderKind::Oidc => (None, None, None),
+    };
+    let authorization = provider.authorization_endpoint.clone().or(authorization);
+    let token = provider.token_endpoint.clone().or(token);
+    let userinfo = provider.userinfo_endpoint.clone().or(userinfo);
+
+    if let (Some(authorization), Some(token), Some(userinfo)) = (&authorization, &token, &userinfo) {
+        return Ok(Endpoints {
+            authorization: authorization.clone(),
+            token: token.clone(),
+            userinfo: userinfo.clone(),
+        });
+    }
+
+    let issuer = provider.issuer.as_deref().ok_or_else(|| {
+        AppError::Provider("Provider has neither an issuer nor explicit endpoints".to_string())
+    })?;
+    let discovery: Discovery = http
+        .get(format!(
+            "{}/.well-known/openid-configuration",
+            issuer.trim_end_matches('/')
+        ))
+        .send()
+        .await
+        .and_then(|res| res.error_for_status())
+        .map_err(provider_error)?
+        .json()
+        .await
+        .map_err(provider_error)?;
+
+    Ok(Endpoints {
+        authorization: authorization.unwrap_or(discovery.authorization_endpoint),
+        token: token.unwrap_or(discovery.token_endpoint),
+        userinfo: userinfo
+            .or(discovery.userinfo_endpoint)
+            .ok_or_else(|| AppError::Provider("Provider has no userinfo endpoint".to_string()))?,
+    })
+}
+
+pub fn new_code_verifier() -> String {
+    generate_opaque_token()
+}
+
+/// Builds the URL the browser is sent to, with an S256 PKCE challenge.
+pub fn authorization_url(
+    provider: &OAuthProviderConfig,
+    endpoints: &Endpoints,
+    redirect_uri: &str,
+    state: &str,
+    code_verifier: &str,
+) -> Result<String, AppError> {
+    let scopes = provider.scopes.as_deref().unwrap_or(match provider.kind {
+        OAuthProviderKind::Oidc => "openid email profile",
+        OAuthProviderKind::Github => "read:user user:email",
+    });
+
+    let mut url = Url::parse(&endpoints.authorization)
+        .map_err(|e| AppError::Provider(format!("Invalid authorization endpoint: {}", e)))?;
+    url.query_pairs_mut()
+        .append_pair("response_type", "code")
+        .append_pair("client_id", &provider.client_id)
+        .append_pair("redirect_uri", redirect_uri)
+        .append_pair("scope", scopes)
+        .append_pair("state", state)
+        .append_pair("code_challenge", &hash_token(code_verifier))
+        .append_pair("code_challenge_method", "S256");
+    Ok(url.into())
+}
+
+/// Exchanges an authorization code for an access token.
+pub async fn exchange_code(
+    http: &reqwest::Client,
+    provider: &OAuthProviderConfig,
+    endpoints: &Endpoints,
+    code: &str,
+    redirect_uri: &str,
+    code_verifier: &str,
+) -> Result<Secret<String>, AppError> {
+    let token: TokenResponse = http
+        .post(&endpoints.token)
+        .header(header::ACCEPT, "application/json")
+        .form(&[
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", redirect_uri),
+            ("client_id", &provider.client_id),
+            ("client_secret", provider.client_secret.expose_secret()),
+            ("code_verifier", code_verifier),
+        ])
+        .send()
+        .await
+        .and_then(|res| res.error_for_status())
+        .map_err(provider_error)?
+        .json()
+        .await
+        .map_err(provider_error)?;
+    Ok(Secret::new(token.access_token))
+}
+
+async fn get_json<T: serde::de::DeserializeOwned>(
+    http: &reqwest::Client,
+    url: &str,
+    access_token: &Secret<String>,
+) -> Result<T, AppError> {
+    http.get(url)
+        .bearer_auth(access_token.expose_secret())
+        .header(header::ACCEPT, "application/json")
+        .send()
+        .await
+        .and_then(|res| res.error_for_status())
+        .map_err(provider_error)?
+        .json()
+        .await
+        .map_err(provider_error)
+}
+
+pub async fn fetch_identity(
+    http: &reqwest::Client,
+    provider: &OAuthProviderConfig,
+    endpoints: &Endpoints,
+    access_token: &Secret<String>,
+) -> Result<ExternalIdentity, AppError> {
+    let info: Value = get_json(http, &endpoints.userinfo, access_token).await?;
+    let name = info["name"].as_str().map(str::to_string);
+
+    match provider.kind {
+        OAuthProviderKind::Oidc => {
+            let subject = info["sub"]
+                .as_str()
+                .ok_or_else(|| AppError::Provider("Userinfo without subject".to_string()))?;
+            // Some providers send the flag as a string.
+            let email_verified = info["email_verified"].as_bool() == Some(true)
+                || info["email_verified"].as_str() == Some("true");
+            Ok(ExternalIdentity {
+                subject: subject.to_string(),
+                email: info["email"].as_str().map(str::to_string),
+                email_verified,
+                name,
+            })
+        }
+        OAuthProviderKind::Github => {
+            let subject = info["id"]
+                .as_i64()
+                .ok_or_else(|| AppError::Provider("GitHub user without id".to_string()))?;
+            // The profile email is the public one, which GitHub does not
+            // vouch for. The primary verified address is listed separately.
+            let emails: Vec<GithubEmail> =
+                get_json(http, &format!("{}/emails", endpoints.userinfo), access_token).await?;
+            let email = emails
+                .into_iter()
+                .find(|e| e.primary && e.verified)
+                .map(|e| e.email);
+            Ok(ExternalIdentity {
+                subject: subject.to_string(),
+                email_verified: email.is_some(),
+                email,
+                name,
+            })
+        }
+    }
+}
+
+/// Finds the user behind an external identity, linking or creating one when
+/// the identity is new. `link_to` is set when a signed-in user adds a provider.
+pub async fn resolve_user(
+    pool: &PgPool,
+    provider: &str,
+    identity: &ExternalIdentity,
+    link_to: Option<Uuid>,
+) -> Result<User, AppError> {
+    let linked = sqlx::query_scalar!(
+        r#"UPDATE user_identities SET email = $3, last_used_at = NOW()
+        WHERE provider = $1 AND subject = $2
+        RETURNING user_id"#,
+        provider,
+        identity.subject,
+        identity.email
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    let user_id = match (linked, link_to) {
+        (Some(user_id), Some(link_to)) if user_id != link_to => {
+            return Err(AppError::Conflict(
+                "This account is already linked to another user".to_string(),
+            ))
+        }
+        (Some(user_id), _) => user_id,
+        (None, Some(link_to)) => {
+            insert_identity(pool, link_to, provider, identity).await?;
+            link_to
+        }
+        (None, None) => return sign_up(pool, provider, identity).await,
+    };
+
+    sqlx::query_as!(User, "SELECT * FROM users WHERE id = $1", user_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))
+}
+
+async fn insert_identity(
+    pool: &PgPool,
+    user_id: Uuid,
+    provider: &str,
+    identity: &ExternalIdentity,
+) -> Result<(), AppError> {
+    sqlx::query!(
+        r#"INSERT INTO user_identities (user_id, provider, subject, email, last_used_at)
+        VALUES ($1, $2, $3, $4, NOW())"#,
+        user_id,
+        provider,
+        identity.subject,
+        identity.email
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+async fn sign_up(
+    pool: &PgPool,
+    provider: &str,
+    identity: &ExternalIdentity,
+) -> Result<User, AppError> {
+    let email = identity.email.as_deref().ok_or_else(|| {
+        AppError::BadRequest("The provider did not share an email address".to_string())
+    })?;
+
+    let existing = sqlx::query_as!(User, "SELECT * FROM users WHERE email = $1", email)
+        .fetch_optional(pool)
+        .await?;
+
+    if let Some(user) = existing {
+        // Both sides must vouch for the address, otherwise whoever registered
+        // it first could take over the other account.
+        if !identity.email_verified || user.email_verified_at.is_none() {
+            return Err(AppError::Conflict(
+                "An account already exists for this email, sign in to link this provider"
+                    .to_string(),
+            ));
+        }
+        insert_identity(pool, user.id, provider, identity).await?;
+        return Ok(user);
+    }
+
+    // The account gets an unusable random password, a real one can be set
+    // through the password reset flow.
+    let password_hash = password::hash_password(Secret::new(generate_opaque_token()))
+        .await
+        .map_err(AppError::Password)?;
+
+    let mut tx = db::begin_cross_tenant(pool).await?;
+    let user = sqlx::query_as!(
+        User,
+        r#"INSERT INTO users (email, password_hash, name, email_verified_at)
+        VALUES ($1, $2, $3, CASE WHEN $4 THEN NOW() END)
+        RETURNING *"#,
+        email,
+        password_hash,
+        identity.name,
+        identity.email_verified
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    sqlx::query!(
+        r#"INSERT INTO user_identities (user_id, provider, subject, email, last_used_at)
+        VALUES ($1, $2, $3, $4, NOW())"#,
+        user.id,
+        provider,
+        identity.subject,
+        identity.email
+    )
+    .execute(&mut *tx)
+    .await?;
+    membership::create_personal(&mut tx, user.id).await?;
+    tx.commit().await?;
+    membership::auto_join(pool, user.id).await?;
+
+    Ok(user)
+}
@@ -5,6 +5,10 @@ use argon2::{
 use secrecy::{ExposeSecret, Secret};
 use thiserror::Error;
 
+use crate::errors::AppError;
+
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+
 #[derive(Error, Debug)]
 pub enum PasswordError {
     #[error("Argon2 error")]
@@ -32,3 +36,13 @@ pub async fn verify_password(
         .map_err(PasswordError::Argon2Error)
         .is_ok())
 }
+
+pub fn validate_strength(password: &Secret<String>) -> Result<(), AppError> {
+    if password.expose_secret().chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(AppError::BadRequest(format!(
+            "Password must be at least {} characters",
+            MIN_PASSWORD_LENGTH
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,336 @@
+use std::{
+    convert::Infallible,
+    fmt,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts, RawPathParams, Request},
+    http::request::Parts,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tower::{Layer, Service};
+use uuid::Uuid;
+
+use crate::{
+    auth::principal::Principal,
+    db,
+    errors::AppError,
+    models::organization::Role,
+    state::AppState,
+};
+
+// What can be done inside an organization. Members get the permissions of
+// their built-in role plus those of their custom role, if any; service
+// accounts get the scopes of their API key, which are permission names.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Permission {
+    #[serde(rename = "org:read")]
+    OrgRead,
+    #[serde(rename = "org:update")]
+    OrgUpdate,
+    #[serde(rename = "org:delete")]
+    OrgDelete,
+    #[serde(rename = "members:read")]
+    MembersRead,
+    #[serde(rename = "members:invite")]
+    MembersInvite,
+    #[serde(rename = "members:manage")]
+    MembersManage,
+    #[serde(rename = "roles:manage")]
+    RolesManage,
+    #[serde(rename = "billing:read")]
+    BillingRead,
+    #[serde(rename = "billing:write")]
+    BillingWrite,
+    #[serde(rename = "service_accounts:read")]
+    ServiceAccountsRead,
+    #[serde(rename = "service_accounts:write")]
+    ServiceAccountsWrite,
+}
+
+use Permission::*;
+
+impl Permission {
+    pub const ALL: &'static [Permission] = &[
+        OrgRead,
+        OrgUpdate,
+        OrgDelete,
+        MembersRead,
+        MembersInvite,
+        MembersManage,
+        RolesManage,
+        BillingRead,
+        BillingWrite,
+        ServiceAccountsRead,
+        ServiceAccountsWrite,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrgRead => "org:read",
+            OrgUpdate => "org:update",
+            OrgDelete => "org:delete",
+            MembersRead => "members:read",
+            MembersInvite => "members:invite",
+            MembersManage => "members:manage",
+            RolesManage => "roles:manage",
+            BillingRead => "billing:read",
+            BillingWrite => "billing:write",
+            ServiceAccountsRead => "service_accounts:read",
+            ServiceAccountsWrite => "service_accounts:write",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Permission> {
+        Self::ALL.iter().copied().find(|p| p.as_str() == name)
+    }
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Role {
+    pub fn permissions(&self) -> &'static [Permission] {
+        match self {
+            // Owners hold every permission.
+            Role::Owner => Permission::ALL,
+            Role::Admin => &[
+                OrgRead,
+                OrgUpdate,
+                MembersRead,
+                MembersInvite,
+                MembersManage,
+                RolesManage,
+                BillingRead,
+                ServiceAccountsRead,
+                ServiceAccountsWrite,
+            ],
+            Role::Member => &[OrgRead, MembersRead],
+            Role::Billing => &[OrgRead, MembersRead, BillingRead, BillingWrite],
+        }
+    }
+}
+
+/// Names that are not permissions, for validation errors.
+pub fn unknown(names: &[String]) -> Option<&String> {
+    names.iter().find(|name| Permission::parse(name).is_none())
+}
+
+/// What the principal of the current request may do in an organization: the
+/// one in the `org_id` path parameter, else the active organization of the
+/// session or the one owning the service account.
+///
+/// Put on routes with `RequirePermission`, or taken by handlers whose checks
+/// depend on the request.
+#[derive(Debug, Clone)]
+pub struct OrgAccess {
+    pub org_id: Uuid,
+    // Built-in role of a member, none for service accounts.
+    pub role: Option<Role>,
+    pub permissions: Vec<Permission>,
+}
+
+impl OrgAccess {
+    pub fn has(&self, permission: Permission) -> bool {
+        self.permissions.contains(&permission)
+    }
+
+    pub fn require(&self, permission: Permission) -> Result<(), AppError> {
+        if !self.has(permission) {
+            return Err(AppError::Forbidden(format!(
+                "Missing permission: {}",
+                permission
+            )));
+        }
+        Ok(())
+    }
+
+    /// Permissions can only be handed out by someone holding them.
+    pub fn require_all<I>(&self, permissions: I) -> Result<(), AppError>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        for name in permissions {
+            let name = name.as_ref();
+            match Permission::parse(name) {
+                Some(permission) => self.require(permission)?,
+                None => return Err(AppError::BadRequest(format!("Unknown permission: {}", name))),
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn resolve(
+        pool: &PgPool,
+        principal: &Principal,
+        org_id: Uuid,
+    ) -> Result<OrgAccess, AppError> {
+        // Organizations are invisible to outsiders.
+        let not_found = || AppError::NotFound("Organization not found".to_string());
+
+        match principal {
+            Principal::User(auth) => {
+                let mut tx = db::begin_for_org(pool, org_id).await?;
+                let membership = sqlx::query!(
+                    r#"SELECT m.role AS "role: Role", r.permissions AS "custom_permissions?"
+                    FROM memberships m LEFT JOIN org_roles r ON r.id = m.custom_role_id
+                    WHERE m.organization_id = $1 AND m.user_id = $2"#,
+                    org_id,
+                    auth.user.id
+                )
+                .fetch_optional(&mut *tx)
+                .await?
+                .ok_or_else(not_found)?;
+                tx.commit().await?;
+
+                let custom = membership.custom_permissions.unwrap_or_default();
+                let permissions = Permission::ALL
+                    .iter()
+                    .copied()
+                    .filter(|p| {
+                        membership.role.permissions().contains(p)
+                            || custom.iter().any(|name| name == p.as_str())
+                    })
+                    // Personal access tokens only act within their scopes.
+                    .filter(|p| auth.require_scope(p.as_str()).is_ok())
+                    .collect();
+
+                Ok(OrgAccess {
+                    org_id,
+                    role: Some(membership.role),
+                    permissions,
+                })
+            }
+            Principal::Service(service) => {
+                if service.service_account.organization_id != org_id {
+                    return Err(not_found());
+                }
+                Ok(OrgAccess {
+                    org_id,
+                    role: None,
+                    permissions: service
+                        .scopes
+                        .iter()
+                        .filter_map(|scope| Permission::parse(scope))
+                        .collect(),
+                })
+            }
+        }
+    }
+}
+
+async fn target_org(parts: &mut Parts, principal: &Principal) -> Result<Uuid, AppError> {
+    let params = RawPathParams::from_request_parts(parts, &())
+        .await
+        .map_err(|_| AppError::InternalServerError)?;
+    if let Some((_, org_id)) = params.iter().find(|(name, _)| *name == "org_id") {
+        return org_id
+            .parse()
+            .map_err(|_| AppError::NotFound("Organization not found".to_string()));
+    }
+
+    let active = match principal {
+        Principal::User(auth) => auth.require_session()?.org,
+        Principal::Service(service) => Some(service.service_account.organization_id),
+    };
+    active.ok_or_else(|| AppError::BadRequest("No active organization".to_string()))
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for OrgAccess
+where
+    AppState: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        if let Some(access) = parts.extensions.get::<OrgAccess>() {
+            return Ok(access.clone());
+        }
+
+        let principal = Principal::from_request_parts(parts, state).await?;
+        let org_id = target_org(parts, &principal).await?;
+        let access = OrgAccess::resolve(&AppState::from_ref(state).pool, &principal, org_id).await?;
+        parts.extensions.insert(access.clone());
+        Ok(access)
+    }
+}
+
+/// Rejects requests whose principal lacks `permission` in the organization
+/// of the route, e.g.
+/// `post(create_invitation.layer(RequirePermission::new(state, MembersInvite)))`.
+#[derive(Clone)]
+pub struct RequirePermission {
+    state: AppState,
+    permission: Permission,
+}
+
+impl RequirePermission {
+    pub fn new(state: AppState, permission: Permission) -> Self {
+        Self { state, permission }
+    }
+}
+
+impl<S> Layer<S> for RequirePermission {
+    type Service = RequirePermissionService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequirePermissionService {
+            inner,
+            state: self.state.clone(),
+            permission: self.permission,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RequirePermissionService<S> {
+    inner: S,
+    state: AppState,
+    permission: Permission,
+}
+
+impl<S> Service<Request> for RequirePermissionService<S>
+where
+    S: Service<Request, Response = Response, Error = Infallible> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Response, Infallible>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        // The clone may not be ready, keep the one that was polled.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let state = self.state.clone();
+        let permission = self.permission;
+
+        Box::pin(async move {
+            let (mut parts, body) = request.into_parts();
+            let allowed = OrgAccess::from_request_parts(&mut parts, &state)
+                .await
+                .and_then(|access| access.require(permission));
+            match allowed {
+                Ok(()) => inner.call(Request::from_parts(parts, body)).await,
+                Err(err) => Ok(err.into_response()),
+            }
+        })
+    }
+}
@@ -0,0 +1,42 @@
+use sqlx::PgPool;
+
+use crate::{
+    errors::AppError,
+    models::personal_token::PersonalAccessToken,
+    utils::token::{generate_prefixed_token, hash_token, PrefixedToken},
+};
+
+// Long-lived bearer credentials for scripts, limited to their scopes.
+
+/// Marks personal access tokens, so that they are told apart from JWTs and
+/// recognized by secret scanners.
+pub const PREFIX: &str = "pat_";
+
+pub const PROFILE_READ: &str = "profile:read";
+pub const PROFILE_WRITE: &str = "profile:write";
+
+pub const SCOPES: &[&str] = &[PROFILE_READ, PROFILE_WRITE];
+
+pub fn generate() -> PrefixedToken {
+    generate_prefixed_token(PREFIX)
+}
+
+pub fn is_personal_token(token: &str) -> bool {
+    token.starts_with(PREFIX)
+}
+
+/// The live token matching `token`, recording its use.
+pub async fn authenticate(
+    pool: &PgPool,
+    token: &str,
+) -> Result<Option<PersonalAccessToken>, AppError> {
+    Ok(sqlx::query_as!(
+        PersonalAccessToken,
+        r#"UPDATE personal_access_tokens SET last_used_at = NOW()
+        WHERE token_hash = $1 AND (expires_at IS NULL OR expires_at > NOW())
+        RETURNING *"#,
+        hash_token(token)
+    )
+    .fetch_optional(pool)
+    .await?)
+}
@@ -0,0 +1,144 @@
+use std::net::{IpAddr, SocketAddr};
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRef, FromRequestParts, Request, State},
+    http::{header, request::Parts},
+    middleware::Next,
+    response::Response,
+};
+use uuid::Uuid;
+
+use crate::{
+    auth::{
+        api_key,
+        extractor::{self, AuthUser},
+    },
+    config::ServerConfig,
+    db,
+    errors::AppError,
+    models::service_account::ServiceAccount,
+    state::AppState,
+};
+
+/// Who is behind the current request: a user, through a session or a
+/// personal access token, or a service account through one of its API keys
+/// sent as a bearer token.
+///
+/// Routes open to integrations take this instead of `AuthUser` and authorize
+/// both kinds the same way, with `require_scope` or `OrgAccess`.
+#[derive(Debug, Clone)]
+pub enum Principal {
+    User(Box<AuthUser>),
+    Service(ServicePrincipal),
+}
+
+#[derive(Debug, Clone)]
+pub struct ServicePrincipal {
+    pub service_account: ServiceAccount,
+    pub key_id: Uuid,
+    pub scopes: Vec<String>,
+}
+
+impl Principal {
+    pub fn require_scope(&self, scope: &str) -> Result<(), AppError> {
+        match self {
+            Principal::User(auth) => auth.require_scope(scope),
+            Principal::Service(service) if service.scopes.iter().any(|s| s == scope) => Ok(()),
+            Principal::Service(_) => Err(AppError::Forbidden(format!(
+                "API key is missing the {} scope",
+                scope
+            ))),
+        }
+    }
+}
+
+/// Address of the client, as told by the reverse proxy when there is one.
+pub(crate) fn client_ip(parts: &Parts, config: &ServerConfig) -> Option<IpAddr> {
+    if config.behind_proxy {
+        // Earlier entries come from the client and can be forged.
+        return parts
+            .headers
+            .get("x-forwarded-for")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.rsplit(',').next())
+            .and_then(|v| v.trim().parse().ok());
+    }
+
+    parts
+        .extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip())
+}
+
+async fn authenticate_api_key(
+    parts: &Parts,
+    state: &AppState,
+    key: &str,
+) -> Result<ServicePrincipal, AppError> {
+    // The key tells which organization it belongs to.
+    let mut tx = db::begin_cross_tenant(&state.pool).await?;
+    let record = api_key::find(&mut *tx, key)
+        .await?
+        .ok_or_else(|| AppError::Unauthorized("Invalid API key".to_string()))?;
+
+    if !api_key::is_allowed_from(&record, client_ip(parts, &state.config.server)) {
+        return Err(AppError::Forbidden(
+            "API key cannot be used from this address".to_string(),
+        ));
+    }
+
+    let service_account = api_key::record_use(&mut *tx, record.id).await?;
+    tx.commit().await?;
+
+    Ok(ServicePrincipal {
+        service_account,
+        key_id: record.id,
+        scopes: record.scopes,
+    })
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Principal
+where
+    AppState: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        if let Some(principal) = parts.extensions.get::<Principal>() {
+            return Ok(principal.clone());
+        }
+
+        let api_key = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .map(str::trim)
+            .filter(|t| api_key::is_api_key(t))
+            .map(str::to_string);
+
+        let principal = match api_key {
+            Some(key) => {
+                let state = AppState::from_ref(state);
+                Principal::Service(authenticate_api_key(parts, &state, &key).await?)
+            }
+            None => Principal::User(Box::new(extractor::authenticated(parts, state).await?)),
+        };
+        parts.extensions.insert(principal.clone());
+        Ok(principal)
+    }
+}
+
+/// Like `require_auth`, for routes that also accept API keys.
+pub async fn require_principal(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let (mut parts, body) = request.into_parts();
+    Principal::from_request_parts(&mut parts, &state).await?;
+    Ok(next.run(Request::from_parts(parts, body)).await)
+}
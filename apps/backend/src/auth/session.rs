@@ -0,0 +1,499 @@
+use std::collections::HashMap;
+
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts},
+    http::{header, request::Parts},
+};
+use chrono::{DateTime, Utc};
+use redis::aio::MultiplexedConnection;
+use redis::AsyncCommands;
+use secrecy::ExposeSecret;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    auth::{denylist, jwt, principal},
+    config::{AuthConfig, TokenDelivery},
+    errors::AppError,
+    models::organization::Membership,
+    state::AppState,
+};
+
+// Every refresh token belongs to a family started at login, and a family is
+// what users see as a session. Refreshing rotates the token inside its
+// family; presenting a token that has already been rotated means it leaked,
+// so the whole family is revoked, along with the access tokens issued to it.
+
+fn token_key(jti: &Uuid) -> String {
+    format!("refresh_token:{}", jti)
+}
+
+fn used_key(jti: &Uuid) -> String {
+    format!("refresh_token_used:{}", jti)
+}
+
+fn family_key(family_id: &Uuid) -> String {
+    format!("refresh_family:{}", family_id)
+}
+
+// `jti` => `exp` of the access tokens issued to a family.
+fn family_access_key(family_id: &Uuid) -> String {
+    format!("refresh_family_access:{}", family_id)
+}
+
+fn user_sessions_key(user_id: &Uuid) -> String {
+    format!("user_sessions:{}", user_id)
+}
+
+/// Device information recorded when a session is created or refreshed, and
+/// how the client wants its tokens: the `X-Token-Delivery` header, else the
+/// setting of the client named by `X-Client-Id`, else cookies.
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+    pub delivery: TokenDelivery,
+}
+
+fn token_delivery(parts: &Parts, config: &AuthConfig) -> Result<TokenDelivery, AppError> {
+    let header = |name: &str| parts.headers.get(name).and_then(|v| v.to_str().ok());
+
+    if let Some(delivery) = header("x-token-delivery") {
+        return match delivery.trim().to_ascii_lowercase().as_str() {
+            "cookie" => Ok(TokenDelivery::Cookie),
+            "body" => Ok(TokenDelivery::Body),
+            _ => Err(AppError::BadRequest(
+                "X-Token-Delivery must be cookie or body".to_string(),
+            )),
+        };
+    }
+
+    Ok(header("x-client-id")
+        .and_then(|id| config.clients.get(&id.trim().to_ascii_lowercase()))
+        .map(|client| client.token_delivery)
+        .unwrap_or_default())
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ClientInfo
+where
+    AppState: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let user_agent = parts
+            .headers
+            .get(header::USER_AGENT)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+
+        let state = AppState::from_ref(state);
+        let ip = principal::client_ip(parts, &state.config.server).map(|ip| ip.to_string());
+
+        let delivery = token_delivery(parts, &state.config.auth)?;
+
+        Ok(Self {
+            user_agent,
+            ip,
+            delivery,
+        })
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Session {
+    pub id: Uuid,
+    pub name: Option<String>,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub current: bool,
+}
+
+impl Session {
+    fn from_hash(id: Uuid, mut fields: HashMap<String, String>, current: Option<Uuid>) -> Self {
+        let timestamp = |v: Option<String>| {
+            v.and_then(|v| DateTime::parse_from_rfc3339(&v).ok())
+                .map(|v| v.with_timezone(&Utc))
+        };
+        Self {
+            id,
+            name: fields.remove("name"),
+            user_agent: fields.remove("user_agent"),
+            ip: fields.remove("ip"),
+            created_at: timestamp(fields.remove("created_at")),
+            last_used_at: timestamp(fields.remove("last_used_at")),
+            current: current == Some(id),
+        }
+    }
+}
+
+pub fn refresh_ttl(config: &AuthConfig) -> Result<u64, AppError> {
+    Ok(duration_str::parse(&config.jwt_refresh_expires_in)?.as_secs())
+}
+
+/// Starts a session and returns its id with its first refresh token.
+pub async fn start_family(
+    conn: &mut MultiplexedConnection,
+    config: &AuthConfig,
+    user_id: Uuid,
+    client: &ClientInfo,
+    org_id: Option<Uuid>,
+) -> Result<(Uuid, String), AppError> {
+    let family_id = Uuid::new_v4();
+    let now = Utc::now().to_rfc3339();
+    let mut fields = vec![
+        ("user_id", user_id.to_string()),
+        ("created_at", now.clone()),
+        ("last_used_at", now),
+    ];
+    if let Some(org_id) = org_id {
+        fields.push(("org_id", org_id.to_string()));
+    }
+    if let Some(user_agent) = &client.user_agent {
+        fields.push(("user_agent", user_agent.clone()));
+    }
+    if let Some(ip) = &client.ip {
+        fields.push(("ip", ip.clone()));
+    }
+    conn.hset_multiple::<_, _, _, ()>(family_key(&family_id), &fields)
+        .await?;
+
+    let token = issue(conn, config, user_id, family_id).await?;
+    Ok((family_id, token))
+}
+
+/// Signs an access token for a session acting on `org`, recorded so that
+/// revoking the session also revokes it.
+pub async fn issue_access_token(
+    conn: &mut MultiplexedConnection,
+    keys: &jwt::KeyRing,
+    config: &AuthConfig,
+    user_id: Uuid,
+    family_id: Uuid,
+    org: Option<&Membership>,
+) -> Result<String, AppError> {
+    let mut claims = jwt::Claims::new(
+        keys,
+        jwt::TokenType::Access,
+        user_id,
+        &config.jwt_access_expires_in,
+    )?;
+    claims.sid = Some(family_id);
+    if let Some(membership) = org {
+        claims.org = Some(membership.organization_id);
+        claims.roles = vec![membership.role.as_str().to_string()];
+    }
+    let token = jwt::sign(keys, &claims)?;
+
+    redis::pipe()
+        .atomic()
+        .hset(family_access_key(&family_id), claims.jti.to_string(), claims.exp)
+        .expire(family_access_key(&family_id), refresh_ttl(config)? as i64)
+        .query_async::<_, ()>(conn)
+        .await?;
+
+    Ok(token)
+}
+
+// Refresh tokens never leave this service, a shared secret is enough.
+fn refresh_keys(config: &AuthConfig) -> Result<jwt::KeyRing, AppError> {
+    let key = jwt::JwtKey::hmac(
+        jsonwebtoken::Algorithm::HS256,
+        config.jwt_refresh_secret.expose_secret(),
+    );
+    jwt::KeyRing::new(config, key, Vec::new())
+}
+
+async fn issue(
+    conn: &mut MultiplexedConnection,
+    config: &AuthConfig,
+    user_id: Uuid,
+    family_id: Uuid,
+) -> Result<String, AppError> {
+    let keys = refresh_keys(config)?;
+    let mut claims = jwt::Claims::new(
+        &keys,
+        jwt::TokenType::Refresh,
+        user_id,
+        &config.jwt_refresh_expires_in,
+    )?;
+    claims.sid = Some(family_id);
+    let jti = claims.jti;
+    let token = jwt::sign(&keys, &claims)?;
+    let ttl = refresh_ttl(config)?;
+
+    redis::pipe()
+        .atomic()
+        .set_ex(token_key(&jti), family_id.to_string(), ttl)
+        .expire(family_key(&family_id), ttl as i64)
+        .sadd(user_sessions_key(&user_id), family_id.to_string())
+        .expire(user_sessions_key(&user_id), ttl as i64)
+        .query_async::<_, ()>(conn)
+        .await?;
+
+    Ok(token)
+}
+
+/// Exchanges a refresh token for a new one in the same family and returns
+/// the owning user and the family alongside it.
+pub async fn rotate(
+    conn: &mut MultiplexedConnection,
+    config: &AuthConfig,
+    refresh_token: &str,
+    client: &ClientInfo,
+) -> Result<(Uuid, Uuid, String), AppError> {
+    let claims = jwt::validate_token(
+        refresh_token,
+        &refresh_keys(config)?,
+        jwt::TokenType::Refresh,
+    )
+    .map_err(|_| AppError::Unauthorized("Invalid refresh token".to_string()))?;
+
+    let family_id = family_of(conn, &claims.jti)
+        .await?
+        .ok_or_else(|| AppError::Unauthorized("Invalid refresh token".to_string()))?;
+
+    let family_exists: bool = conn.exists(family_key(&family_id)).await?;
+    if !family_exists {
+        return Err(AppError::Unauthorized("Refresh token revoked".to_string()));
+    }
+
+    // Marking the token as used must be atomic so that two concurrent
+    // refreshes with the same token cannot both succeed.
+    let remaining = (claims.exp - Utc::now().timestamp()).max(1);
+    let first_use: Option<String> = redis::cmd("SET")
+        .arg(used_key(&claims.jti))
+        .arg(1)
+        .arg("NX")
+        .arg("EX")
+        .arg(remaining)
+        .query_async(conn)
+        .await?;
+
+    if first_use.is_none() {
+        tracing::warn!(
+            "Refresh token reuse detected for user {}, revoking family {}",
+            claims.sub,
+            family_id
+        );
+        revoke_family(conn, family_id).await?;
+        return Err(AppError::Unauthorized(
+            "Refresh token reuse detected".to_string(),
+        ));
+    }
+
+    let mut fields = vec![("last_used_at", Utc::now().to_rfc3339())];
+    if let Some(user_agent) = &client.user_agent {
+        fields.push(("user_agent", user_agent.clone()));
+    }
+    if let Some(ip) = &client.ip {
+        fields.push(("ip", ip.clone()));
+    }
+    conn.hset_multiple::<_, _, _, ()>(family_key(&family_id), &fields)
+        .await?;
+
+    let token = issue(conn, config, claims.sub, family_id).await?;
+    Ok((claims.sub, family_id, token))
+}
+
+/// Organization the session last acted on.
+pub async fn active_org(
+    conn: &mut MultiplexedConnection,
+    family_id: Uuid,
+) -> Result<Option<Uuid>, AppError> {
+    let org_id: Option<String> = conn.hget(family_key(&family_id), "org_id").await?;
+    Ok(org_id.map(|id| id.parse()).transpose()?)
+}
+
+pub async fn set_active_org(
+    conn: &mut MultiplexedConnection,
+    user_id: Uuid,
+    family_id: Uuid,
+    org_id: Uuid,
+) -> Result<(), AppError> {
+    if !owns(conn, user_id, family_id).await? {
+        return Err(AppError::Unauthorized("Session revoked".to_string()));
+    }
+    conn.hset::<_, _, _, ()>(family_key(&family_id), "org_id", org_id.to_string())
+        .await?;
+    Ok(())
+}
+
+async fn family_of(
+    conn: &mut MultiplexedConnection,
+    jti: &Uuid,
+) -> Result<Option<Uuid>, AppError> {
+    let family_id: Option<String> = conn.get(token_key(jti)).await?;
+    Ok(family_id.map(|id| id.parse()).transpose()?)
+}
+
+/// Session named by a refresh token, without checking it is still alive.
+pub fn session_of(config: &AuthConfig, refresh_token: &str) -> Option<Uuid> {
+    let keys = refresh_keys(config).ok()?;
+    jwt::validate_token(refresh_token, &keys, jwt::TokenType::Refresh)
+        .ok()?
+        .sid
+}
+
+/// Resolves the user and session behind a live refresh token.
+pub async fn current(
+    conn: &mut MultiplexedConnection,
+    config: &AuthConfig,
+    refresh_token: &str,
+) -> Result<(Uuid, Uuid), AppError> {
+    let claims = jwt::validate_token(
+        refresh_token,
+        &refresh_keys(config)?,
+        jwt::TokenType::Refresh,
+    )
+    .map_err(|_| AppError::Unauthorized("Invalid refresh token".to_string()))?;
+
+    let family_id = family_of(conn, &claims.jti)
+        .await?
+        .ok_or_else(|| AppError::Unauthorized("Invalid refresh token".to_string()))?;
+
+    let owner: Option<String> = conn.hget(family_key(&family_id), "user_id").await?;
+    if owner != Some(claims.sub.to_string()) {
+        return Err(AppError::Unauthorized("Refresh token revoked".to_string()));
+    }
+
+    Ok((claims.sub, family_id))
+}
+
+/// Resolves the user and session an access token was issued for, while the
+/// session is alive. Bearer clients have no refresh token cookie to tell.
+pub async fn of_access_token(
+    conn: &mut MultiplexedConnection,
+    claims: &jwt::Claims,
+) -> Result<(Uuid, Uuid), AppError> {
+    let family_id = claims
+        .sid
+        .ok_or_else(|| AppError::Unauthorized("Invalid access token".to_string()))?;
+
+    let owner: Option<String> = conn.hget(family_key(&family_id), "user_id").await?;
+    if owner != Some(claims.sub.to_string()) {
+        return Err(AppError::Unauthorized("Session revoked".to_string()));
+    }
+
+    Ok((claims.sub, family_id))
+}
+
+pub async fn list(
+    conn: &mut MultiplexedConnection,
+    user_id: Uuid,
+    current: Option<Uuid>,
+) -> Result<Vec<Session>, AppError> {
+    let ids: Vec<String> = conn.smembers(user_sessions_key(&user_id)).await?;
+
+    let mut sessions = Vec::with_capacity(ids.len());
+    for id in ids {
+        let family_id: Uuid = id.parse()?;
+        let fields: HashMap<String, String> = conn.hgetall(family_key(&family_id)).await?;
+        if fields.is_empty() {
+            // The family expired on its own, drop it from the index.
+            conn.srem::<_, _, ()>(user_sessions_key(&user_id), &id).await?;
+            continue;
+        }
+        sessions.push(Session::from_hash(family_id, fields, current));
+    }
+    sessions.sort_by_key(|s| std::cmp::Reverse(s.last_used_at));
+
+    Ok(sessions)
+}
+
+async fn owns(
+    conn: &mut MultiplexedConnection,
+    user_id: Uuid,
+    family_id: Uuid,
+) -> Result<bool, AppError> {
+    let owner: Option<String> = conn.hget(family_key(&family_id), "user_id").await?;
+    Ok(owner == Some(user_id.to_string()))
+}
+
+pub async fn rename(
+    conn: &mut MultiplexedConnection,
+    user_id: Uuid,
+    family_id: Uuid,
+    name: &str,
+) -> Result<(), AppError> {
+    if !owns(conn, user_id, family_id).await? {
+        return Err(AppError::NotFound("Session not found".to_string()));
+    }
+    conn.hset::<_, _, _, ()>(family_key(&family_id), "name", name)
+        .await?;
+    Ok(())
+}
+
+pub async fn revoke_session(
+    conn: &mut MultiplexedConnection,
+    user_id: Uuid,
+    family_id: Uuid,
+) -> Result<(), AppError> {
+    if !owns(conn, user_id, family_id).await? {
+        return Err(AppError::NotFound("Session not found".to_string()));
+    }
+    revoke_family(conn, family_id).await
+}
+
+/// Revokes every session of a user, optionally keeping one alive.
+pub async fn revoke_all(
+    conn: &mut MultiplexedConnection,
+    user_id: Uuid,
+    except: Option<Uuid>,
+) -> Result<(), AppError> {
+    let ids: Vec<String> = conn.smembers(user_sessions_key(&user_id)).await?;
+    for id in ids {
+        let family_id: Uuid = id.parse()?;
+        if Some(family_id) != except {
+            revoke_family(conn, family_id).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Revokes the family of the given refresh token. Invalid or unknown tokens
+/// are ignored so that logging out stays idempotent.
+pub async fn revoke(
+    conn: &mut MultiplexedConnection,
+    config: &AuthConfig,
+    refresh_token: &str,
+) -> Result<(), AppError> {
+    let Ok(claims) =
+        jwt::validate_token(refresh_token, &refresh_keys(config)?, jwt::TokenType::Refresh)
+    else {
+        return Ok(());
+    };
+
+    if let Some(family_id) = family_of(conn, &claims.jti).await? {
+        revoke_family(conn, family_id).await?;
+    }
+    Ok(())
+}
+
+pub async fn revoke_family(
+    conn: &mut MultiplexedConnection,
+    family_id: Uuid,
+) -> Result<(), AppError> {
+    let owner: Option<String> = conn.hget(family_key(&family_id), "user_id").await?;
+
+    let access_tokens: HashMap<String, i64> =
+        conn.hgetall(family_access_key(&family_id)).await?;
+    for (jti, exp) in access_tokens {
+        denylist::deny(conn, jti.parse()?, exp).await?;
+    }
+
+    let mut pipe = redis::pipe();
+    pipe.atomic()
+        .del(family_key(&family_id))
+        .del(family_access_key(&family_id));
+    if let Some(owner) = owner {
+        pipe.srem(user_sessions_key(&owner.parse()?), family_id.to_string());
+    }
+    pipe.query_async::<_, ()>(conn).await?;
+    Ok(())
+}
@@ -0,0 +1,73 @@
+use redis::aio::MultiplexedConnection;
+use redis::AsyncCommands;
+
+use crate::{
+    errors::AppError,
+    utils::token::{generate_opaque_token, hash_token},
+};
+
+// Short-lived tokens sent by email (verification, password reset, ...).
+// Only a hash of the token is kept in Redis and it is deleted on first use.
+
+fn key(purpose: &str, token: &str) -> String {
+    format!("single_use:{}:{}", purpose, hash_token(token))
+}
+
+/// Stores `value` behind a fresh token and returns the token.
+pub async fn issue(
+    conn: &mut MultiplexedConnection,
+    purpose: &str,
+    value: &str,
+    ttl: u64,
+) -> Result<String, AppError> {
+    let token = generate_opaque_token();
+    conn.set_ex::<_, _, ()>(key(purpose, &token), value, ttl)
+        .await?;
+    Ok(token)
+}
+
+/// Returns the value stored behind the token without invalidating it.
+pub async fn peek(
+    conn: &mut MultiplexedConnection,
+    purpose: &str,
+    token: &str,
+) -> Result<Option<String>, AppError> {
+    Ok(conn.get(key(purpose, token)).await?)
+}
+
+/// Returns the value stored behind the token and invalidates it.
+pub async fn consume(
+    conn: &mut MultiplexedConnection,
+    purpose: &str,
+    token: &str,
+) -> Result<Option<String>, AppError> {
+    let value: Option<String> = redis::cmd("GETDEL")
+        .arg(key(purpose, token))
+        .query_async(conn)
+        .await?;
+    Ok(value)
+}
+
+/// Sets `key` for `ttl` seconds unless it already exists. Returns whether it
+/// was set, which makes it usable as a simple per-key throttle.
+pub async fn mark(
+    conn: &mut MultiplexedConnection,
+    key: &str,
+    ttl: u64,
+) -> Result<bool, AppError> {
+    let set: Option<String> = redis::cmd("SET")
+        .arg(key)
+        .arg(1)
+        .arg("NX")
+        .arg("EX")
+        .arg(ttl)
+        .query_async(conn)
+        .await?;
+    Ok(set.is_some())
+}
+
+/// Removes a key set by `mark`.
+pub async fn unmark(conn: &mut MultiplexedConnection, key: &str) -> Result<(), AppError> {
+    conn.del::<_, ()>(key).await?;
+    Ok(())
+}
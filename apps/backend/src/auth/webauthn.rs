@@ -0,0 +1,133 @@
+use redis::aio::MultiplexedConnection;
+use redis::AsyncCommands;
+use serde::{de::DeserializeOwned, Serialize};
+use sqlx::{types::Json, PgPool};
+use uuid::Uuid;
+use webauthn_rs::prelude::{
+    Passkey, PasskeyAuthentication, PublicKeyCredential, RequestChallengeResponse, Url, Webauthn,
+    WebauthnBuilder,
+};
+
+use crate::{
+    config::WebauthnConfig, errors::AppError, models::webauthn::WebauthnCredential,
+};
+
+// Ceremony state lives in Redis between the start and finish calls.
+const CEREMONY_TTL: u64 = 5 * 60;
+
+pub fn build(config: &WebauthnConfig) -> Result<Webauthn, AppError> {
+    let invalid = |msg: String| AppError::Config(config::ConfigError::Message(msg));
+
+    let origin = Url::parse(&config.rp_origin)
+        .map_err(|e| invalid(format!("Invalid WebAuthn origin: {}", e)))?;
+    WebauthnBuilder::new(&config.rp_id, &origin)
+        .and_then(|builder| builder.rp_name(&config.rp_name).build())
+        .map_err(|e| invalid(format!("Invalid WebAuthn configuration: {}", e)))
+}
+
+pub async fn credentials_for(
+    pool: &PgPool,
+    user_id: Uuid,
+) -> Result<Vec<WebauthnCredential>, AppError> {
+    Ok(sqlx::query_as!(
+        WebauthnCredential,
+        r#"SELECT id, user_id, credential_id, passkey as "passkey: Json<Passkey>", name, created_at, last_used_at
+        FROM webauthn_credentials WHERE user_id = $1 ORDER BY created_at"#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await?)
+}
+
+pub async fn store_state<T: Serialize>(
+    conn: &mut MultiplexedConnection,
+    key: &str,
+    state: &T,
+) -> Result<(), AppError> {
+    let state = serde_json::to_string(state).map_err(|_| AppError::InternalServerError)?;
+    conn.set_ex::<_, _, ()>(key, state, CEREMONY_TTL).await?;
+    Ok(())
+}
+
+/// Returns the ceremony state stored under `key`, which can only be used once.
+pub async fn take_state<T: DeserializeOwned>(
+    conn: &mut MultiplexedConnection,
+    key: &str,
+) -> Result<Option<T>, AppError> {
+    let state: Option<String> = redis::cmd("GETDEL").arg(key).query_async(conn).await?;
+    state
+        .map(|state| serde_json::from_str(&state).map_err(|_| AppError::InternalServerError))
+        .transpose()
+}
+
+/// Starts an assertion ceremony over the passkeys of a user and keeps its
+/// state under `key`.
+pub async fn start_authentication(
+    webauthn: &Webauthn,
+    conn: &mut MultiplexedConnection,
+    pool: &PgPool,
+    user_id: Uuid,
+    key: &str,
+) -> Result<RequestChallengeResponse, AppError> {
+    let passkeys: Vec<_> = credentials_for(pool, user_id)
+        .await?
+        .into_iter()
+        .map(|c| c.passkey.0)
+        .collect();
+    if passkeys.is_empty() {
+        return Err(AppError::BadRequest("No passkey registered".to_string()));
+    }
+
+    let (options, state) = webauthn
+        .start_passkey_authentication(&passkeys)
+        .map_err(|e| {
+            tracing::error!("WebAuthn error: {:?}", e);
+            AppError::InternalServerError
+        })?;
+    store_state(conn, key, &state).await?;
+
+    Ok(options)
+}
+
+/// Verifies an assertion against the state under `key` and returns the user
+/// owning the passkey.
+pub async fn finish_authentication(
+    webauthn: &Webauthn,
+    conn: &mut MultiplexedConnection,
+    pool: &PgPool,
+    key: &str,
+    credential: &PublicKeyCredential,
+) -> Result<Uuid, AppError> {
+    let failed = || AppError::Unauthorized("Passkey verification failed".to_string());
+
+    let state: PasskeyAuthentication = take_state(conn, key).await?.ok_or_else(failed)?;
+    let result = webauthn
+        .finish_passkey_authentication(credential, &state)
+        .map_err(|e| {
+            tracing::debug!("Passkey assertion rejected: {:?}", e);
+            failed()
+        })?;
+
+    let stored = sqlx::query_as!(
+        WebauthnCredential,
+        r#"SELECT id, user_id, credential_id, passkey as "passkey: Json<Passkey>", name, created_at, last_used_at
+        FROM webauthn_credentials WHERE credential_id = $1"#,
+        result.cred_id().as_ref()
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(failed)?;
+
+    // Keeps the signature counter up to date to detect cloned authenticators.
+    let mut passkey = stored.passkey.0;
+    passkey.update_credential(&result);
+    sqlx::query!(
+        "UPDATE webauthn_credentials SET passkey = $1, last_used_at = NOW() WHERE id = $2",
+        Json(passkey) as _,
+        stored.id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(stored.user_id)
+}
@@ -0,0 +1,120 @@
+use std::path::Path;
+
+use jsonwebtoken::Algorithm;
+
+use crate::{
+    auth::{
+        admin,
+        jwt::{self, JwtKey, KeyRing},
+    },
+    config::AppConfig,
+    db,
+    errors::AppError,
+};
+
+const USAGE: &str = "usage: backend rotate-jwt-key <output-dir> [algorithm]
+       backend grant-admin <email>
+       backend revoke-admin <email>";
+
+/// Administration commands, run instead of the server when arguments are given.
+pub async fn run(config: &AppConfig, args: &[String]) -> Result<(), AppError> {
+    match args {
+        [command, email] if command == "grant-admin" => set_platform_admin(config, email, true).await,
+        [command, email] if command == "revoke-admin" => set_platform_admin(config, email, false).await,
+        [command, dir] if command == "rotate-jwt-key" => {
+            rotate_jwt_key(config, Path::new(dir), config.auth.jwt_algorithm)
+        }
+        [command, dir, algorithm] if command == "rotate-jwt-key" => {
+            let algorithm = algorithm
+                .parse()
+                .map_err(|_| AppError::BadRequest(format!("Unknown algorithm: {}", algorithm)))?;
+            rotate_jwt_key(config, Path::new(dir), algorithm)
+        }
+        _ => Err(AppError::BadRequest(USAGE.to_string())),
+    }
+}
+
+/// Generates the next access token key and prints how to roll it out. Each
+/// step is a deployment, so instances never see a token signed by a key they
+/// do not know yet.
+fn rotate_jwt_key(config: &AppConfig, dir: &Path, algorithm: Algorithm) -> Result<(), AppError> {
+    let current = KeyRing::from_config(&config.auth)?;
+    let (key, secret) = JwtKey::generate(algorithm)?;
+
+    let io_error = |e: std::io::Error| AppError::BadRequest(format!("Cannot write the key: {}", e));
+    std::fs::create_dir_all(dir).map_err(io_error)?;
+    let extension = if jwt::is_hmac(algorithm) { "key" } else { "pem" };
+    let path = dir.join(format!("{}.{}", key.kid, extension));
+    write_private(&path, &secret).map_err(io_error)?;
+    let path = path.display();
+
+    let previous_key = match &config.auth.jwt_private_key_file {
+        _ if jwt::is_hmac(config.auth.jwt_algorithm) => {
+            "AUTH__JWT_VERIFICATION_KEYS__PREVIOUS__KEY=<current AUTH__JWT_ACCESS_SECRET>".to_string()
+        }
+        Some(file) => format!("AUTH__JWT_VERIFICATION_KEYS__PREVIOUS__KEY_FILE={}", file),
+        None => "AUTH__JWT_VERIFICATION_KEYS__PREVIOUS__KEY=<current AUTH__JWT_PRIVATE_KEY>".to_string(),
+    };
+    let signing_key = if jwt::is_hmac(algorithm) {
+        format!("AUTH__JWT_ACCESS_SECRET=<content of {}>", path)
+    } else {
+        format!("AUTH__JWT_PRIVATE_KEY_FILE={}", path)
+    };
+
+    let (algorithm, previous) = (
+        format!("{:?}", algorithm),
+        format!("{:?}", config.auth.jwt_algorithm),
+    );
+    println!("Generated {} key {} in {}", algorithm, key.kid, path);
+    println!();
+    println!("1. Deploy with the new key accepted (and published) but not signing yet:");
+    println!("     AUTH__JWT_VERIFICATION_KEYS__NEXT__ALGORITHM={}", algorithm);
+    println!("     AUTH__JWT_VERIFICATION_KEYS__NEXT__KEY_FILE={}", path);
+    println!();
+    println!("2. Deploy with the new key signing, without the NEXT entry, keeping the");
+    println!("   current key {} for the tokens it already signed:", current.active().kid);
+    println!("     AUTH__JWT_ALGORITHM={}", algorithm);
+    println!("     {}", signing_key);
+    println!("     AUTH__JWT_VERIFICATION_KEYS__PREVIOUS__ALGORITHM={}", previous);
+    println!("     {}", previous_key);
+    println!();
+    println!(
+        "3. Once {} have passed, deploy without the PREVIOUS entry.",
+        config.auth.jwt_access_expires_in
+    );
+    Ok(())
+}
+
+/// Grants or revokes the platform admin role, which guards `/admin`. The
+/// first admin can only be made this way.
+async fn set_platform_admin(config: &AppConfig, email: &str, granted: bool) -> Result<(), AppError> {
+    let pool = db::create_pool(&config.database).await?;
+
+    let mut tx = pool.begin().await?;
+    let user_id = sqlx::query_scalar!(
+        "UPDATE users SET is_platform_admin = $2, updated_at = NOW() WHERE LOWER(email) = LOWER($1) RETURNING id",
+        email,
+        granted
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| AppError::NotFound(format!("No account for {}", email)))?;
+    let action = if granted { admin::GRANT_ADMIN } else { admin::REVOKE_ADMIN };
+    admin::record(&mut *tx, None, action, user_id, serde_json::json!({})).await?;
+    tx.commit().await?;
+
+    println!("{} {} the platform admin role", email, if granted { "now has" } else { "no longer has" });
+    Ok(())
+}
+
+fn write_private(path: &Path, content: &str) -> std::io::Result<()> {
+    use std::io::Write;
+    #[cfg(unix)]
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    options.open(path)?.write_all(content.as_bytes())
+}
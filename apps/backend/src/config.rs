@@ -2,6 +2,7 @@ use secrecy::{ExposeSecret, Secret};
 use serde::Deserialize;
 use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
 use sqlx::{ConnectOptions, PgPool};
+use std::collections::HashMap;
 use std::time::Duration;
 
 #[derive(Deserialize, Debug, Clone)]
@@ -10,12 +11,35 @@ pub struct AppConfig {
     pub database: DatabaseConfig,
     pub redis: RedisConfig,
     pub auth: AuthConfig,
+    #[serde(default)]
+    pub mail: MailConfig,
+    #[serde(default)]
+    pub webauthn: WebauthnConfig,
+    #[serde(default)]
+    pub cookie: CookieConfig,
+    // Social login providers, keyed by the name used in `/auth/oauth/{provider}`.
+    #[serde(default)]
+    pub oauth: HashMap<String, OAuthProviderConfig>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct ServerConfig {
     pub host: String,
     pub port: u16,
+    // Base URL of the frontend, used to build links sent by email.
+    #[serde(default = "default_public_url")]
+    pub public_url: String,
+    // Base URL of this API as seen by browsers, used for OAuth redirect URIs.
+    #[serde(default = "default_api_url")]
+    pub api_url: String,
+    // Behind a reverse proxy, the client address is the last entry it appends
+    // to X-Forwarded-For rather than the peer of the connection.
+    #[serde(default)]
+    pub behind_proxy: bool,
+    // DNS-over-HTTPS resolver (JSON API) checking the TXT records that prove
+    // ownership of auto-join domains.
+    #[serde(default = "default_dns_resolver_url")]
+    pub dns_resolver_url: String,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -36,8 +60,254 @@ pub struct RedisConfig {
 pub struct AuthConfig {
     pub jwt_access_secret: Secret<String>,
     pub jwt_access_expires_in: String,
+    // Algorithm of access tokens. Asymmetric ones (RS256, ES256, EdDSA, ...)
+    // sign with the private key below and publish the public key as a JWKS.
+    #[serde(default = "default_jwt_algorithm")]
+    pub jwt_algorithm: jsonwebtoken::Algorithm,
+    // PEM private key, inline or read from a file.
+    pub jwt_private_key: Option<Secret<String>>,
+    pub jwt_private_key_file: Option<String>,
+    // Keys that still verify access tokens but no longer sign them, keyed by
+    // any name. See `backend rotate-jwt-key`.
+    #[serde(default)]
+    pub jwt_verification_keys: HashMap<String, JwtKeyConfig>,
+    // `iss` and `aud` of every token issued, checked when validating them.
+    #[serde(default = "default_api_url")]
+    pub jwt_issuer: String,
+    #[serde(default = "default_jwt_audience")]
+    pub jwt_audience: String,
     pub jwt_refresh_secret: Secret<String>,
     pub jwt_refresh_expires_in: String,
+    #[serde(default)]
+    pub email_verification: EmailVerificationPolicy,
+    #[serde(default = "default_email_verification_expires_in")]
+    pub email_verification_expires_in: String,
+    #[serde(default = "default_password_reset_expires_in")]
+    pub password_reset_expires_in: String,
+    #[serde(default = "default_magic_link_expires_in")]
+    pub magic_link_expires_in: String,
+    #[serde(default = "default_invitation_expires_in")]
+    pub invitation_expires_in: String,
+    // Base64-encoded 32-byte key protecting secrets stored in the database.
+    pub encryption_key: Option<Secret<String>>,
+    #[serde(default = "default_mfa_issuer")]
+    pub mfa_issuer: String,
+    // Known API clients, keyed by the lowercase id sent in `X-Client-Id`.
+    #[serde(default)]
+    pub clients: HashMap<String, ClientConfig>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenDelivery {
+    // HttpOnly cookies, for browsers.
+    #[default]
+    Cookie,
+    // In the JSON body, for clients sending `Authorization: Bearer` (mobile, CLI).
+    Body,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ClientConfig {
+    #[serde(default)]
+    pub token_delivery: TokenDelivery,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct JwtKeyConfig {
+    pub algorithm: jsonwebtoken::Algorithm,
+    // PEM key (public or private) or, for HMAC algorithms, the secret.
+    pub key: Option<Secret<String>>,
+    pub key_file: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EmailVerificationPolicy {
+    // Unverified users can sign in, they are only reminded to verify.
+    #[default]
+    Optional,
+    // No session is issued until the email address has been verified.
+    Required,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct MailConfig {
+    #[serde(default = "default_mail_from")]
+    pub from: String,
+    pub smtp_host: Option<String>,
+    pub smtp_port: Option<u16>,
+    pub smtp_username: Option<String>,
+    pub smtp_password: Option<Secret<String>>,
+    // Writes emails to this directory instead of sending them (development and tests).
+    pub outbox_dir: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct WebauthnConfig {
+    // Relying party id, the domain passkeys are bound to.
+    #[serde(default = "default_webauthn_rp_id")]
+    pub rp_id: String,
+    // Origin of the frontend performing the ceremonies.
+    #[serde(default = "default_public_url")]
+    pub rp_origin: String,
+    #[serde(default = "default_mfa_issuer")]
+    pub rp_name: String,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CookieSameSite {
+    #[default]
+    Strict,
+    Lax,
+    // Frontends on another site, requires `secure`.
+    None,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct CookieConfig {
+    // Set to share the session with subdomains, e.g. `.example.com`.
+    pub domain: Option<String>,
+    #[serde(default = "default_cookie_path")]
+    pub path: String,
+    #[serde(default)]
+    pub same_site: CookieSameSite,
+    // Turn off only for local development over plain HTTP.
+    #[serde(default = "default_true")]
+    pub secure: bool,
+    // Names session cookies `__Host-...`, pinning them to this exact host.
+    // Requires `secure`, the `/` path and no domain.
+    #[serde(default)]
+    pub host_prefix: bool,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OAuthProviderKind {
+    // OpenID Connect, endpoints are discovered from the issuer unless set.
+    #[default]
+    Oidc,
+    // GitHub only speaks OAuth2 and exposes the user through its REST API.
+    Github,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct OAuthProviderConfig {
+    #[serde(default)]
+    pub kind: OAuthProviderKind,
+    pub client_id: String,
+    pub client_secret: Secret<String>,
+    pub issuer: Option<String>,
+    pub authorization_endpoint: Option<String>,
+    pub token_endpoint: Option<String>,
+    pub userinfo_endpoint: Option<String>,
+    // Space-separated, defaults depend on the kind of provider.
+    pub scopes: Option<String>,
+}
+
+impl Default for WebauthnConfig {
+    fn default() -> Self {
+        Self {
+            rp_id: default_webauthn_rp_id(),
+            rp_origin: default_public_url(),
+            rp_name: default_mfa_issuer(),
+        }
+    }
+}
+
+impl Default for CookieConfig {
+    fn default() -> Self {
+        Self {
+            domain: None,
+            path: default_cookie_path(),
+            same_site: CookieSameSite::default(),
+            secure: true,
+            host_prefix: false,
+        }
+    }
+}
+
+impl CookieConfig {
+    fn validate(&self) -> Result<(), config::ConfigError> {
+        let invalid = |msg: &str| Err(config::ConfigError::Message(format!("cookie: {}", msg)));
+        if self.same_site == CookieSameSite::None && !self.secure {
+            return invalid("same_site = none requires secure");
+        }
+        if self.host_prefix && (!self.secure || self.domain.is_some() || self.path != "/") {
+            return invalid("host_prefix requires secure, the / path and no domain");
+        }
+        Ok(())
+    }
+}
+
+impl Default for MailConfig {
+    fn default() -> Self {
+        Self {
+            from: default_mail_from(),
+            smtp_host: None,
+            smtp_port: None,
+            smtp_username: None,
+            smtp_password: None,
+            outbox_dir: None,
+        }
+    }
+}
+
+fn default_public_url() -> String {
+    "http://localhost:3000".to_string()
+}
+
+fn default_api_url() -> String {
+    "http://localhost:8000".to_string()
+}
+
+fn default_dns_resolver_url() -> String {
+    "https://cloudflare-dns.com/dns-query".to_string()
+}
+
+fn default_cookie_path() -> String {
+    "/".to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_jwt_algorithm() -> jsonwebtoken::Algorithm {
+    jsonwebtoken::Algorithm::HS256
+}
+
+fn default_jwt_audience() -> String {
+    "saas-api".to_string()
+}
+
+fn default_email_verification_expires_in() -> String {
+    "24h".to_string()
+}
+
+fn default_password_reset_expires_in() -> String {
+    "30m".to_string()
+}
+
+fn default_magic_link_expires_in() -> String {
+    "15m".to_string()
+}
+
+fn default_invitation_expires_in() -> String {
+    "7d".to_string()
+}
+
+fn default_mfa_issuer() -> String {
+    "SaaS".to_string()
+}
+
+fn default_webauthn_rp_id() -> String {
+    "localhost".to_string()
+}
+
+fn default_mail_from() -> String {
+    "SaaS <no-reply@localhost>".to_string()
 }
 
 impl AppConfig {
@@ -45,7 +315,9 @@ impl AppConfig {
         let config = config::Config::builder()
             .add_source(config::Environment::default().separator("__"))
             .build()?;
-        config.try_deserialize()
+        let config: Self = config.try_deserialize()?;
+        config.cookie.validate()?;
+        Ok(config)
     }
 }
 
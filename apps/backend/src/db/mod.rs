@@ -1,7 +1,38 @@
 use crate::config::DatabaseConfig;
 use crate::errors::AppError;
-use sqlx::PgPool;
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
 
 pub async fn create_pool(config: &DatabaseConfig) -> Result<PgPool, AppError> {
     config.create_pool().await.map_err(AppError::Sqlx)
-}
\ No newline at end of file
+}
+
+/// Starts a transaction limited to the rows of `org_id` by the row-level
+/// security policies, whatever its queries filter on. The setting is local
+/// to the transaction and goes away with it.
+pub async fn begin_for_org(
+    pool: &PgPool,
+    org_id: Uuid,
+) -> Result<Transaction<'static, Postgres>, AppError> {
+    let mut tx = pool.begin().await?;
+    // SET LOCAL does not take parameters.
+    sqlx::query!(
+        "SELECT set_config('app.current_org', $1, TRUE)",
+        org_id.to_string()
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    Ok(tx)
+}
+
+/// Starts a transaction seeing the rows of every organization, for what
+/// spans them: authentication, the memberships of a user, platform staff.
+/// Everything else goes through `begin_for_org`.
+pub async fn begin_cross_tenant(pool: &PgPool) -> Result<Transaction<'static, Postgres>, AppError> {
+    let mut tx = pool.begin().await?;
+    // Exempt from row-level security, see the migrations.
+    sqlx::query!("SET LOCAL ROLE saas_cross_tenant")
+        .execute(&mut *tx)
+        .await?;
+    Ok(tx)
+}
@@ -24,6 +24,9 @@ pub enum AppError {
     #[error("Forbidden: {0}")]
     Forbidden(String),
 
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
     #[error("Database error: {0}")]
     Sqlx(#[from] sqlx::Error),
 
@@ -44,6 +47,12 @@ pub enum AppError {
 
     #[error("Duration parsing error: {0}")]
     ParseDuration(#[from] DError),
+
+    #[error("Mail error: {0}")]
+    Mail(String),
+
+    #[error("Identity provider error: {0}")]
+    Provider(String),
 }
 
 impl IntoResponse for AppError {
@@ -57,6 +66,11 @@ impl IntoResponse for AppError {
             AppError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
             AppError::Unauthorized(msg) => (StatusCode::UNAUTHORIZED, msg),
             AppError::Forbidden(msg) => (StatusCode::FORBIDDEN, msg),
+            AppError::Conflict(msg) => (StatusCode::CONFLICT, msg),
+            AppError::Sqlx(sqlx::Error::Database(err)) if err.is_unique_violation() => (
+                StatusCode::CONFLICT,
+                "Resource already exists".to_string(),
+            ),
             AppError::Sqlx(err) => {
                 tracing::error!("SQLx error: {:?}", err);
                 (
@@ -106,6 +120,20 @@ impl IntoResponse for AppError {
                     "Duration parsing error".to_string(),
                 )
             }
+            AppError::Mail(err) => {
+                tracing::error!("Mail error: {:?}", err);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Email delivery error".to_string(),
+                )
+            }
+            AppError::Provider(err) => {
+                tracing::error!("Identity provider error: {:?}", err);
+                (
+                    StatusCode::BAD_GATEWAY,
+                    "Identity provider error".to_string(),
+                )
+            }
         };
 
         let body = Json(json!({ "error": error_message }));
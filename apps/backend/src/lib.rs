@@ -1,8 +1,10 @@
 
 pub mod auth;
+pub mod commands;
 pub mod config;
 pub mod db;
 pub mod errors;
+pub mod mail;
 pub mod models;
 pub mod redis;
 pub mod routes;
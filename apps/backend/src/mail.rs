@@ -0,0 +1,102 @@
+use std::path::PathBuf;
+
+use chrono::Utc;
+use lettre::{
+    message::{header::ContentType, Mailbox},
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use secrecy::ExposeSecret;
+use uuid::Uuid;
+
+use crate::{config::MailConfig, errors::AppError};
+
+#[derive(Clone)]
+enum Transport {
+    Smtp(AsyncSmtpTransport<Tokio1Executor>),
+    Outbox(PathBuf),
+    Log,
+}
+
+#[derive(Clone)]
+pub struct Mailer {
+    from: Mailbox,
+    transport: Transport,
+}
+
+impl Mailer {
+    pub fn from_config(config: &MailConfig) -> Result<Self, AppError> {
+        let from = config
+            .from
+            .parse()
+            .map_err(|e| AppError::Mail(format!("Invalid sender address: {}", e)))?;
+
+        let transport = if let Some(dir) = &config.outbox_dir {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| AppError::Mail(format!("Cannot create outbox: {}", e)))?;
+            Transport::Outbox(PathBuf::from(dir))
+        } else if let Some(host) = &config.smtp_host {
+            let mut builder = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)
+                .map_err(|e| AppError::Mail(e.to_string()))?;
+            if let Some(port) = config.smtp_port {
+                builder = builder.port(port);
+            }
+            if let (Some(username), Some(password)) = (&config.smtp_username, &config.smtp_password)
+            {
+                builder = builder.credentials(Credentials::new(
+                    username.clone(),
+                    password.expose_secret().clone(),
+                ));
+            }
+            Transport::Smtp(builder.build())
+        } else {
+            tracing::warn!("No mail transport configured, emails will only be logged.");
+            Transport::Log
+        };
+
+        Ok(Self { from, transport })
+    }
+
+    pub async fn send(&self, to: &str, subject: &str, body: &str) -> Result<(), AppError> {
+        // Checked for every transport, so that an address SMTP would refuse
+        // also fails with the outbox.
+        let mailbox: Mailbox = to
+            .parse()
+            .map_err(|e| AppError::Mail(format!("Invalid recipient: {}", e)))?;
+
+        match &self.transport {
+            Transport::Smtp(smtp) => {
+                let message = Message::builder()
+                    .from(self.from.clone())
+                    .to(mailbox)
+                    .subject(subject)
+                    .header(ContentType::TEXT_PLAIN)
+                    .body(body.to_string())
+                    .map_err(|e| AppError::Mail(e.to_string()))?;
+                smtp.send(message)
+                    .await
+                    .map_err(|e| AppError::Mail(e.to_string()))?;
+            }
+            Transport::Outbox(dir) => {
+                let path = dir.join(format!(
+                    "{}-{}.json",
+                    Utc::now().timestamp_millis(),
+                    Uuid::new_v4()
+                ));
+                let email = serde_json::json!({
+                    "from": self.from.to_string(),
+                    "to": to,
+                    "subject": subject,
+                    "body": body,
+                });
+                tokio::fs::write(path, email.to_string())
+                    .await
+                    .map_err(|e| AppError::Mail(e.to_string()))?;
+            }
+            Transport::Log => {
+                tracing::info!("Email to {}: {}\n{}", to, subject, body);
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,12 +1,14 @@
 use backend::{
+    auth::{jwt::KeyRing, webauthn},
+    commands,
     config::AppConfig,
     db,
+    mail::Mailer,
     redis,
     routes::create_router,
     state::AppState,
 };
 use std::net::SocketAddr;
-use tokio;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 #[tokio::main]
@@ -23,6 +25,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     dotenvy::dotenv().ok();
     let config = AppConfig::from_env()?;
 
+    // Commandes d'administration, par ex. `backend rotate-jwt-key <dir>` ou `backend grant-admin <email>`
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        commands::run(&config, &args).await?;
+        return Ok(());
+    }
+
     // Créer le pool de connexions à la base de données
     let pool = db::create_pool(&config.database).await?;
     tracing::info!("Database pool created successfully.");
@@ -31,11 +40,30 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let redis_client = redis::create_client(&config.redis)?;
     tracing::info!("Redis client created successfully.");
 
+    // Charger la clé de signature des access tokens
+    let jwt = KeyRing::from_config(&config.auth)?;
+
+    // Créer le client mail
+    let mailer = Mailer::from_config(&config.mail)?;
+
+    // Configurer WebAuthn (passkeys)
+    let webauthn = webauthn::build(&config.webauthn)?;
+
+    // Client HTTP pour les fournisseurs d'identité (OAuth)
+    let http = reqwest::Client::builder()
+        .user_agent(concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")))
+        .timeout(std::time::Duration::from_secs(10))
+        .build()?;
+
     // Créer l'état de l'application
     let state = AppState {
         pool,
         config: config.clone(),
         redis: redis_client,
+        mailer,
+        jwt,
+        webauthn,
+        http,
     };
 
     // Définir les routes de notre application
@@ -50,7 +78,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Lancer le serveur
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
 
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,46 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use crate::models::user::User;
+
+/// An account as support staff see it.
+#[derive(Debug, Serialize)]
+pub struct AdminUser {
+    pub id: Uuid,
+    pub email: String,
+    pub name: Option<String>,
+    pub email_verified_at: Option<DateTime<Utc>>,
+    pub is_platform_admin: bool,
+    pub suspended_at: Option<DateTime<Utc>>,
+    pub password_reset_required: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<User> for AdminUser {
+    fn from(user: User) -> Self {
+        Self {
+            id: user.id,
+            email: user.email,
+            name: user.name,
+            email_verified_at: user.email_verified_at,
+            is_platform_admin: user.is_platform_admin,
+            suspended_at: user.suspended_at,
+            password_reset_required: user.password_reset_required,
+            created_at: user.created_at,
+            updated_at: user.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct AuditEntry {
+    pub id: Uuid,
+    pub actor_id: Option<Uuid>,
+    pub action: String,
+    pub target_user_id: Option<Uuid>,
+    pub details: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
@@ -1,3 +1,10 @@
 // Déclare le module `user` pour le rendre accessible
 // depuis d'autres parties du code via `crate::models::user`.
+pub mod admin;
+pub mod identity;
+pub mod mfa;
+pub mod organization;
+pub mod personal_token;
+pub mod service_account;
 pub mod user;
+pub mod webauthn;
@@ -0,0 +1,98 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct Organization {
+    pub id: Uuid,
+    pub name: String,
+    // Set on the organization created with each account.
+    pub personal_user_id: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub auto_join_domain: Option<String>,
+    // Published in a TXT record of the domain, see `GET /orgs/:org_id/domain`.
+    #[serde(skip_serializing)]
+    pub auto_join_domain_token: Option<String>,
+    // Auto-join only applies once the domain is verified.
+    pub auto_join_domain_verified_at: Option<DateTime<Utc>>,
+}
+
+/// An organization as seen by one of its members.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct MemberOrganization {
+    pub id: Uuid,
+    pub name: String,
+    pub personal_user_id: Option<Uuid>,
+    pub role: Role,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub auto_join_domain: Option<String>,
+    pub auto_join_domain_verified_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "varchar", rename_all = "lowercase")]
+pub enum Role {
+    Owner,
+    Admin,
+    Member,
+    Billing,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Owner => "owner",
+            Role::Admin => "admin",
+            Role::Member => "member",
+            Role::Billing => "billing",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct Membership {
+    pub organization_id: Uuid,
+    pub user_id: Uuid,
+    pub role: Role,
+    pub custom_role_id: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A member as listed to the rest of the organization.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct Member {
+    pub user_id: Uuid,
+    pub email: String,
+    pub name: Option<String>,
+    pub role: Role,
+    pub custom_role_id: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct Invitation {
+    pub id: Uuid,
+    pub organization_id: Uuid,
+    pub email: String,
+    pub role: Role,
+    #[serde(skip_serializing)]
+    pub token_hash: String,
+    pub invited_by: Option<Uuid>,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A role defined by an organization, see `auth::permission`.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct OrgRole {
+    pub id: Uuid,
+    pub organization_id: Uuid,
+    pub name: String,
+    pub permissions: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
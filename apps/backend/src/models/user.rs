@@ -11,18 +11,44 @@ pub struct User {
     pub password_hash: String,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub name: Option<String>,
+    pub locale: Option<String>,
+    pub timezone: Option<String>,
+    pub avatar_url: Option<String>,
+    pub email_verified_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing)]
+    pub is_platform_admin: bool,
+    #[serde(skip_serializing)]
+    pub suspended_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing)]
+    pub password_reset_required: bool,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct CreateUser {
     pub email: String,
     pub password: String,
+    // Joins the inviting organization along with the sign-up.
+    pub invitation_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateProfile {
+    pub name: Option<String>,
+    pub locale: Option<String>,
+    pub timezone: Option<String>,
+    pub avatar_url: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct UserResponse {
     pub id: Uuid,
     pub email: String,
+    pub name: Option<String>,
+    pub locale: Option<String>,
+    pub timezone: Option<String>,
+    pub avatar_url: Option<String>,
+    pub email_verified_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -32,6 +58,11 @@ impl From<User> for UserResponse {
         Self {
             id: user.id,
             email: user.email,
+            name: user.name,
+            locale: user.locale,
+            timezone: user.timezone,
+            avatar_url: user.avatar_url,
+            email_verified_at: user.email_verified_at,
             created_at: user.created_at,
             updated_at: user.updated_at,
         }
@@ -0,0 +1,19 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{types::Json, FromRow};
+use uuid::Uuid;
+use webauthn_rs::prelude::Passkey;
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct WebauthnCredential {
+    pub id: Uuid,
+    #[serde(skip_serializing)]
+    pub user_id: Uuid,
+    #[serde(skip_serializing)]
+    pub credential_id: Vec<u8>,
+    #[serde(skip_serializing)]
+    pub passkey: Json<Passkey>,
+    pub name: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
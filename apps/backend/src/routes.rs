@@ -1,23 +1,208 @@
+pub mod admin;
 pub mod auth;
+pub mod invitations;
+pub mod jwks;
+pub mod magic_link;
+pub mod me;
+pub mod mfa;
+pub mod oauth;
+pub mod orgs;
+pub mod roles;
+pub mod service_accounts;
+pub mod tokens;
+pub mod webauthn;
 
-use crate::state::AppState;
+use crate::{
+    auth::{
+        admin::require_platform_admin,
+        csrf,
+        extractor::require_auth,
+        permission::{Permission::*, RequirePermission},
+        principal::require_principal,
+    },
+    state::AppState,
+};
 use axum::{
-    routing::{get, post},
+    handler::Handler,
+    middleware,
+    routing::{delete, get, patch, post},
     Router,
 };
 
-use self::auth::{login, logout, refresh, register};
+use self::admin::{
+    delete_user, force_logout, force_password_reset, get_user, list_audit_log,
+    list_user_sessions, search_users, suspend_user, unsuspend_user,
+};
+use self::auth::{
+    forgot_password, list_sessions, login, logout, logout_all, refresh, register,
+    rename_session, resend_verification, reset_password, revoke_session, verify_email,
+};
+use self::invitations::{
+    accept_invitation, create_invitation, list_invitations, resend_invitation,
+    revoke_invitation,
+};
+use self::jwks::jwks;
+use self::magic_link::{consume_magic_link, request_magic_link};
+use self::mfa::{confirm_totp, disable_totp, setup_totp, start_webauthn_mfa, verify_mfa};
+use self::me::{
+    change_password, confirm_email_change, get_me, request_email_change, update_me,
+};
+use self::oauth::{callback, list_identities, start, unlink_identity};
+use self::orgs::{
+    create_org, delete_org, get_domain, get_org, list_members, list_orgs, remove_member,
+    switch_org, update_member, update_org, verify_domain,
+};
+use self::roles::{create_role, delete_role, list_roles, update_role};
+use self::service_accounts::{
+    create_api_key, create_service_account, delete_api_key, delete_service_account,
+    list_api_keys, list_service_accounts, rotate_api_key,
+};
+use self::tokens::{create_token, delete_token, list_tokens};
+use self::webauthn::{
+    delete_credential, finish_login, finish_registration, list_credentials, start_login,
+    start_registration,
+};
 
 pub fn create_router(state: AppState) -> Router {
+    // Permission the caller needs in the organization of the route.
+    let can = |permission| RequirePermission::new(state.clone(), permission);
+
+    let protected = Router::new()
+        .route("/me", get(get_me).patch(update_me))
+        .route("/me/password", post(change_password))
+        .route("/me/email", post(request_email_change))
+        .route("/me/mfa/totp/setup", post(setup_totp))
+        .route("/me/mfa/totp/confirm", post(confirm_totp))
+        .route("/me/mfa/totp/disable", post(disable_totp))
+        .route("/me/identities", get(list_identities))
+        .route("/me/identities/:id", delete(unlink_identity))
+        .route("/me/tokens", get(list_tokens).post(create_token))
+        .route("/me/tokens/:id", delete(delete_token))
+        .route("/me/webauthn/register/start", post(start_registration))
+        .route("/me/webauthn/register/finish", post(finish_registration))
+        .route("/me/webauthn/credentials", get(list_credentials))
+        .route("/me/webauthn/credentials/:id", delete(delete_credential))
+        .route("/orgs", get(list_orgs).post(create_org))
+        .route(
+            "/orgs/:org_id",
+            get(get_org.layer(can(OrgRead)))
+                .patch(update_org.layer(can(OrgUpdate)))
+                .delete(delete_org.layer(can(OrgDelete))),
+        )
+        .route("/orgs/:org_id/domain", get(get_domain.layer(can(OrgUpdate))))
+        .route(
+            "/orgs/:org_id/domain/verify",
+            post(verify_domain.layer(can(OrgUpdate))),
+        )
+        .route("/orgs/:org_id/switch", post(switch_org))
+        .route(
+            "/orgs/:org_id/members",
+            get(list_members.layer(can(MembersRead))),
+        )
+        .route(
+            "/orgs/:org_id/invitations",
+            get(list_invitations.layer(can(MembersInvite)))
+                .post(create_invitation.layer(can(MembersInvite))),
+        )
+        .route(
+            "/orgs/:org_id/invitations/:id",
+            delete(revoke_invitation.layer(can(MembersInvite))),
+        )
+        .route(
+            "/orgs/:org_id/invitations/:id/resend",
+            post(resend_invitation.layer(can(MembersInvite))),
+        )
+        .route("/invitations/accept", post(accept_invitation))
+        .route(
+            "/orgs/:org_id/members/:user_id",
+            // Members may leave without any permission.
+            patch(update_member.layer(can(MembersManage))).delete(remove_member),
+        )
+        .route(
+            "/orgs/:org_id/roles",
+            get(list_roles.layer(can(MembersRead))).post(create_role.layer(can(RolesManage))),
+        )
+        .route(
+            "/orgs/:org_id/roles/:id",
+            patch(update_role.layer(can(RolesManage)))
+                .delete(delete_role.layer(can(RolesManage))),
+        )
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_auth));
+
+    // Also open to service accounts through their API keys.
+    let integrations = Router::new()
+        .route(
+            "/orgs/:org_id/service-accounts",
+            get(list_service_accounts.layer(can(ServiceAccountsRead)))
+                .post(create_service_account.layer(can(ServiceAccountsWrite))),
+        )
+        .route(
+            "/orgs/:org_id/service-accounts/:id",
+            delete(delete_service_account.layer(can(ServiceAccountsWrite))),
+        )
+        .route(
+            "/orgs/:org_id/service-accounts/:id/keys",
+            get(list_api_keys.layer(can(ServiceAccountsRead)))
+                .post(create_api_key.layer(can(ServiceAccountsWrite))),
+        )
+        .route(
+            "/orgs/:org_id/service-accounts/:id/keys/:key_id",
+            delete(delete_api_key.layer(can(ServiceAccountsWrite))),
+        )
+        .route(
+            "/orgs/:org_id/service-accounts/:id/keys/:key_id/rotate",
+            post(rotate_api_key.layer(can(ServiceAccountsWrite))),
+        )
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_principal));
+
+    // Platform staff only.
+    let admin = Router::new()
+        .route("/admin/users", get(search_users))
+        .route("/admin/users/:id", get(get_user).delete(delete_user))
+        .route("/admin/users/:id/sessions", get(list_user_sessions))
+        .route("/admin/users/:id/suspend", post(suspend_user))
+        .route("/admin/users/:id/unsuspend", post(unsuspend_user))
+        .route("/admin/users/:id/password-reset", post(force_password_reset))
+        .route("/admin/users/:id/logout", post(force_logout))
+        .route("/admin/audit-log", get(list_audit_log))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            require_platform_admin,
+        ));
+
     Router::new()
         .route("/", get(root))
+        .route("/.well-known/jwks.json", get(jwks))
         .route("/auth/register", post(register))
         .route("/auth/login", post(login))
         .route("/auth/refresh", post(refresh))
         .route("/auth/logout", post(logout))
+        .route("/auth/logout-all", post(logout_all))
+        .route("/auth/verify-email", post(verify_email))
+        .route("/auth/resend-verification", post(resend_verification))
+        .route("/auth/password/forgot", post(forgot_password))
+        .route("/auth/password/reset", post(reset_password))
+        .route("/auth/email/confirm", post(confirm_email_change))
+        .route("/auth/magic-link", post(request_magic_link))
+        .route("/auth/magic-link/consume", get(consume_magic_link))
+        .route("/auth/mfa/verify", post(verify_mfa))
+        .route("/auth/mfa/webauthn/start", post(start_webauthn_mfa))
+        .route("/auth/webauthn/login/start", post(start_login))
+        .route("/auth/webauthn/login/finish", post(finish_login))
+        .route("/auth/oauth/:provider/start", get(start))
+        .route("/auth/oauth/:provider/callback", get(callback))
+        .route("/auth/sessions", get(list_sessions))
+        .route(
+            "/auth/sessions/:id",
+            patch(rename_session).delete(revoke_session),
+        )
+        .merge(protected)
+        .merge(integrations)
+        .merge(admin)
+        .layer(middleware::from_fn_with_state(state.clone(), csrf::protect))
         .with_state(state)
 }
 
 async fn root() -> &'static str {
     "Welcome to the SaaS API"
-}
\ No newline at end of file
+}
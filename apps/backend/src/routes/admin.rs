@@ -0,0 +1,418 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use redis::aio::MultiplexedConnection;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    auth::{admin, extractor::AuthUser, session},
+    db,
+    errors::AppError,
+    models::{
+        admin::{AdminUser, AuditEntry},
+        organization::{MemberOrganization, Role},
+        user::User,
+    },
+    routes::auth::send_password_reset_email,
+    state::AppState,
+};
+
+// Support staff acting on accounts, every action lands in the audit trail.
+
+const DEFAULT_PER_PAGE: i64 = 20;
+const MAX_PER_PAGE: i64 = 100;
+
+#[derive(serde::Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum UserStatus {
+    Active,
+    Suspended,
+}
+
+#[derive(serde::Deserialize)]
+pub struct UserSearchQuery {
+    // Part of the email address or name.
+    q: Option<String>,
+    status: Option<UserStatus>,
+    verified: Option<bool>,
+    page: Option<i64>,
+    per_page: Option<i64>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct AuditLogQuery {
+    user_id: Option<Uuid>,
+    action: Option<String>,
+    page: Option<i64>,
+    per_page: Option<i64>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct SuspendPayload {
+    reason: Option<String>,
+}
+
+/// Pages start at 1. Returns the page, its size and the rows to skip.
+fn pagination(page: Option<i64>, per_page: Option<i64>) -> Result<(i64, i64, i64), AppError> {
+    let page = page.unwrap_or(1);
+    let per_page = per_page.unwrap_or(DEFAULT_PER_PAGE);
+    if page < 1 || !(1..=MAX_PER_PAGE).contains(&per_page) {
+        return Err(AppError::BadRequest(format!(
+            "Pages start at 1 and hold at most {} entries",
+            MAX_PER_PAGE
+        )));
+    }
+    Ok((page, per_page, (page - 1) * per_page))
+}
+
+// The search is matched literally.
+fn like_pattern(q: &str) -> String {
+    let escaped = q
+        .trim()
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+async fn find_user(state: &AppState, id: Uuid) -> Result<User, AppError> {
+    sqlx::query_as!(User, "SELECT * FROM users WHERE id = $1", id)
+        .fetch_optional(&state.pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))
+}
+
+// Staff cannot lock themselves out.
+fn ensure_other_user(auth: &AuthUser, id: Uuid) -> Result<(), AppError> {
+    if auth.user.id == id {
+        return Err(AppError::BadRequest(
+            "This cannot be done to your own account".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+async fn redis_connection(state: &AppState) -> Result<MultiplexedConnection, AppError> {
+    state
+        .redis
+        .get_multiplexed_async_connection()
+        .await
+        .map_err(AppError::Redis)
+}
+
+pub async fn search_users(
+    State(state): State<AppState>,
+    Query(query): Query<UserSearchQuery>,
+) -> Result<Response, AppError> {
+    let (page, per_page, offset) = pagination(query.page, query.per_page)?;
+    let pattern = query
+        .q
+        .as_deref()
+        .filter(|q| !q.trim().is_empty())
+        .map(like_pattern);
+    let suspended = query.status.map(|status| matches!(status, UserStatus::Suspended));
+
+    let total = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) AS "count!" FROM users
+        WHERE ($1::TEXT IS NULL OR email ILIKE $1 OR name ILIKE $1)
+            AND ($2::BOOLEAN IS NULL OR (suspended_at IS NOT NULL) = $2)
+            AND ($3::BOOLEAN IS NULL OR (email_verified_at IS NOT NULL) = $3)"#,
+        pattern,
+        suspended,
+        query.verified
+    )
+    .fetch_one(&state.pool)
+    .await?;
+
+    let users = sqlx::query_as!(
+        User,
+        r#"SELECT * FROM users
+        WHERE ($1::TEXT IS NULL OR email ILIKE $1 OR name ILIKE $1)
+            AND ($2::BOOLEAN IS NULL OR (suspended_at IS NOT NULL) = $2)
+            AND ($3::BOOLEAN IS NULL OR (email_verified_at IS NOT NULL) = $3)
+        ORDER BY created_at DESC, id
+        LIMIT $4 OFFSET $5"#,
+        pattern,
+        suspended,
+        query.verified,
+        per_page,
+        offset
+    )
+    .fetch_all(&state.pool)
+    .await?;
+    let users: Vec<AdminUser> = users.into_iter().map(AdminUser::from).collect();
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "users": users,
+            "page": page,
+            "per_page": per_page,
+            "total": total,
+        })),
+    )
+        .into_response())
+}
+
+pub async fn get_user(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> Result<Response, AppError> {
+    let user = find_user(&state, id).await?;
+
+    let mut tx = db::begin_cross_tenant(&state.pool).await?;
+    let organizations = sqlx::query_as!(
+        MemberOrganization,
+        r#"SELECT o.id, o.name, o.personal_user_id, m.role AS "role: Role", o.created_at, o.updated_at,
+            o.auto_join_domain, o.auto_join_domain_verified_at
+        FROM organizations o JOIN memberships m ON m.organization_id = o.id
+        WHERE m.user_id = $1
+        ORDER BY o.personal_user_id IS NULL, o.created_at"#,
+        id
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "user": AdminUser::from(user),
+            "organizations": organizations,
+        })),
+    )
+        .into_response())
+}
+
+pub async fn list_user_sessions(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> Result<Response, AppError> {
+    find_user(&state, id).await?;
+
+    let mut redis_conn = redis_connection(&state).await?;
+    let sessions = session::list(&mut redis_conn, id, None).await?;
+
+    Ok((StatusCode::OK, Json(json!({ "sessions": sessions }))).into_response())
+}
+
+/// Signs the user out everywhere and keeps them from signing in, personal
+/// access tokens included, until the account is unsuspended.
+pub async fn suspend_user(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<SuspendPayload>,
+) -> Result<Response, AppError> {
+    ensure_other_user(&auth, id)?;
+
+    let mut tx = state.pool.begin().await?;
+    let user = sqlx::query_as!(
+        User,
+        "UPDATE users SET suspended_at = NOW(), updated_at = NOW() WHERE id = $1 AND suspended_at IS NULL RETURNING *",
+        id
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    let Some(user) = user else {
+        find_user(&state, id).await?;
+        return Err(AppError::Conflict("The account is already suspended".to_string()));
+    };
+    admin::record(
+        &mut *tx,
+        Some(auth.user.id),
+        admin::SUSPEND,
+        id,
+        json!({ "reason": payload.reason }),
+    )
+    .await?;
+    tx.commit().await?;
+
+    let mut redis_conn = redis_connection(&state).await?;
+    session::revoke_all(&mut redis_conn, id, None).await?;
+
+    Ok((StatusCode::OK, Json(AdminUser::from(user))).into_response())
+}
+
+pub async fn unsuspend_user(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Response, AppError> {
+    let mut tx = state.pool.begin().await?;
+    let user = sqlx::query_as!(
+        User,
+        "UPDATE users SET suspended_at = NULL, updated_at = NOW() WHERE id = $1 AND suspended_at IS NOT NULL RETURNING *",
+        id
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    let Some(user) = user else {
+        find_user(&state, id).await?;
+        return Err(AppError::Conflict("The account is not suspended".to_string()));
+    };
+    admin::record(&mut *tx, Some(auth.user.id), admin::UNSUSPEND, id, json!({})).await?;
+    tx.commit().await?;
+
+    Ok((StatusCode::OK, Json(AdminUser::from(user))).into_response())
+}
+
+/// Signs the user out and emails them a reset link. Their password stops
+/// working until they choose a new one, other sign-in methods still do.
+pub async fn force_password_reset(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Response, AppError> {
+    ensure_other_user(&auth, id)?;
+
+    let mut tx = state.pool.begin().await?;
+    let user = sqlx::query_as!(
+        User,
+        "UPDATE users SET password_reset_required = TRUE, updated_at = NOW() WHERE id = $1 RETURNING *",
+        id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+    admin::record(
+        &mut *tx,
+        Some(auth.user.id),
+        admin::FORCE_PASSWORD_RESET,
+        id,
+        json!({}),
+    )
+    .await?;
+    tx.commit().await?;
+
+    let mut redis_conn = redis_connection(&state).await?;
+    session::revoke_all(&mut redis_conn, id, None).await?;
+    // The reset is in force either way, the user can still use "forgot
+    // password" if the email does not arrive.
+    if let Err(err) = send_password_reset_email(
+        &state,
+        &mut redis_conn,
+        &user,
+        "Our support team asked you to choose a new password for your account.",
+        "Your current password no longer signs you in.",
+    )
+    .await
+    {
+        tracing::error!("Failed to send password reset email: {:?}", err);
+    }
+
+    Ok((StatusCode::OK, Json(AdminUser::from(user))).into_response())
+}
+
+/// Revokes every session of the user, their access tokens stop working too.
+pub async fn force_logout(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Response, AppError> {
+    ensure_other_user(&auth, id)?;
+    find_user(&state, id).await?;
+
+    let mut redis_conn = redis_connection(&state).await?;
+    let sessions = session::list(&mut redis_conn, id, None).await?.len();
+    session::revoke_all(&mut redis_conn, id, None).await?;
+    admin::record(
+        &state.pool,
+        Some(auth.user.id),
+        admin::FORCE_LOGOUT,
+        id,
+        json!({ "sessions": sessions }),
+    )
+    .await?;
+
+    Ok((StatusCode::OK, Json(json!({ "status": "success", "sessions": sessions }))).into_response())
+}
+
+/// Deletes the account along with its personal organization. Refused while
+/// it is the last owner of another organization, which would be left
+/// unmanageable.
+pub async fn delete_user(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Response, AppError> {
+    ensure_other_user(&auth, id)?;
+    let user = find_user(&state, id).await?;
+
+    let mut tx = db::begin_cross_tenant(&state.pool).await?;
+    let sole_owner_of = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) AS "count!" FROM memberships m
+        JOIN organizations o ON o.id = m.organization_id
+        WHERE m.user_id = $1 AND m.role = $2 AND o.personal_user_id IS NULL
+            AND NOT EXISTS (
+                SELECT 1 FROM memberships other
+                WHERE other.organization_id = m.organization_id
+                    AND other.role = $2 AND other.user_id <> $1
+            )"#,
+        id,
+        Role::Owner as Role
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    if sole_owner_of > 0 {
+        return Err(AppError::Conflict(format!(
+            "The user is the only owner of {} organization(s), transfer them first",
+            sole_owner_of
+        )));
+    }
+
+    // The trail keeps who the account was.
+    admin::record(
+        &mut *tx,
+        Some(auth.user.id),
+        admin::DELETE_USER,
+        id,
+        json!({ "email": user.email }),
+    )
+    .await?;
+    sqlx::query!("DELETE FROM users WHERE id = $1", id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    let mut redis_conn = redis_connection(&state).await?;
+    session::revoke_all(&mut redis_conn, id, None).await?;
+
+    Ok((StatusCode::OK, Json(json!({"status": "success"}))).into_response())
+}
+
+pub async fn list_audit_log(
+    State(state): State<AppState>,
+    Query(query): Query<AuditLogQuery>,
+) -> Result<Response, AppError> {
+    let (page, per_page, offset) = pagination(query.page, query.per_page)?;
+
+    let entries = sqlx::query_as!(
+        AuditEntry,
+        r#"SELECT * FROM admin_audit_log
+        WHERE ($1::UUID IS NULL OR target_user_id = $1)
+            AND ($2::TEXT IS NULL OR action = $2)
+        ORDER BY created_at DESC, id
+        LIMIT $3 OFFSET $4"#,
+        query.user_id,
+        query.action,
+        per_page,
+        offset
+    )
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "entries": entries,
+            "page": page,
+            "per_page": per_page,
+        })),
+    )
+        .into_response())
+}
@@ -1,17 +1,21 @@
 use axum::{
-    extract::State,
+    extract::{Path, State},
     http::StatusCode,
-    response::{IntoResponse, Response},
+    response::{IntoResponse, Redirect, Response},
     Json,
 };
-use axum_extra::extract::cookie::{Cookie, SameSite, CookieJar};
-use secrecy::{ExposeSecret, Secret};
+use axum_extra::extract::cookie::CookieJar;
+use secrecy::Secret;
 use redis::aio::MultiplexedConnection;
-use redis::AsyncCommands;
-use crate::utils::token::create_jwt_token;
 
 use crate::{
-    auth::{password},
+    auth::{
+        admin, cookies, csrf, extractor::AuthUser, invitation, membership, mfa, password,
+        session::{self, ClientInfo},
+        single_use,
+    },
+    config::{EmailVerificationPolicy, TokenDelivery},
+    db,
     errors::AppError,
     models::user::{CreateUser, User},
     state::AppState,
@@ -23,34 +27,280 @@ pub struct AuthPayload {
     password: Secret<String>,
 }
 
+#[derive(serde::Deserialize)]
+pub struct RefreshPayload {
+    refresh_token: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct RenameSessionPayload {
+    name: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct VerifyEmailPayload {
+    token: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct EmailPayload {
+    pub(crate) email: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct ResetPasswordPayload {
+    token: String,
+    password: Secret<String>,
+}
+
+const VERIFY_EMAIL: &str = "verify_email";
+const RESET_PASSWORD: &str = "reset_password";
+
+async fn send_verification_email(
+    state: &AppState,
+    redis_conn: &mut MultiplexedConnection,
+    user: &User,
+) -> Result<(), AppError> {
+    let ttl = duration_str::parse(&state.config.auth.email_verification_expires_in)?.as_secs();
+    let token = single_use::issue(redis_conn, VERIFY_EMAIL, &user.id.to_string(), ttl).await?;
+
+    let link = format!(
+        "{}/verify-email?token={}",
+        state.config.server.public_url, token
+    );
+    state
+        .mailer
+        .send(
+            &user.email,
+            "Verify your email address",
+            &format!(
+                "Welcome!\n\nConfirm your email address by opening the link below:\n{}\n\nIf you did not create an account, you can ignore this email.",
+                link
+            ),
+        )
+        .await
+}
+
+struct SessionTokens {
+    session_id: uuid::Uuid,
+    access_token: String,
+    refresh_token: String,
+}
+
+async fn issue_session(
+    state: &AppState,
+    redis_conn: &mut MultiplexedConnection,
+    user_id: uuid::Uuid,
+    client: &ClientInfo,
+) -> Result<SessionTokens, AppError> {
+    // Every way of signing in ends up here.
+    let user = sqlx::query_as!(User, "SELECT * FROM users WHERE id = $1", user_id)
+        .fetch_one(&state.pool)
+        .await?;
+    admin::ensure_active(&user)?;
+
+    let org = membership::default_for(&state.pool, user_id).await?;
+    let (family_id, refresh_token) = session::start_family(
+        redis_conn,
+        &state.config.auth,
+        user_id,
+        client,
+        org.as_ref().map(|m| m.organization_id),
+    )
+    .await?;
+    let access_token = session::issue_access_token(
+        redis_conn,
+        &state.jwt,
+        &state.config.auth,
+        user_id,
+        family_id,
+        org.as_ref(),
+    )
+    .await?;
+
+    Ok(SessionTokens {
+        session_id: family_id,
+        access_token,
+        refresh_token,
+    })
+}
+
+fn with_cookies(
+    state: &AppState,
+    mut response: Response,
+    tokens: SessionTokens,
+) -> Result<Response, AppError> {
+    let config = &state.config.cookie;
+    let access_max_age = duration_str::parse(&state.config.auth.jwt_access_expires_in)?;
+    let refresh_max_age = duration_str::parse(&state.config.auth.jwt_refresh_expires_in)?;
+    let csrf_token = csrf::token(&state.config.auth, tokens.session_id);
+
+    for cookie in [
+        cookies::ACCESS_TOKEN.build(config, tokens.access_token, access_max_age),
+        cookies::REFRESH_TOKEN.build(config, tokens.refresh_token, refresh_max_age),
+        cookies::CSRF_TOKEN.build(config, csrf_token, refresh_max_age),
+    ] {
+        response
+            .headers_mut()
+            .append("set-cookie", cookie.to_string().parse().unwrap());
+    }
+
+    Ok(response)
+}
+
+/// Hands the tokens over the way the client asked for them: as cookies, or
+/// added to the JSON `body`.
+fn deliver(
+    state: &AppState,
+    client: &ClientInfo,
+    tokens: SessionTokens,
+    status: StatusCode,
+    mut body: serde_json::Value,
+) -> Result<Response, AppError> {
+    match client.delivery {
+        TokenDelivery::Cookie => with_cookies(state, (status, Json(body)).into_response(), tokens),
+        TokenDelivery::Body => {
+            let expires_in =
+                duration_str::parse(&state.config.auth.jwt_access_expires_in)?.as_secs();
+            body["token_type"] = "Bearer".into();
+            body["access_token"] = tokens.access_token.into();
+            body["expires_in"] = expires_in.into();
+            body["refresh_token"] = tokens.refresh_token.into();
+            Ok((status, Json(body)).into_response())
+        }
+    }
+}
+
+/// Responds with `body` and a new access token for the current session, the
+/// refresh token does not change.
+pub(crate) fn deliver_access_token(
+    state: &AppState,
+    client: &ClientInfo,
+    access_token: String,
+    status: StatusCode,
+    mut body: serde_json::Value,
+) -> Result<Response, AppError> {
+    let max_age = duration_str::parse(&state.config.auth.jwt_access_expires_in)?;
+    match client.delivery {
+        TokenDelivery::Cookie => {
+            let cookie = cookies::ACCESS_TOKEN.build(&state.config.cookie, access_token, max_age);
+            Ok((status, [("set-cookie", cookie.to_string())], Json(body)).into_response())
+        }
+        TokenDelivery::Body => {
+            body["token_type"] = "Bearer".into();
+            body["access_token"] = access_token.into();
+            body["expires_in"] = max_age.as_secs().into();
+            Ok((status, Json(body)).into_response())
+        }
+    }
+}
+
+/// Starts a session and responds with `body` and its tokens.
+pub(crate) async fn start_session(
+    state: &AppState,
+    redis_conn: &mut MultiplexedConnection,
+    user_id: uuid::Uuid,
+    client: &ClientInfo,
+    status: StatusCode,
+    body: serde_json::Value,
+) -> Result<Response, AppError> {
+    let tokens = issue_session(state, redis_conn, user_id, client).await?;
+    deliver(state, client, tokens, status, body)
+}
+
+/// Ends a browser sign-in flow (social login, magic link) with a redirect to
+/// `destination`, or to the second factor page when the account has one.
+pub(crate) async fn redirect_sign_in(
+    state: &AppState,
+    redis_conn: &mut MultiplexedConnection,
+    user: &User,
+    client: &ClientInfo,
+    jar: CookieJar,
+    destination: &str,
+) -> Result<Response, AppError> {
+    admin::ensure_active(user)?;
+    if state.config.auth.email_verification == EmailVerificationPolicy::Required
+        && user.email_verified_at.is_none()
+    {
+        return Err(AppError::Forbidden("Email not verified".to_string()));
+    }
+
+    if !mfa::methods(&state.pool, user.id).await?.is_empty() {
+        let mfa_token = mfa::start_challenge(redis_conn, user.id).await?;
+        let destination = format!(
+            "{}/login/mfa#mfa_token={}",
+            state.config.server.public_url, mfa_token
+        );
+        return Ok((jar, Redirect::to(&destination)).into_response());
+    }
+
+    // Browsers follow the redirect, so tokens always go in cookies here.
+    let tokens = issue_session(state, redis_conn, user.id, client).await?;
+    with_cookies(
+        state,
+        (jar, Redirect::to(destination)).into_response(),
+        tokens,
+    )
+}
+
+fn refresh_token_from(state: &AppState, jar: &CookieJar) -> Result<String, AppError> {
+    cookies::REFRESH_TOKEN
+        .get(&state.config.cookie, jar)
+        .ok_or_else(|| AppError::Unauthorized("Missing refresh token".to_string()))
+}
+
+// Clients without cookies send the refresh token in the body instead.
+fn refresh_token_in(
+    state: &AppState,
+    jar: &CookieJar,
+    payload: Option<Json<RefreshPayload>>,
+) -> Result<String, AppError> {
+    match payload {
+        Some(Json(payload)) => Ok(payload.refresh_token),
+        None => refresh_token_from(state, jar),
+    }
+}
+
+/// The user and session making the request: the session of the refresh
+/// token cookie, else the one bearer clients got their access token from.
+async fn current_session(
+    state: &AppState,
+    redis_conn: &mut MultiplexedConnection,
+    jar: &CookieJar,
+    auth: Result<AuthUser, AppError>,
+) -> Result<(uuid::Uuid, uuid::Uuid), AppError> {
+    match cookies::REFRESH_TOKEN.get(&state.config.cookie, jar) {
+        Some(refresh_token) => {
+            session::current(redis_conn, &state.config.auth, &refresh_token).await
+        }
+        None => session::of_access_token(redis_conn, auth?.require_session()?).await,
+    }
+}
+
 pub async fn register(
     State(state): State<AppState>,
+    client: ClientInfo,
     Json(payload): Json<CreateUser>,
 ) -> Result<Response, AppError> {
     let hashed_password = password::hash_password(Secret::new(payload.password))
         .await
         .map_err(AppError::Password)?;
 
+    // The invitation tells which organization to join.
+    let mut tx = db::begin_cross_tenant(&state.pool).await?;
     let user = sqlx::query_as!(
         User,
         "INSERT INTO users (email, password_hash) VALUES ($1, $2) RETURNING *",
         payload.email,
         hashed_password
     )
-    .fetch_one(&state.pool)
+    .fetch_one(&mut *tx)
     .await?;
-
-    let access_token = create_jwt_token(
-        user.id,
-        state.config.auth.jwt_access_secret.expose_secret(),
-        &state.config.auth.jwt_access_expires_in,
-    )?;
-
-    let refresh_token = create_jwt_token(
-        user.id,
-        state.config.auth.jwt_refresh_secret.expose_secret(),
-        &state.config.auth.jwt_refresh_expires_in,
-    )?;
+    membership::create_personal(&mut tx, user.id).await?;
+    if let Some(token) = &payload.invitation_token {
+        invitation::accept(&mut tx, token, &user).await?;
+    }
+    tx.commit().await?;
 
     let mut redis_conn: MultiplexedConnection = state
         .redis
@@ -58,42 +308,29 @@ pub async fn register(
         .await
         .map_err(AppError::Redis)?;
 
-    redis_conn
-        .set_ex::<&str, String, ()>(
-            &refresh_token,
-            user.id.to_string(),
-            60 * 60 * 24 * 7,
-        )
-        .await
-        .map_err(AppError::Redis)?;
+    // The account exists either way, the user can ask for a new email later.
+    if let Err(err) = send_verification_email(&state, &mut redis_conn, &user).await {
+        tracing::error!("Failed to send verification email: {:?}", err);
+    }
 
-    let access_cookie = Cookie::build(("access_token", access_token))
-        .path("/")
-        .http_only(true)
-        .same_site(SameSite::Strict)
-        .secure(true)
-        .build();
-
-    let refresh_cookie = Cookie::build(("refresh_token", refresh_token))
-        .path("/")
-        .http_only(true)
-        .same_site(SameSite::Strict)
-        .secure(true)
-        .build();
-
-    let mut response = (StatusCode::CREATED, Json(serde_json::json!({"user": user}))).into_response();
-    response
-        .headers_mut()
-        .append("set-cookie", access_cookie.to_string().parse().unwrap());
-    response
-        .headers_mut()
-        .append("set-cookie", refresh_cookie.to_string().parse().unwrap());
+    if state.config.auth.email_verification == EmailVerificationPolicy::Required {
+        return Ok((StatusCode::CREATED, Json(serde_json::json!({"user": user}))).into_response());
+    }
 
-    Ok(response)
+    start_session(
+        &state,
+        &mut redis_conn,
+        user.id,
+        &client,
+        StatusCode::CREATED,
+        serde_json::json!({"user": user}),
+    )
+    .await
 }
 
 pub async fn login(
     State(state): State<AppState>,
+    client: ClientInfo,
     Json(payload): Json<AuthPayload>,
 ) -> Result<Response, AppError> {
     let user = sqlx::query_as!(
@@ -112,18 +349,18 @@ pub async fn login(
     if !is_valid {
         return Err(AppError::Unauthorized("Invalid credentials".to_string()));
     }
+    admin::ensure_active(&user)?;
+    if user.password_reset_required {
+        return Err(AppError::Forbidden(
+            "Password reset required, follow the link sent by email".to_string(),
+        ));
+    }
 
-    let access_token = create_jwt_token(
-        user.id,
-        state.config.auth.jwt_access_secret.expose_secret(),
-        &state.config.auth.jwt_access_expires_in,
-    )?;
-
-    let refresh_token = create_jwt_token(
-        user.id,
-        state.config.auth.jwt_refresh_secret.expose_secret(),
-        &state.config.auth.jwt_refresh_expires_in,
-    )?;
+    if state.config.auth.email_verification == EmailVerificationPolicy::Required
+        && user.email_verified_at.is_none()
+    {
+        return Err(AppError::Forbidden("Email not verified".to_string()));
+    }
 
     let mut redis_conn: MultiplexedConnection = state
         .redis
@@ -131,97 +368,334 @@ pub async fn login(
         .await
         .map_err(AppError::Redis)?;
 
-    redis_conn
-        .set_ex::<&str, String, ()>(
-            &refresh_token,
-            user.id.to_string(),
-            60 * 60 * 24 * 7,
+    // With two-factor authentication enabled, the password alone only earns
+    // a challenge token to be completed at `/auth/mfa/verify`.
+    let methods = mfa::methods(&state.pool, user.id).await?;
+    if !methods.is_empty() {
+        let mfa_token = mfa::start_challenge(&mut redis_conn, user.id).await?;
+        return Ok((
+            StatusCode::OK,
+            Json(serde_json::json!({
+                "mfa_required": true,
+                "mfa_token": mfa_token,
+                "methods": methods,
+            })),
         )
+            .into_response());
+    }
+
+    start_session(
+        &state,
+        &mut redis_conn,
+        user.id,
+        &client,
+        StatusCode::OK,
+        serde_json::json!({"status": "success"}),
+    )
+    .await
+}
+
+pub async fn refresh(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    jar: CookieJar,
+    payload: Option<Json<RefreshPayload>>,
+) -> Result<Response, AppError> {
+    let refresh_token = refresh_token_in(&state, &jar, payload)?;
+
+    let mut redis_conn: MultiplexedConnection = state
+        .redis
+        .get_multiplexed_async_connection()
         .await
         .map_err(AppError::Redis)?;
 
-    let access_cookie = Cookie::build(("access_token", access_token))
-        .path("/")
-        .http_only(true)
-        .same_site(SameSite::Strict)
-        .secure(true)
-        .build();
+    let (user_id, family_id, refresh_token) =
+        session::rotate(&mut redis_conn, &state.config.auth, &refresh_token, &client).await?;
+
+    let user = sqlx::query_as!(
+        User,
+        "SELECT * FROM users WHERE id = $1",
+        user_id
+    )
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or_else(|| AppError::Unauthorized("Invalid refresh token".to_string()))?;
+    admin::ensure_active(&user)?;
+
+    if state.config.auth.email_verification == EmailVerificationPolicy::Required
+        && user.email_verified_at.is_none()
+    {
+        return Err(AppError::Forbidden("Email not verified".to_string()));
+    }
+
+    // Membership may have changed since the session picked its organization.
+    let preferred = session::active_org(&mut redis_conn, family_id).await?;
+    let org = membership::resolve_active(&state.pool, user.id, preferred).await?;
+    let access_token = session::issue_access_token(
+        &mut redis_conn,
+        &state.jwt,
+        &state.config.auth,
+        user.id,
+        family_id,
+        org.as_ref(),
+    )
+    .await?;
+
+    let tokens = SessionTokens {
+        session_id: family_id,
+        access_token,
+        refresh_token,
+    };
+    deliver(
+        &state,
+        &client,
+        tokens,
+        StatusCode::OK,
+        serde_json::json!({"status": "success"}),
+    )
+}
 
-    let refresh_cookie = Cookie::build(("refresh_token", refresh_token))
-        .path("/")
-        .http_only(true)
-        .same_site(SameSite::Strict)
-        .secure(true)
-        .build();
+pub async fn logout(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    payload: Option<Json<RefreshPayload>>,
+) -> Result<Response, AppError> {
+    let refresh_token = refresh_token_in(&state, &jar, payload)?;
 
+    let mut redis_conn: MultiplexedConnection = state
+        .redis
+        .get_multiplexed_async_connection()
+        .await
+        .map_err(AppError::Redis)?;
+
+    session::revoke(&mut redis_conn, &state.config.auth, &refresh_token).await?;
+
+    // Expired cookies make the browser drop the tokens right away.
+    let config = &state.config.cookie;
     let mut response = (StatusCode::OK, Json(serde_json::json!({"status": "success"}))).into_response();
-    response
-        .headers_mut()
-        .append("set-cookie", access_cookie.to_string().parse().unwrap());
-    response
-        .headers_mut()
-        .append("set-cookie", refresh_cookie.to_string().parse().unwrap());
+    for spec in [cookies::ACCESS_TOKEN, cookies::REFRESH_TOKEN, cookies::CSRF_TOKEN] {
+        response
+            .headers_mut()
+            .append("set-cookie", spec.expired(config).to_string().parse().unwrap());
+    }
 
     Ok(response)
 }
 
-pub async fn refresh(
+pub async fn verify_email(
     State(state): State<AppState>,
-    jar: CookieJar,
+    Json(payload): Json<VerifyEmailPayload>,
 ) -> Result<Response, AppError> {
-    let refresh_token = jar
-        .get("refresh_token")
-        .map(|c| c.value().to_string())
-        .ok_or_else(|| AppError::Unauthorized("Missing refresh token".to_string()))?;
+    let mut redis_conn: MultiplexedConnection = state
+        .redis
+        .get_multiplexed_async_connection()
+        .await
+        .map_err(AppError::Redis)?;
 
+    let user_id: uuid::Uuid = single_use::consume(&mut redis_conn, VERIFY_EMAIL, &payload.token)
+        .await?
+        .ok_or_else(|| AppError::BadRequest("Invalid or expired verification token".to_string()))?
+        .parse()?;
+
+    sqlx::query!(
+        "UPDATE users SET email_verified_at = NOW(), updated_at = NOW() WHERE id = $1 AND email_verified_at IS NULL",
+        user_id
+    )
+    .execute(&state.pool)
+    .await?;
+    membership::auto_join(&state.pool, user_id).await?;
+
+    Ok((StatusCode::OK, Json(serde_json::json!({"status": "success"}))).into_response())
+}
+
+pub async fn resend_verification(
+    State(state): State<AppState>,
+    Json(payload): Json<EmailPayload>,
+) -> Result<Response, AppError> {
+    let user = sqlx::query_as!(
+        User,
+        "SELECT * FROM users WHERE email = $1",
+        payload.email
+    )
+    .fetch_optional(&state.pool)
+    .await?;
+
+    // Same answer whether the account exists or not, so that this endpoint
+    // cannot be used to enumerate accounts.
+    if let Some(user) = user.filter(|u| u.email_verified_at.is_none()) {
+        if let Err(err) = resend_verification_email(&state, &user).await {
+            tracing::error!("Failed to send verification email: {:?}", err);
+        }
+    }
+
+    Ok((StatusCode::ACCEPTED, Json(serde_json::json!({"status": "accepted"}))).into_response())
+}
+
+/// Same throttle as `request_password_reset`.
+async fn resend_verification_email(state: &AppState, user: &User) -> Result<(), AppError> {
     let mut redis_conn: MultiplexedConnection = state
         .redis
         .get_multiplexed_async_connection()
         .await
         .map_err(AppError::Redis)?;
 
-    let user_id: String = redis_conn
-        .get::<_, String>(&refresh_token)
+    let key = format!("verification_resend:{}", user.id);
+    if !single_use::mark(&mut redis_conn, &key, 60).await? {
+        return Ok(());
+    }
+    if let Err(err) = send_verification_email(state, &mut redis_conn, user).await {
+        single_use::unmark(&mut redis_conn, &key).await?;
+        return Err(err);
+    }
+    Ok(())
+}
+
+/// Emails `user` a link to choose a new password, between `intro` and
+/// `outro`.
+pub(crate) async fn send_password_reset_email(
+    state: &AppState,
+    redis_conn: &mut MultiplexedConnection,
+    user: &User,
+    intro: &str,
+    outro: &str,
+) -> Result<(), AppError> {
+    let ttl = duration_str::parse(&state.config.auth.password_reset_expires_in)?.as_secs();
+    let token = single_use::issue(redis_conn, RESET_PASSWORD, &user.id.to_string(), ttl).await?;
+
+    let link = format!(
+        "{}/reset-password?token={}",
+        state.config.server.public_url, token
+    );
+    state
+        .mailer
+        .send(
+            &user.email,
+            "Reset your password",
+            &format!(
+                "{}\n\nChoose a new password by opening the link below:\n{}\n\n{}",
+                intro, link, outro
+            ),
+        )
+        .await
+}
+
+/// At most one email a minute. The throttle is lifted when sending fails so
+/// that the user can try again right away.
+async fn request_password_reset(state: &AppState, user: &User) -> Result<(), AppError> {
+    let mut redis_conn: MultiplexedConnection = state
+        .redis
+        .get_multiplexed_async_connection()
         .await
-        .map_err(|_| AppError::Unauthorized("Invalid refresh token".to_string()))?;
+        .map_err(AppError::Redis)?;
+
+    let key = format!("password_reset_requested:{}", user.id);
+    if !single_use::mark(&mut redis_conn, &key, 60).await? {
+        return Ok(());
+    }
+    if let Err(err) = send_password_reset_email(
+        state,
+        &mut redis_conn,
+        user,
+        "Someone asked to reset the password of your account.",
+        "If it was not you, you can ignore this email.",
+    )
+    .await
+    {
+        single_use::unmark(&mut redis_conn, &key).await?;
+        return Err(err);
+    }
+    Ok(())
+}
 
+pub async fn forgot_password(
+    State(state): State<AppState>,
+    Json(payload): Json<EmailPayload>,
+) -> Result<Response, AppError> {
     let user = sqlx::query_as!(
         User,
-        "SELECT * FROM users WHERE id = $1",
-        user_id.parse::<uuid::Uuid>().unwrap()
+        "SELECT * FROM users WHERE email = $1",
+        payload.email
     )
-    .fetch_one(&state.pool)
+    .fetch_optional(&state.pool)
     .await?;
 
-    let access_token = create_jwt_token(
-        user.id,
-        state.config.auth.jwt_access_secret.expose_secret(),
-        &state.config.auth.jwt_access_expires_in,
-    )?;
+    // Always 202, whether the account exists or not, so a failure is only
+    // logged.
+    if let Some(user) = user {
+        if let Err(err) = request_password_reset(&state, &user).await {
+            tracing::error!("Failed to send password reset email: {:?}", err);
+        }
+    }
 
-    let access_cookie = Cookie::build(("access_token", access_token))
-        .path("/")
-        .http_only(true)
-        .same_site(SameSite::Strict)
-        .secure(true)
-        .build();
+    Ok((StatusCode::ACCEPTED, Json(serde_json::json!({"status": "accepted"}))).into_response())
+}
 
-    let mut response = (StatusCode::OK, Json(serde_json::json!({"status": "success"}))).into_response();
-    response
-        .headers_mut()
-        .append("set-cookie", access_cookie.to_string().parse().unwrap());
+pub async fn reset_password(
+    State(state): State<AppState>,
+    Json(payload): Json<ResetPasswordPayload>,
+) -> Result<Response, AppError> {
+    password::validate_strength(&payload.password)?;
 
-    Ok(response)
+    let mut redis_conn: MultiplexedConnection = state
+        .redis
+        .get_multiplexed_async_connection()
+        .await
+        .map_err(AppError::Redis)?;
+
+    let user_id: uuid::Uuid = single_use::consume(&mut redis_conn, RESET_PASSWORD, &payload.token)
+        .await?
+        .ok_or_else(|| AppError::BadRequest("Invalid or expired reset token".to_string()))?
+        .parse()?;
+
+    let hashed_password = password::hash_password(payload.password)
+        .await
+        .map_err(AppError::Password)?;
+
+    // Following the emailed link also proves ownership of the address.
+    sqlx::query!(
+        "UPDATE users SET password_hash = $1, password_reset_required = FALSE, email_verified_at = COALESCE(email_verified_at, NOW()), updated_at = NOW() WHERE id = $2",
+        hashed_password,
+        user_id
+    )
+    .execute(&state.pool)
+    .await?;
+    membership::auto_join(&state.pool, user_id).await?;
+
+    session::revoke_all(&mut redis_conn, user_id, None).await?;
+
+    Ok((StatusCode::OK, Json(serde_json::json!({"status": "success"}))).into_response())
 }
 
-pub async fn logout(
+pub async fn list_sessions(
     State(state): State<AppState>,
     jar: CookieJar,
+    auth: Result<AuthUser, AppError>,
 ) -> Result<Response, AppError> {
-    let refresh_token = jar
-        .get("refresh_token")
-        .map(|c| c.value().to_string())
-        .ok_or_else(|| AppError::Unauthorized("Missing refresh token".to_string()))?;
+    let mut redis_conn: MultiplexedConnection = state
+        .redis
+        .get_multiplexed_async_connection()
+        .await
+        .map_err(AppError::Redis)?;
+
+    let (user_id, current) = current_session(&state, &mut redis_conn, &jar, auth).await?;
+    let sessions = session::list(&mut redis_conn, user_id, Some(current)).await?;
+
+    Ok((StatusCode::OK, Json(serde_json::json!({"sessions": sessions}))).into_response())
+}
+
+pub async fn rename_session(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    auth: Result<AuthUser, AppError>,
+    Path(session_id): Path<uuid::Uuid>,
+    Json(payload): Json<RenameSessionPayload>,
+) -> Result<Response, AppError> {
+    let name = payload.name.trim();
+    if name.is_empty() || name.len() > 100 {
+        return Err(AppError::BadRequest(
+            "Session name must be between 1 and 100 characters".to_string(),
+        ));
+    }
 
     let mut redis_conn: MultiplexedConnection = state
         .redis
@@ -229,32 +703,44 @@ pub async fn logout(
         .await
         .map_err(AppError::Redis)?;
 
-    redis_conn
-        .del::<&str, ()>(&refresh_token)
+    let (user_id, _) = current_session(&state, &mut redis_conn, &jar, auth).await?;
+    session::rename(&mut redis_conn, user_id, session_id, name).await?;
+
+    Ok((StatusCode::OK, Json(serde_json::json!({"status": "success"}))).into_response())
+}
+
+pub async fn revoke_session(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    auth: Result<AuthUser, AppError>,
+    Path(session_id): Path<uuid::Uuid>,
+) -> Result<Response, AppError> {
+    let mut redis_conn: MultiplexedConnection = state
+        .redis
+        .get_multiplexed_async_connection()
         .await
         .map_err(AppError::Redis)?;
 
-    let access_cookie = Cookie::build(("access_token", ""))
-        .path("/")
-        .http_only(true)
-        .same_site(SameSite::Strict)
-        .secure(true)
-        .build();
+    let (user_id, _) = current_session(&state, &mut redis_conn, &jar, auth).await?;
+    session::revoke_session(&mut redis_conn, user_id, session_id).await?;
 
-    let refresh_cookie = Cookie::build(("refresh_token", ""))
-        .path("/")
-        .http_only(true)
-        .same_site(SameSite::Strict)
-        .secure(true)
-        .build();
+    Ok((StatusCode::OK, Json(serde_json::json!({"status": "success"}))).into_response())
+}
 
-    let mut response = (StatusCode::OK, Json(serde_json::json!({"status": "success"}))).into_response();
-    response
-        .headers_mut()
-        .append("set-cookie", access_cookie.to_string().parse().unwrap());
-    response
-        .headers_mut()
-        .append("set-cookie", refresh_cookie.to_string().parse().unwrap());
+pub async fn logout_all(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    auth: Result<AuthUser, AppError>,
+) -> Result<Response, AppError> {
+    let mut redis_conn: MultiplexedConnection = state
+        .redis
+        .get_multiplexed_async_connection()
+        .await
+        .map_err(AppError::Redis)?;
 
-    Ok(response)
+    // Keeps the session making the request, every other device is signed out.
+    let (user_id, current) = current_session(&state, &mut redis_conn, &jar, auth).await?;
+    session::revoke_all(&mut redis_conn, user_id, Some(current)).await?;
+
+    Ok((StatusCode::OK, Json(serde_json::json!({"status": "success"}))).into_response())
 }
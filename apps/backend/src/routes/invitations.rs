@@ -0,0 +1,243 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{Duration, Utc};
+use redis::aio::MultiplexedConnection;
+use uuid::Uuid;
+
+use crate::{
+    auth::{
+        extractor::AuthUser,
+        invitation, membership,
+        permission::{OrgAccess, Permission},
+        single_use,
+    },
+    db,
+    errors::AppError,
+    models::organization::{Invitation, Role},
+    state::AppState,
+    utils::token::{generate_opaque_token, hash_token},
+};
+
+#[derive(serde::Deserialize)]
+pub struct InvitePayload {
+    email: String,
+    role: Role,
+}
+
+#[derive(serde::Deserialize)]
+pub struct AcceptInvitationPayload {
+    token: String,
+}
+
+fn expires_at(state: &AppState) -> Result<chrono::DateTime<Utc>, AppError> {
+    let ttl = duration_str::parse(&state.config.auth.invitation_expires_in)?;
+    Ok(Utc::now() + Duration::from_std(ttl).map_err(|_| AppError::InternalServerError)?)
+}
+
+async fn send_invitation(
+    state: &AppState,
+    invitation: &Invitation,
+    token: &str,
+) -> Result<(), AppError> {
+    let mut tx = db::begin_for_org(&state.pool, invitation.organization_id).await?;
+    let org_name = sqlx::query_scalar!(
+        "SELECT name FROM organizations WHERE id = $1",
+        invitation.organization_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    let link = format!(
+        "{}/invitations/accept?token={}",
+        state.config.server.public_url, token
+    );
+    state
+        .mailer
+        .send(
+            &invitation.email,
+            &format!("You have been invited to join {}", org_name),
+            &format!(
+                "You have been invited to join {} as {}.\n\nAccept the invitation by opening the link below, it expires on {}:\n{}\n\nIf you were not expecting this invitation, you can ignore this email.",
+                org_name,
+                invitation.role.as_str(),
+                invitation.expires_at.format("%Y-%m-%d %H:%M UTC"),
+                link
+            ),
+        )
+        .await
+}
+
+pub async fn create_invitation(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    access: OrgAccess,
+    Path(org_id): Path<Uuid>,
+    Json(payload): Json<InvitePayload>,
+) -> Result<Response, AppError> {
+    auth.require_session()?;
+    // Only owners bring in owners, and nobody hands out permissions they do
+    // not hold.
+    if payload.role == Role::Owner && access.role != Some(Role::Owner) {
+        return Err(AppError::Forbidden("Requires the owner role".to_string()));
+    }
+    access.require_all(payload.role.permissions().iter().map(Permission::as_str))?;
+
+    let email = payload.email.trim();
+    if membership::email_domain(email).is_none() || email.len() > 255 {
+        return Err(AppError::BadRequest(format!("Invalid email address: {}", email)));
+    }
+
+    let mut tx = db::begin_for_org(&state.pool, org_id).await?;
+    let already_member = sqlx::query_scalar!(
+        r#"SELECT EXISTS(
+            SELECT 1 FROM memberships m JOIN users u ON u.id = m.user_id
+            WHERE m.organization_id = $1 AND LOWER(u.email) = LOWER($2)
+        ) AS "exists!""#,
+        org_id,
+        email
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    if already_member {
+        return Err(AppError::Conflict(
+            "This address already belongs to a member".to_string(),
+        ));
+    }
+
+    let token = generate_opaque_token();
+    let invitation = sqlx::query_as!(
+        Invitation,
+        r#"INSERT INTO invitations (organization_id, email, role, token_hash, invited_by, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id, organization_id, email, role AS "role: Role", token_hash, invited_by, expires_at, created_at"#,
+        org_id,
+        email,
+        payload.role as Role,
+        hash_token(&token),
+        auth.user.id,
+        expires_at(&state)?
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|err| match err {
+        sqlx::Error::Database(db) if db.is_unique_violation() => AppError::Conflict(
+            "This address already has a pending invitation".to_string(),
+        ),
+        err => err.into(),
+    })?;
+
+    tx.commit().await?;
+    send_invitation(&state, &invitation, &token).await?;
+
+    Ok((StatusCode::CREATED, Json(invitation)).into_response())
+}
+
+pub async fn list_invitations(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(org_id): Path<Uuid>,
+) -> Result<Response, AppError> {
+    auth.require_session()?;
+
+    let mut tx = db::begin_for_org(&state.pool, org_id).await?;
+    // Expired invitations are listed too, they can be resent.
+    let invitations = sqlx::query_as!(
+        Invitation,
+        r#"SELECT id, organization_id, email, role AS "role: Role", token_hash, invited_by, expires_at, created_at
+        FROM invitations WHERE organization_id = $1
+        ORDER BY created_at"#,
+        org_id
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok((StatusCode::OK, Json(invitations)).into_response())
+}
+
+pub async fn revoke_invitation(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path((org_id, id)): Path<(Uuid, Uuid)>,
+) -> Result<Response, AppError> {
+    auth.require_session()?;
+
+    let mut tx = db::begin_for_org(&state.pool, org_id).await?;
+    let deleted = sqlx::query!(
+        "DELETE FROM invitations WHERE id = $1 AND organization_id = $2",
+        id,
+        org_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    if deleted.rows_affected() == 0 {
+        return Err(AppError::NotFound("Invitation not found".to_string()));
+    }
+
+    tx.commit().await?;
+
+    Ok((StatusCode::OK, Json(serde_json::json!({"status": "success"}))).into_response())
+}
+
+/// Sends the invitation again with a new token and expiration, the previous
+/// link stops working.
+pub async fn resend_invitation(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path((org_id, id)): Path<(Uuid, Uuid)>,
+) -> Result<Response, AppError> {
+    auth.require_session()?;
+
+    let mut redis_conn: MultiplexedConnection = state
+        .redis
+        .get_multiplexed_async_connection()
+        .await
+        .map_err(AppError::Redis)?;
+    if !single_use::mark(&mut redis_conn, &format!("invitation_resend:{}", id), 60).await? {
+        return Err(AppError::Conflict(
+            "The invitation was just sent, try again in a minute".to_string(),
+        ));
+    }
+
+    let token = generate_opaque_token();
+    let mut tx = db::begin_for_org(&state.pool, org_id).await?;
+    let invitation = sqlx::query_as!(
+        Invitation,
+        r#"UPDATE invitations SET token_hash = $3, expires_at = $4
+        WHERE id = $1 AND organization_id = $2
+        RETURNING id, organization_id, email, role AS "role: Role", token_hash, invited_by, expires_at, created_at"#,
+        id,
+        org_id,
+        hash_token(&token),
+        expires_at(&state)?
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Invitation not found".to_string()))?;
+
+    tx.commit().await?;
+    send_invitation(&state, &invitation, &token).await?;
+
+    Ok((StatusCode::OK, Json(invitation)).into_response())
+}
+
+pub async fn accept_invitation(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Json(payload): Json<AcceptInvitationPayload>,
+) -> Result<Response, AppError> {
+    auth.require_session()?;
+
+    let mut tx = db::begin_cross_tenant(&state.pool).await?;
+    let membership = invitation::accept(&mut tx, &payload.token, &auth.user).await?;
+    tx.commit().await?;
+
+    Ok((StatusCode::OK, Json(membership)).into_response())
+}
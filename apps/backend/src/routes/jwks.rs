@@ -0,0 +1,19 @@
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+
+use crate::state::AppState;
+
+/// Public keys verifying access tokens, for services that only need to
+/// check them. Empty while tokens are signed with a shared secret.
+pub async fn jwks(State(state): State<AppState>) -> Response {
+    (
+        StatusCode::OK,
+        [(header::CACHE_CONTROL, "public, max-age=300")],
+        Json(state.jwt.jwks()),
+    )
+        .into_response()
+}
@@ -0,0 +1,164 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use axum_extra::extract::cookie::CookieJar;
+use redis::aio::MultiplexedConnection;
+
+use crate::{
+    auth::{cookies, membership, session::ClientInfo, single_use},
+    errors::AppError,
+    models::user::User,
+    routes::auth::{redirect_sign_in, EmailPayload},
+    state::AppState,
+    utils::token::{generate_opaque_token, hash_token},
+};
+
+const MAGIC_LINK: &str = "magic_link";
+
+#[derive(serde::Deserialize)]
+pub struct ConsumeQuery {
+    token: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct PendingMagicLink {
+    user_id: uuid::Uuid,
+    // Hash of the nonce kept in the cookie of the browser that asked for it.
+    browser: String,
+}
+
+pub async fn request_magic_link(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(payload): Json<EmailPayload>,
+) -> Result<Response, AppError> {
+    // Asking again from the same browser keeps links already sent valid.
+    let nonce = cookies::MAGIC_LINK
+        .get(&state.config.cookie, &jar)
+        .unwrap_or_else(generate_opaque_token);
+
+    let ttl = duration_str::parse(&state.config.auth.magic_link_expires_in)?;
+
+    let user = sqlx::query_as!(
+        User,
+        "SELECT * FROM users WHERE email = $1",
+        payload.email
+    )
+    .fetch_optional(&state.pool)
+    .await?;
+
+    // Always 202 with the cookie, whether the account exists or not, so a
+    // failure is only logged.
+    if let Some(user) = user {
+        if let Err(err) = send_magic_link(&state, &user, &nonce, ttl.as_secs()).await {
+            tracing::error!("Failed to send magic link: {:?}", err);
+        }
+    }
+
+    Ok((
+        StatusCode::ACCEPTED,
+        jar.add(cookies::MAGIC_LINK.build(&state.config.cookie, nonce, ttl)),
+        Json(serde_json::json!({"status": "accepted"})),
+    )
+        .into_response())
+}
+
+/// At most one link a minute. The throttle is lifted when sending fails so
+/// that the user can try again right away.
+async fn send_magic_link(
+    state: &AppState,
+    user: &User,
+    nonce: &str,
+    ttl: u64,
+) -> Result<(), AppError> {
+    let mut redis_conn: MultiplexedConnection = state
+        .redis
+        .get_multiplexed_async_connection()
+        .await
+        .map_err(AppError::Redis)?;
+
+    let key = format!("magic_link_requested:{}", user.id);
+    if !single_use::mark(&mut redis_conn, &key, 60).await? {
+        return Ok(());
+    }
+
+    let pending = serde_json::to_string(&PendingMagicLink {
+        user_id: user.id,
+        browser: hash_token(nonce),
+    })
+    .map_err(|_| AppError::InternalServerError)?;
+    let token = single_use::issue(&mut redis_conn, MAGIC_LINK, &pending, ttl).await?;
+
+    let link = format!(
+        "{}/auth/magic-link/consume?token={}",
+        state.config.server.api_url, token
+    );
+    let sent = state
+        .mailer
+        .send(
+            &user.email,
+            "Your sign-in link",
+            &format!(
+                "Sign in by opening the link below in the browser you requested it from:\n{}\n\nThe link can only be used once. If you did not ask for it, you can ignore this email.",
+                link
+            ),
+        )
+        .await;
+    if sent.is_err() {
+        single_use::unmark(&mut redis_conn, &key).await?;
+    }
+    sent
+}
+
+pub async fn consume_magic_link(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    jar: CookieJar,
+    Query(query): Query<ConsumeQuery>,
+) -> Result<Response, AppError> {
+    let invalid = || AppError::BadRequest("Invalid or expired sign-in link".to_string());
+
+    let mut redis_conn: MultiplexedConnection = state
+        .redis
+        .get_multiplexed_async_connection()
+        .await
+        .map_err(AppError::Redis)?;
+
+    // Checked before consuming, so that a forwarded link or a mail scanner
+    // following it does not burn the token for its owner.
+    let pending = single_use::peek(&mut redis_conn, MAGIC_LINK, &query.token)
+        .await?
+        .ok_or_else(invalid)?;
+    let pending: PendingMagicLink =
+        serde_json::from_str(&pending).map_err(|_| AppError::InternalServerError)?;
+    let browser = cookies::MAGIC_LINK
+        .get(&state.config.cookie, &jar)
+        .map(|nonce| hash_token(&nonce));
+    if browser.as_deref() != Some(pending.browser.as_str()) {
+        return Err(AppError::Forbidden(
+            "Open the link in the browser the sign-in was requested from".to_string(),
+        ));
+    }
+
+    single_use::consume(&mut redis_conn, MAGIC_LINK, &query.token)
+        .await?
+        .ok_or_else(invalid)?;
+
+    // Following the link proves the address belongs to the user.
+    let user = sqlx::query_as!(
+        User,
+        "UPDATE users SET email_verified_at = COALESCE(email_verified_at, NOW()) WHERE id = $1 RETURNING *",
+        pending.user_id
+    )
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or_else(invalid)?;
+    membership::auto_join(&state.pool, user.id).await?;
+
+    let jar = jar.add(cookies::MAGIC_LINK.expired(&state.config.cookie));
+    let destination = format!("{}/", state.config.server.public_url);
+    redirect_sign_in(&state, &mut redis_conn, &user, &client, jar, &destination).await
+}
@@ -0,0 +1,288 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use axum_extra::extract::cookie::CookieJar;
+use redis::aio::MultiplexedConnection;
+use secrecy::Secret;
+
+use crate::{
+    auth::{
+        cookies,
+        extractor::{AuthUser, ScopedAuth},
+        membership,
+        password,
+        personal_token,
+        session,
+        single_use,
+    },
+    errors::AppError,
+    models::user::{UpdateProfile, User, UserResponse},
+    state::AppState,
+};
+
+#[derive(serde::Deserialize)]
+pub struct ChangePasswordPayload {
+    current_password: Secret<String>,
+    new_password: Secret<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct ChangeEmailPayload {
+    new_email: String,
+    password: Secret<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct ConfirmEmailPayload {
+    token: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct PendingEmailChange {
+    user_id: uuid::Uuid,
+    email: String,
+}
+
+const CHANGE_EMAIL: &str = "change_email";
+
+fn validate_profile(payload: &UpdateProfile) -> Result<(), AppError> {
+    if let Some(name) = &payload.name {
+        if name.chars().count() > 100 {
+            return Err(AppError::BadRequest(
+                "Name must be at most 100 characters".to_string(),
+            ));
+        }
+    }
+
+    if let Some(locale) = &payload.locale {
+        let valid = locale.len() <= 35
+            && locale
+                .split('-')
+                .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric()));
+        if !locale.is_empty() && !valid {
+            return Err(AppError::BadRequest(format!("Invalid locale: {}", locale)));
+        }
+    }
+
+    if let Some(timezone) = &payload.timezone {
+        let valid = timezone.len() <= 64
+            && timezone
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "/_+-".contains(c));
+        if !valid {
+            return Err(AppError::BadRequest(format!(
+                "Invalid timezone: {}",
+                timezone
+            )));
+        }
+    }
+
+    if let Some(avatar_url) = &payload.avatar_url {
+        let valid = avatar_url.len() <= 2048
+            && (avatar_url.starts_with("https://") || avatar_url.starts_with("http://"));
+        if !avatar_url.is_empty() && !valid {
+            return Err(AppError::BadRequest("Invalid avatar URL".to_string()));
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn get_me(auth: ScopedAuth) -> Result<Response, AppError> {
+    let auth = auth.require_scope(personal_token::PROFILE_READ)?;
+    Ok((StatusCode::OK, Json(UserResponse::from(auth.user))).into_response())
+}
+
+pub async fn update_me(
+    State(state): State<AppState>,
+    auth: ScopedAuth,
+    Json(payload): Json<UpdateProfile>,
+) -> Result<Response, AppError> {
+    let auth = auth.require_scope(personal_token::PROFILE_WRITE)?;
+    validate_profile(&payload)?;
+
+    // Omitted fields are left untouched, empty strings clear the field.
+    let user = sqlx::query_as!(
+        User,
+        r#"UPDATE users SET
+            name = NULLIF(TRIM(COALESCE($1, name)), ''),
+            locale = NULLIF(COALESCE($2, locale), ''),
+            timezone = NULLIF(COALESCE($3, timezone), ''),
+            avatar_url = NULLIF(COALESCE($4, avatar_url), ''),
+            updated_at = NOW()
+        WHERE id = $5
+        RETURNING *"#,
+        payload.name,
+        payload.locale,
+        payload.timezone,
+        payload.avatar_url,
+        auth.user.id
+    )
+    .fetch_one(&state.pool)
+    .await?;
+
+    Ok((StatusCode::OK, Json(UserResponse::from(user))).into_response())
+}
+
+async fn verify_current_password(user: &User, password: Secret<String>) -> Result<(), AppError> {
+    let is_valid = password::verify_password(password, &user.password_hash)
+        .await
+        .map_err(AppError::Password)?;
+
+    if !is_valid {
+        return Err(AppError::Unauthorized("Invalid password".to_string()));
+    }
+    Ok(())
+}
+
+pub async fn change_password(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    jar: CookieJar,
+    Json(payload): Json<ChangePasswordPayload>,
+) -> Result<Response, AppError> {
+    auth.require_session()?;
+    verify_current_password(&auth.user, payload.current_password).await?;
+    password::validate_strength(&payload.new_password)?;
+
+    let hashed_password = password::hash_password(payload.new_password)
+        .await
+        .map_err(AppError::Password)?;
+
+    sqlx::query!(
+        "UPDATE users SET password_hash = $1, password_reset_required = FALSE, updated_at = NOW() WHERE id = $2",
+        hashed_password,
+        auth.user.id
+    )
+    .execute(&state.pool)
+    .await?;
+
+    let mut redis_conn: MultiplexedConnection = state
+        .redis
+        .get_multiplexed_async_connection()
+        .await
+        .map_err(AppError::Redis)?;
+
+    // Every other device has to sign in again with the new password.
+    let current = match cookies::REFRESH_TOKEN.get(&state.config.cookie, &jar) {
+        Some(refresh_token) => {
+            session::current(&mut redis_conn, &state.config.auth, &refresh_token)
+                .await
+                .ok()
+                .map(|(_, family_id)| family_id)
+        }
+        // Bearer clients keep the session of their access token.
+        None => auth.require_session()?.sid,
+    };
+    session::revoke_all(&mut redis_conn, auth.user.id, current).await?;
+
+    Ok((StatusCode::OK, Json(serde_json::json!({"status": "success"}))).into_response())
+}
+
+pub async fn request_email_change(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Json(payload): Json<ChangeEmailPayload>,
+) -> Result<Response, AppError> {
+    auth.require_session()?;
+    verify_current_password(&auth.user, payload.password).await?;
+
+    let new_email = payload.new_email.trim().to_string();
+    if !new_email.contains('@') || new_email.len() > 255 {
+        return Err(AppError::BadRequest("Invalid email address".to_string()));
+    }
+
+    let taken = sqlx::query_scalar!(
+        "SELECT EXISTS(SELECT 1 FROM users WHERE email = $1)",
+        new_email
+    )
+    .fetch_one(&state.pool)
+    .await?
+    .unwrap_or(false);
+
+    if taken {
+        return Err(AppError::Conflict("Email already in use".to_string()));
+    }
+
+    let mut redis_conn: MultiplexedConnection = state
+        .redis
+        .get_multiplexed_async_connection()
+        .await
+        .map_err(AppError::Redis)?;
+
+    let pending = serde_json::to_string(&PendingEmailChange {
+        user_id: auth.user.id,
+        email: new_email.clone(),
+    })
+    .map_err(|_| AppError::InternalServerError)?;
+    let ttl = duration_str::parse(&state.config.auth.email_verification_expires_in)?.as_secs();
+    let token = single_use::issue(&mut redis_conn, CHANGE_EMAIL, &pending, ttl).await?;
+
+    let link = format!(
+        "{}/confirm-email?token={}",
+        state.config.server.public_url, token
+    );
+    state
+        .mailer
+        .send(
+            &new_email,
+            "Confirm your new email address",
+            &format!(
+                "Confirm that this address should be used for your account by opening the link below:\n{}\n\nIf you did not ask for this change, you can ignore this email.",
+                link
+            ),
+        )
+        .await?;
+    state
+        .mailer
+        .send(
+            &auth.user.email,
+            "Email change requested",
+            &format!(
+                "A change of the email address of your account to {} was requested. It will only take effect once confirmed from the new address.",
+                new_email
+            ),
+        )
+        .await?;
+
+    Ok((StatusCode::ACCEPTED, Json(serde_json::json!({"status": "accepted"}))).into_response())
+}
+
+pub async fn confirm_email_change(
+    State(state): State<AppState>,
+    Json(payload): Json<ConfirmEmailPayload>,
+) -> Result<Response, AppError> {
+    let mut redis_conn: MultiplexedConnection = state
+        .redis
+        .get_multiplexed_async_connection()
+        .await
+        .map_err(AppError::Redis)?;
+
+    let invalid = || AppError::BadRequest("Invalid or expired confirmation token".to_string());
+    // Only consumed once the change went through, so that the link still
+    // works after a failure.
+    let pending = single_use::peek(&mut redis_conn, CHANGE_EMAIL, &payload.token)
+        .await?
+        .ok_or_else(invalid)?;
+    let pending: PendingEmailChange =
+        serde_json::from_str(&pending).map_err(|_| AppError::InternalServerError)?;
+
+    // The address may have been taken since the request, the unique
+    // constraint turns that into a 409.
+    let user = sqlx::query_as!(
+        User,
+        "UPDATE users SET email = $1, email_verified_at = NOW(), updated_at = NOW() WHERE id = $2 RETURNING *",
+        pending.email,
+        pending.user_id
+    )
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+    single_use::consume(&mut redis_conn, CHANGE_EMAIL, &payload.token).await?;
+    membership::auto_join(&state.pool, user.id).await?;
+
+    Ok((StatusCode::OK, Json(UserResponse::from(user))).into_response())
+}
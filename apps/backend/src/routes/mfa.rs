@@ -0,0 +1,257 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use redis::aio::MultiplexedConnection;
+use secrecy::Secret;
+use webauthn_rs::prelude::PublicKeyCredential;
+
+use crate::{
+    auth::{crypto, extractor::AuthUser, mfa, password, session::ClientInfo, webauthn},
+    errors::AppError,
+    models::user::User,
+    routes::auth::start_session,
+    state::AppState,
+    utils::token::hash_token,
+};
+
+#[derive(serde::Deserialize)]
+pub struct TotpCodePayload {
+    code: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct DisableTotpPayload {
+    password: Secret<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct MfaVerifyPayload {
+    mfa_token: String,
+    code: Option<String>,
+    recovery_code: Option<String>,
+    credential: Option<PublicKeyCredential>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct MfaTokenPayload {
+    mfa_token: String,
+}
+
+fn webauthn_mfa_key(mfa_token: &str) -> String {
+    format!("webauthn_mfa:{}", hash_token(mfa_token))
+}
+
+pub async fn setup_totp(
+    State(state): State<AppState>,
+    auth: AuthUser,
+) -> Result<Response, AppError> {
+    auth.require_session()?;
+    if mfa::totp_enabled(&state.pool, auth.user.id).await? {
+        return Err(AppError::Conflict(
+            "Two-factor authentication is already enabled".to_string(),
+        ));
+    }
+
+    let secret = mfa::new_totp_secret();
+    let totp = mfa::totp(&state.config.auth, secret.clone(), &auth.user.email)?;
+    let secret_encrypted = crypto::encrypt(&state.config.auth, &secret)?;
+
+    // Starting over replaces any enrollment that was never confirmed.
+    sqlx::query!(
+        r#"INSERT INTO mfa_totp (user_id, secret_encrypted) VALUES ($1, $2)
+        ON CONFLICT (user_id) DO UPDATE SET secret_encrypted = EXCLUDED.secret_encrypted, created_at = NOW()"#,
+        auth.user.id,
+        secret_encrypted
+    )
+    .execute(&state.pool)
+    .await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(serde_json::json!({
+            "secret": totp.get_secret_base32(),
+            "otpauth_uri": totp.get_url(),
+        })),
+    )
+        .into_response())
+}
+
+pub async fn confirm_totp(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Json(payload): Json<TotpCodePayload>,
+) -> Result<Response, AppError> {
+    auth.require_session()?;
+    let record = mfa::find_totp(&state.pool, auth.user.id)
+        .await?
+        .ok_or_else(|| AppError::BadRequest("Two-factor setup has not been started".to_string()))?;
+
+    if record.confirmed_at.is_some() {
+        return Err(AppError::Conflict(
+            "Two-factor authentication is already enabled".to_string(),
+        ));
+    }
+
+    let mut redis_conn: MultiplexedConnection = state
+        .redis
+        .get_multiplexed_async_connection()
+        .await
+        .map_err(AppError::Redis)?;
+
+    if !mfa::check_code(
+        &mut redis_conn,
+        &state.config.auth,
+        &record,
+        &auth.user.email,
+        &payload.code,
+    )
+    .await?
+    {
+        return Err(AppError::BadRequest("Invalid code".to_string()));
+    }
+
+    sqlx::query!(
+        "UPDATE mfa_totp SET confirmed_at = NOW() WHERE user_id = $1",
+        auth.user.id
+    )
+    .execute(&state.pool)
+    .await?;
+
+    let recovery_codes = mfa::regenerate_recovery_codes(&state.pool, auth.user.id).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(serde_json::json!({ "recovery_codes": recovery_codes })),
+    )
+        .into_response())
+}
+
+pub async fn disable_totp(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Json(payload): Json<DisableTotpPayload>,
+) -> Result<Response, AppError> {
+    auth.require_session()?;
+    let is_valid = password::verify_password(payload.password, &auth.user.password_hash)
+        .await
+        .map_err(AppError::Password)?;
+
+    if !is_valid {
+        return Err(AppError::Unauthorized("Invalid password".to_string()));
+    }
+
+    let mut tx = state.pool.begin().await?;
+    sqlx::query!("DELETE FROM mfa_totp WHERE user_id = $1", auth.user.id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!(
+        "DELETE FROM mfa_recovery_codes WHERE user_id = $1",
+        auth.user.id
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok((StatusCode::OK, Json(serde_json::json!({"status": "success"}))).into_response())
+}
+
+pub async fn verify_mfa(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    Json(payload): Json<MfaVerifyPayload>,
+) -> Result<Response, AppError> {
+    let mut redis_conn: MultiplexedConnection = state
+        .redis
+        .get_multiplexed_async_connection()
+        .await
+        .map_err(AppError::Redis)?;
+
+    let user_id = mfa::challenge_user(&mut redis_conn, &payload.mfa_token).await?;
+
+    let user = sqlx::query_as!(
+        User,
+        "SELECT * FROM users WHERE id = $1",
+        user_id
+    )
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or_else(|| AppError::Unauthorized("Invalid or expired MFA challenge".to_string()))?;
+
+    let verified = match (&payload.code, &payload.recovery_code, &payload.credential) {
+        (Some(code), _, _) => match mfa::find_totp(&state.pool, user.id).await? {
+            Some(record) if record.confirmed_at.is_some() => {
+                mfa::check_code(
+                    &mut redis_conn,
+                    &state.config.auth,
+                    &record,
+                    &user.email,
+                    code,
+                )
+                .await?
+            }
+            _ => false,
+        },
+        (None, Some(recovery_code), _) => {
+            mfa::use_recovery_code(&state.pool, user.id, recovery_code).await?
+        }
+        (None, None, Some(credential)) => {
+            let owner = webauthn::finish_authentication(
+                &state.webauthn,
+                &mut redis_conn,
+                &state.pool,
+                &webauthn_mfa_key(&payload.mfa_token),
+                credential,
+            )
+            .await;
+            matches!(owner, Ok(owner) if owner == user.id)
+        }
+        (None, None, None) => {
+            return Err(AppError::BadRequest(
+                "A code, a recovery code or a passkey is required".to_string(),
+            ))
+        }
+    };
+
+    if !verified {
+        mfa::fail_challenge(&mut redis_conn, &payload.mfa_token).await?;
+        return Err(AppError::Unauthorized("Invalid code".to_string()));
+    }
+
+    mfa::finish_challenge(&mut redis_conn, &payload.mfa_token).await?;
+
+    start_session(
+        &state,
+        &mut redis_conn,
+        user.id,
+        &client,
+        StatusCode::OK,
+        serde_json::json!({"status": "success"}),
+    )
+    .await
+}
+
+pub async fn start_webauthn_mfa(
+    State(state): State<AppState>,
+    Json(payload): Json<MfaTokenPayload>,
+) -> Result<Response, AppError> {
+    let mut redis_conn: MultiplexedConnection = state
+        .redis
+        .get_multiplexed_async_connection()
+        .await
+        .map_err(AppError::Redis)?;
+
+    let user_id = mfa::challenge_user(&mut redis_conn, &payload.mfa_token).await?;
+    let options = webauthn::start_authentication(
+        &state.webauthn,
+        &mut redis_conn,
+        &state.pool,
+        user_id,
+        &webauthn_mfa_key(&payload.mfa_token),
+    )
+    .await?;
+
+    Ok((StatusCode::OK, Json(options)).into_response())
+}
@@ -0,0 +1,217 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
+    Json,
+};
+use axum_extra::extract::cookie::CookieJar;
+use redis::aio::MultiplexedConnection;
+use uuid::Uuid;
+
+use crate::{
+    auth::{
+        cookies,
+        extractor::{AuthUser, ScopedAuth},
+        oauth,
+        personal_token,
+        session::ClientInfo,
+        single_use,
+    },
+    config::OAuthProviderConfig,
+    errors::AppError,
+    models::identity::UserIdentity,
+    routes::auth::redirect_sign_in,
+    state::AppState,
+};
+
+const OAUTH_STATE: &str = "oauth_state";
+const OAUTH_STATE_TTL: u64 = 10 * 60;
+
+#[derive(serde::Deserialize)]
+pub struct StartQuery {
+    redirect_to: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct CallbackQuery {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct PendingAuthorization {
+    provider: String,
+    code_verifier: String,
+    link_to: Option<Uuid>,
+    redirect_to: String,
+}
+
+fn provider_config<'a>(state: &'a AppState, provider: &str) -> Result<&'a OAuthProviderConfig, AppError> {
+    state
+        .config
+        .oauth
+        .get(provider)
+        .ok_or_else(|| AppError::NotFound(format!("Unknown provider: {}", provider)))
+}
+
+fn redirect_uri(state: &AppState, provider: &str) -> String {
+    format!(
+        "{}/auth/oauth/{}/callback",
+        state.config.server.api_url, provider
+    )
+}
+
+pub async fn start(
+    State(state): State<AppState>,
+    Path(provider): Path<String>,
+    auth: Option<AuthUser>,
+    jar: CookieJar,
+    Query(query): Query<StartQuery>,
+) -> Result<Response, AppError> {
+    if let Some(auth) = &auth {
+        auth.require_session()?;
+    }
+    let config = provider_config(&state, &provider)?;
+    let endpoints = oauth::endpoints(&state.http, config).await?;
+
+    // Only same-site paths, anything else would make this an open redirect.
+    let redirect_to = query
+        .redirect_to
+        .filter(|path| path.starts_with('/') && !path.starts_with("//"))
+        .unwrap_or_else(|| "/".to_string());
+
+    let code_verifier = oauth::new_code_verifier();
+    let pending = serde_json::to_string(&PendingAuthorization {
+        provider: provider.clone(),
+        code_verifier: code_verifier.clone(),
+        link_to: auth.map(|auth| auth.user.id),
+        redirect_to,
+    })
+    .map_err(|_| AppError::InternalServerError)?;
+
+    let mut redis_conn: MultiplexedConnection = state
+        .redis
+        .get_multiplexed_async_connection()
+        .await
+        .map_err(AppError::Redis)?;
+    let oauth_state = single_use::issue(&mut redis_conn, OAUTH_STATE, &pending, OAUTH_STATE_TTL).await?;
+
+    let url = oauth::authorization_url(
+        config,
+        &endpoints,
+        &redirect_uri(&state, &provider),
+        &oauth_state,
+        &code_verifier,
+    )?;
+
+    // The state is also kept in a cookie so that the callback only completes
+    // in the browser that started the flow.
+    let cookie = cookies::OAUTH_STATE.build(
+        &state.config.cookie,
+        oauth_state,
+        std::time::Duration::from_secs(OAUTH_STATE_TTL),
+    );
+    Ok((jar.add(cookie), Redirect::to(&url)).into_response())
+}
+
+pub async fn callback(
+    State(state): State<AppState>,
+    Path(provider): Path<String>,
+    client: ClientInfo,
+    jar: CookieJar,
+    Query(query): Query<CallbackQuery>,
+) -> Result<Response, AppError> {
+    if let Some(error) = query.error {
+        return Err(AppError::BadRequest(format!(
+            "The provider refused the sign-in: {}",
+            error
+        )));
+    }
+    let (Some(code), Some(oauth_state)) = (query.code, query.state) else {
+        return Err(AppError::BadRequest("Missing code or state".to_string()));
+    };
+
+    let invalid_state = || AppError::BadRequest("Invalid or expired OAuth state".to_string());
+    let browser_state = cookies::OAUTH_STATE.get(&state.config.cookie, &jar);
+    if browser_state.as_deref() != Some(oauth_state.as_str()) {
+        return Err(invalid_state());
+    }
+
+    let mut redis_conn: MultiplexedConnection = state
+        .redis
+        .get_multiplexed_async_connection()
+        .await
+        .map_err(AppError::Redis)?;
+
+    let pending = single_use::consume(&mut redis_conn, OAUTH_STATE, &oauth_state)
+        .await?
+        .ok_or_else(invalid_state)?;
+    let pending: PendingAuthorization =
+        serde_json::from_str(&pending).map_err(|_| AppError::InternalServerError)?;
+    if pending.provider != provider {
+        return Err(invalid_state());
+    }
+
+    let config = provider_config(&state, &provider)?;
+    let endpoints = oauth::endpoints(&state.http, config).await?;
+    let access_token = oauth::exchange_code(
+        &state.http,
+        config,
+        &endpoints,
+        &code,
+        &redirect_uri(&state, &provider),
+        &pending.code_verifier,
+    )
+    .await?;
+    let identity = oauth::fetch_identity(&state.http, config, &endpoints, &access_token).await?;
+
+    let user = oauth::resolve_user(&state.pool, &provider, &identity, pending.link_to).await?;
+
+    let jar = jar.add(cookies::OAUTH_STATE.expired(&state.config.cookie));
+    let destination = format!("{}{}", state.config.server.public_url, pending.redirect_to);
+
+    // Linking happens from an existing session, there is nothing to start.
+    if pending.link_to.is_some() {
+        return Ok((jar, Redirect::to(&destination)).into_response());
+    }
+
+    redirect_sign_in(&state, &mut redis_conn, &user, &client, jar, &destination).await
+}
+
+pub async fn list_identities(
+    State(state): State<AppState>,
+    auth: ScopedAuth,
+) -> Result<Response, AppError> {
+    let auth = auth.require_scope(personal_token::PROFILE_READ)?;
+    let identities = sqlx::query_as!(
+        UserIdentity,
+        "SELECT * FROM user_identities WHERE user_id = $1 ORDER BY created_at",
+        auth.user.id
+    )
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok((StatusCode::OK, Json(identities)).into_response())
+}
+
+pub async fn unlink_identity(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Response, AppError> {
+    auth.require_session()?;
+    let deleted = sqlx::query!(
+        "DELETE FROM user_identities WHERE id = $1 AND user_id = $2",
+        id,
+        auth.user.id
+    )
+    .execute(&state.pool)
+    .await?;
+
+    if deleted.rows_affected() == 0 {
+        return Err(AppError::NotFound("Identity not found".to_string()));
+    }
+
+    Ok((StatusCode::OK, Json(serde_json::json!({"status": "success"}))).into_response())
+}
@@ -0,0 +1,558 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use redis::aio::MultiplexedConnection;
+use serde::{Deserialize, Deserializer};
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::{
+    auth::{
+        extractor::AuthUser,
+        membership::{self, DomainRecord},
+        permission::{OrgAccess, Permission},
+        session::{self, ClientInfo},
+    },
+    db,
+    errors::AppError,
+    models::organization::{Member, MemberOrganization, Membership, OrgRole, Organization, Role},
+    routes::auth::deliver_access_token,
+    state::AppState,
+    utils::token::generate_opaque_token,
+};
+
+#[derive(serde::Deserialize)]
+pub struct CreateOrgPayload {
+    name: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct UpdateOrgPayload {
+    name: Option<String>,
+    // Empty to turn auto-join off.
+    auto_join_domain: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct UpdateMemberPayload {
+    role: Option<Role>,
+    // Null takes the custom role back.
+    #[serde(default, deserialize_with = "some_or_null")]
+    custom_role_id: Option<Option<Uuid>>,
+}
+
+// Tells an explicit null apart from a missing field.
+fn some_or_null<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::deserialize(deserializer).map(Some)
+}
+
+fn validate_name(name: &str) -> Result<&str, AppError> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > 100 {
+        return Err(AppError::BadRequest(
+            "Organization name must be between 1 and 100 characters".to_string(),
+        ));
+    }
+    Ok(name)
+}
+
+// Only owners hand out or take back ownership, whatever their permissions.
+fn require_owner(access: &OrgAccess) -> Result<(), AppError> {
+    if access.role != Some(Role::Owner) {
+        return Err(AppError::Forbidden("Requires the owner role".to_string()));
+    }
+    Ok(())
+}
+
+/// Fails when `user_id` is the only owner left, the organization would be
+/// unmanageable. Locks the organization until the transaction ends.
+async fn ensure_other_owner(
+    conn: &mut PgConnection,
+    org_id: Uuid,
+    user_id: Uuid,
+) -> Result<(), AppError> {
+    sqlx::query!("SELECT id FROM organizations WHERE id = $1 FOR UPDATE", org_id)
+        .fetch_one(&mut *conn)
+        .await?;
+    let others = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) AS "count!" FROM memberships
+        WHERE organization_id = $1 AND role = $2 AND user_id <> $3"#,
+        org_id,
+        Role::Owner as Role,
+        user_id
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    if others == 0 {
+        return Err(AppError::BadRequest(
+            "An organization needs at least one owner".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+pub async fn create_org(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Json(payload): Json<CreateOrgPayload>,
+) -> Result<Response, AppError> {
+    auth.require_session()?;
+    let name = validate_name(&payload.name)?;
+
+    // Not scoped, the organization does not exist yet.
+    let mut tx = db::begin_cross_tenant(&state.pool).await?;
+    let org = sqlx::query_as!(
+        Organization,
+        "INSERT INTO organizations (name) VALUES ($1) RETURNING *",
+        name
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    // The creator owns the organization.
+    sqlx::query!(
+        "INSERT INTO memberships (organization_id, user_id, role) VALUES ($1, $2, $3)",
+        org.id,
+        auth.user.id,
+        Role::Owner as Role
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok((StatusCode::CREATED, Json(org)).into_response())
+}
+
+pub async fn list_orgs(
+    State(state): State<AppState>,
+    auth: AuthUser,
+) -> Result<Response, AppError> {
+    auth.require_session()?;
+
+    let mut tx = db::begin_cross_tenant(&state.pool).await?;
+    let orgs = sqlx::query_as!(
+        MemberOrganization,
+        r#"SELECT o.id, o.name, o.personal_user_id, m.role AS "role: Role", o.created_at, o.updated_at,
+            o.auto_join_domain, o.auto_join_domain_verified_at
+        FROM organizations o JOIN memberships m ON m.organization_id = o.id
+        WHERE m.user_id = $1
+        ORDER BY o.personal_user_id IS NULL, o.created_at"#,
+        auth.user.id
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok((StatusCode::OK, Json(orgs)).into_response())
+}
+
+pub async fn get_org(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(org_id): Path<Uuid>,
+) -> Result<Response, AppError> {
+    auth.require_session()?;
+
+    let mut tx = db::begin_for_org(&state.pool, org_id).await?;
+    let org = sqlx::query_as!(
+        MemberOrganization,
+        r#"SELECT o.id, o.name, o.personal_user_id, m.role AS "role: Role", o.created_at, o.updated_at,
+            o.auto_join_domain, o.auto_join_domain_verified_at
+        FROM organizations o JOIN memberships m ON m.organization_id = o.id
+        WHERE o.id = $1 AND m.user_id = $2"#,
+        org_id,
+        auth.user.id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Organization not found".to_string()))?;
+
+    tx.commit().await?;
+
+    Ok((StatusCode::OK, Json(org)).into_response())
+}
+
+pub async fn update_org(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(org_id): Path<Uuid>,
+    Json(payload): Json<UpdateOrgPayload>,
+) -> Result<Response, AppError> {
+    auth.require_session()?;
+    let name = payload.name.as_deref().map(validate_name).transpose()?;
+
+    let auto_join_domain = payload
+        .auto_join_domain
+        .map(|domain| domain.trim().to_ascii_lowercase());
+    let mut token = None;
+    if let Some(domain) = auto_join_domain.as_deref().filter(|d| !d.is_empty()) {
+        validate_auto_join_domain(&state, org_id, &auth, domain).await?;
+        token = Some(generate_opaque_token());
+    }
+
+    let mut tx = db::begin_for_org(&state.pool, org_id).await?;
+    // Omitted fields are left untouched, an empty domain clears it. Another
+    // domain has to be verified again.
+    let org = sqlx::query_as!(
+        Organization,
+        r#"UPDATE organizations SET
+            name = COALESCE($2, name),
+            auto_join_domain = NULLIF(COALESCE($3, auto_join_domain), ''),
+            auto_join_domain_token = CASE WHEN $3 IS NULL OR $3 = auto_join_domain
+                THEN auto_join_domain_token ELSE $4 END,
+            auto_join_domain_verified_at = CASE WHEN $3 IS NULL OR $3 = auto_join_domain
+                THEN auto_join_domain_verified_at END,
+            updated_at = NOW()
+        WHERE id = $1
+        RETURNING *"#,
+        org_id,
+        name,
+        auto_join_domain,
+        token
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok((StatusCode::OK, Json(org)).into_response())
+}
+
+/// Whoever turns auto-join on must have proven an address at the domain, and
+/// public email providers are left out, anyone can get an address there. It
+/// takes effect once the domain is verified, see `verify_domain`.
+async fn validate_auto_join_domain(
+    state: &AppState,
+    org_id: Uuid,
+    auth: &AuthUser,
+    domain: &str,
+) -> Result<(), AppError> {
+    let mut tx = db::begin_for_org(&state.pool, org_id).await?;
+    let personal = sqlx::query_scalar!(
+        "SELECT personal_user_id FROM organizations WHERE id = $1",
+        org_id
+    )
+    .fetch_one(&mut *tx)
+    .await?
+    .is_some();
+    tx.commit().await?;
+    if personal {
+        return Err(AppError::BadRequest(
+            "Personal organizations cannot auto-join members".to_string(),
+        ));
+    }
+
+    let valid = domain.len() <= 253
+        && domain.contains('.')
+        && domain
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.');
+    if !valid {
+        return Err(AppError::BadRequest("Invalid domain".to_string()));
+    }
+    if membership::is_public_email_domain(domain) {
+        return Err(AppError::BadRequest(format!(
+            "{} is a public email domain",
+            domain
+        )));
+    }
+
+    let owns_domain = auth.user.email_verified_at.is_some()
+        && membership::email_domain(&auth.user.email).as_deref() == Some(domain);
+    if !owns_domain {
+        return Err(AppError::Forbidden(
+            "A verified email address at this domain is required".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+async fn find_org(state: &AppState, org_id: Uuid) -> Result<Organization, AppError> {
+    let mut tx = db::begin_for_org(&state.pool, org_id).await?;
+    let org = sqlx::query_as!(
+        Organization,
+        "SELECT * FROM organizations WHERE id = $1",
+        org_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(org)
+}
+
+fn domain_record(org: &Organization) -> Result<(&str, DomainRecord), AppError> {
+    match (&org.auto_join_domain, &org.auto_join_domain_token) {
+        (Some(domain), Some(token)) => Ok((domain, membership::verification_record(domain, token))),
+        _ => Err(AppError::NotFound("No auto-join domain".to_string())),
+    }
+}
+
+/// The TXT record to publish for the auto-join domain to be verified.
+pub async fn get_domain(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(org_id): Path<Uuid>,
+) -> Result<Response, AppError> {
+    auth.require_session()?;
+    let org = find_org(&state, org_id).await?;
+    let (domain, record) = domain_record(&org)?;
+
+    Ok((
+        StatusCode::OK,
+        Json(serde_json::json!({
+            "domain": domain,
+            "verified_at": org.auto_join_domain_verified_at,
+            "record": {"type": "TXT", "name": record.name, "value": record.value},
+        })),
+    )
+        .into_response())
+}
+
+/// Checks the TXT record of the auto-join domain, which then takes effect.
+/// Only one organization can hold a verified domain.
+pub async fn verify_domain(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(org_id): Path<Uuid>,
+) -> Result<Response, AppError> {
+    auth.require_session()?;
+    let org = find_org(&state, org_id).await?;
+    if org.auto_join_domain_verified_at.is_some() {
+        return Ok((StatusCode::OK, Json(org)).into_response());
+    }
+    let (domain, record) = domain_record(&org)?;
+
+    let resolver_url = &state.config.server.dns_resolver_url;
+    if !membership::has_record(&state.http, resolver_url, &record).await? {
+        return Err(AppError::BadRequest(format!(
+            "TXT record {} not found",
+            record.name
+        )));
+    }
+
+    let mut tx = db::begin_for_org(&state.pool, org_id).await?;
+    // The domain may have changed during the lookup.
+    let org = sqlx::query_as!(
+        Organization,
+        r#"UPDATE organizations SET auto_join_domain_verified_at = NOW(), updated_at = NOW()
+        WHERE id = $1 AND auto_join_domain = $2 AND auto_join_domain_token = $3
+        RETURNING *"#,
+        org_id,
+        domain,
+        org.auto_join_domain_token
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|err| match err {
+        sqlx::Error::Database(db) if db.is_unique_violation() => AppError::Conflict(
+            "Another organization has already verified this domain".to_string(),
+        ),
+        err => err.into(),
+    })?
+    .ok_or_else(|| AppError::Conflict("The auto-join domain has changed".to_string()))?;
+
+    tx.commit().await?;
+
+    Ok((StatusCode::OK, Json(org)).into_response())
+}
+
+pub async fn delete_org(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(org_id): Path<Uuid>,
+) -> Result<Response, AppError> {
+    auth.require_session()?;
+
+    let mut tx = db::begin_for_org(&state.pool, org_id).await?;
+    // Personal organizations go away with their account only.
+    let deleted = sqlx::query!(
+        "DELETE FROM organizations WHERE id = $1 AND personal_user_id IS NULL",
+        org_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    if deleted.rows_affected() == 0 {
+        return Err(AppError::BadRequest(
+            "Personal organizations cannot be deleted".to_string(),
+        ));
+    }
+
+    tx.commit().await?;
+
+    Ok((StatusCode::OK, Json(serde_json::json!({"status": "success"}))).into_response())
+}
+
+/// Makes `org_id` the active organization of the current session, carried
+/// by its access tokens from now on.
+pub async fn switch_org(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    client: ClientInfo,
+    Path(org_id): Path<Uuid>,
+) -> Result<Response, AppError> {
+    let family_id = auth
+        .require_session()?
+        .sid
+        .ok_or_else(|| AppError::Unauthorized("Invalid access token".to_string()))?;
+    let mut tx = db::begin_for_org(&state.pool, org_id).await?;
+    let membership = membership::find(&mut *tx, org_id, auth.user.id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Organization not found".to_string()))?;
+    tx.commit().await?;
+
+    let mut redis_conn: MultiplexedConnection = state
+        .redis
+        .get_multiplexed_async_connection()
+        .await
+        .map_err(AppError::Redis)?;
+    session::set_active_org(&mut redis_conn, auth.user.id, family_id, org_id).await?;
+
+    let access_token = session::issue_access_token(
+        &mut redis_conn,
+        &state.jwt,
+        &state.config.auth,
+        auth.user.id,
+        family_id,
+        Some(&membership),
+    )
+    .await?;
+
+    deliver_access_token(
+        &state,
+        &client,
+        access_token,
+        StatusCode::OK,
+        serde_json::json!({"org": org_id, "role": membership.role}),
+    )
+}
+
+pub async fn list_members(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(org_id): Path<Uuid>,
+) -> Result<Response, AppError> {
+    auth.require_session()?;
+
+    let mut tx = db::begin_for_org(&state.pool, org_id).await?;
+    let members = sqlx::query_as!(
+        Member,
+        r#"SELECT u.id AS user_id, u.email, u.name, m.role AS "role: Role", m.custom_role_id,
+            m.created_at
+        FROM memberships m JOIN users u ON u.id = m.user_id
+        WHERE m.organization_id = $1
+        ORDER BY m.created_at"#,
+        org_id
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok((StatusCode::OK, Json(members)).into_response())
+}
+
+/// Changes the built-in role of a member, and assigns or takes back their
+/// custom role. Nobody hands out permissions they do not hold.
+pub async fn update_member(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    access: OrgAccess,
+    Path((org_id, user_id)): Path<(Uuid, Uuid)>,
+    Json(payload): Json<UpdateMemberPayload>,
+) -> Result<Response, AppError> {
+    auth.require_session()?;
+
+    let mut tx = db::begin_for_org(&state.pool, org_id).await?;
+    let target = membership::find(&mut *tx, org_id, user_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Member not found".to_string()))?;
+    let role = payload.role.unwrap_or(target.role);
+    if role != target.role {
+        if target.role == Role::Owner || role == Role::Owner {
+            require_owner(&access)?;
+        }
+        access.require_all(role.permissions().iter().map(Permission::as_str))?;
+    }
+
+    let custom_role_id = match payload.custom_role_id {
+        Some(Some(role_id)) => {
+            let custom_role = sqlx::query_as!(
+                OrgRole,
+                "SELECT * FROM org_roles WHERE id = $1 AND organization_id = $2",
+                role_id,
+                org_id
+            )
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Role not found".to_string()))?;
+            access.require_all(&custom_role.permissions)?;
+            Some(custom_role.id)
+        }
+        Some(None) => None,
+        None => target.custom_role_id,
+    };
+
+    if target.role == Role::Owner && role != Role::Owner {
+        ensure_other_owner(&mut tx, org_id, user_id).await?;
+    }
+    let updated = sqlx::query_as!(
+        Membership,
+        r#"UPDATE memberships SET role = $3, custom_role_id = $4
+        WHERE organization_id = $1 AND user_id = $2
+        RETURNING organization_id, user_id, role AS "role: Role", custom_role_id, created_at"#,
+        org_id,
+        user_id,
+        role as Role,
+        custom_role_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok((StatusCode::OK, Json(updated)).into_response())
+}
+
+/// Removes a member, or lets a member leave.
+pub async fn remove_member(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    access: OrgAccess,
+    Path((org_id, user_id)): Path<(Uuid, Uuid)>,
+) -> Result<Response, AppError> {
+    auth.require_session()?;
+
+    let mut tx = db::begin_for_org(&state.pool, org_id).await?;
+    let target = membership::find(&mut *tx, org_id, user_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Member not found".to_string()))?;
+    if user_id != auth.user.id {
+        access.require(Permission::MembersManage)?;
+        if target.role == Role::Owner {
+            require_owner(&access)?;
+        }
+    }
+
+    if target.role == Role::Owner {
+        ensure_other_owner(&mut tx, org_id, user_id).await?;
+    }
+    sqlx::query!(
+        "DELETE FROM memberships WHERE organization_id = $1 AND user_id = $2",
+        org_id,
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok((StatusCode::OK, Json(serde_json::json!({"status": "success"}))).into_response())
+}
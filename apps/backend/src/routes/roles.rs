@@ -0,0 +1,173 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use uuid::Uuid;
+
+use crate::{
+    auth::{extractor::AuthUser, permission::OrgAccess},
+    db,
+    errors::AppError,
+    models::organization::OrgRole,
+    state::AppState,
+};
+
+#[derive(serde::Deserialize)]
+pub struct CreateRolePayload {
+    name: String,
+    permissions: Vec<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct UpdateRolePayload {
+    name: Option<String>,
+    permissions: Option<Vec<String>>,
+}
+
+fn validate_name(name: &str) -> Result<&str, AppError> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > 50 {
+        return Err(AppError::BadRequest(
+            "Role name must be between 1 and 50 characters".to_string(),
+        ));
+    }
+    Ok(name)
+}
+
+// A role cannot grant more than the member defining it holds.
+fn validate_permissions(access: &OrgAccess, mut permissions: Vec<String>) -> Result<Vec<String>, AppError> {
+    permissions.sort();
+    permissions.dedup();
+    if permissions.is_empty() {
+        return Err(AppError::BadRequest(
+            "At least one permission is required".to_string(),
+        ));
+    }
+    access.require_all(&permissions)?;
+    Ok(permissions)
+}
+
+fn map_conflict(err: sqlx::Error) -> AppError {
+    match err {
+        sqlx::Error::Database(db) if db.is_unique_violation() => {
+            AppError::Conflict("A role with this name already exists".to_string())
+        }
+        err => err.into(),
+    }
+}
+
+pub async fn list_roles(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(org_id): Path<Uuid>,
+) -> Result<Response, AppError> {
+    auth.require_session()?;
+
+    let mut tx = db::begin_for_org(&state.pool, org_id).await?;
+    let roles = sqlx::query_as!(
+        OrgRole,
+        "SELECT * FROM org_roles WHERE organization_id = $1 ORDER BY name",
+        org_id
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok((StatusCode::OK, Json(roles)).into_response())
+}
+
+pub async fn create_role(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    access: OrgAccess,
+    Path(org_id): Path<Uuid>,
+    Json(payload): Json<CreateRolePayload>,
+) -> Result<Response, AppError> {
+    auth.require_session()?;
+    let name = validate_name(&payload.name)?;
+    let permissions = validate_permissions(&access, payload.permissions)?;
+
+    let mut tx = db::begin_for_org(&state.pool, org_id).await?;
+    let role = sqlx::query_as!(
+        OrgRole,
+        "INSERT INTO org_roles (organization_id, name, permissions) VALUES ($1, $2, $3) RETURNING *",
+        org_id,
+        name,
+        &permissions
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(map_conflict)?;
+
+    tx.commit().await?;
+
+    Ok((StatusCode::CREATED, Json(role)).into_response())
+}
+
+/// Changes apply right away to the members holding the role.
+pub async fn update_role(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    access: OrgAccess,
+    Path((org_id, id)): Path<(Uuid, Uuid)>,
+    Json(payload): Json<UpdateRolePayload>,
+) -> Result<Response, AppError> {
+    auth.require_session()?;
+    let name = payload.name.as_deref().map(validate_name).transpose()?;
+    let permissions = payload
+        .permissions
+        .map(|permissions| validate_permissions(&access, permissions))
+        .transpose()?;
+
+    let mut tx = db::begin_for_org(&state.pool, org_id).await?;
+    let role = sqlx::query_as!(
+        OrgRole,
+        r#"UPDATE org_roles SET
+            name = COALESCE($3, name),
+            permissions = COALESCE($4, permissions),
+            updated_at = NOW()
+        WHERE id = $1 AND organization_id = $2
+        RETURNING *"#,
+        id,
+        org_id,
+        name,
+        permissions.as_deref()
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(map_conflict)?
+    .ok_or_else(|| AppError::NotFound("Role not found".to_string()))?;
+
+    tx.commit().await?;
+
+    Ok((StatusCode::OK, Json(role)).into_response())
+}
+
+/// Members holding the role are left with their built-in role.
+pub async fn delete_role(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path((org_id, id)): Path<(Uuid, Uuid)>,
+) -> Result<Response, AppError> {
+    auth.require_session()?;
+
+    let mut tx = db::begin_for_org(&state.pool, org_id).await?;
+    let deleted = sqlx::query!(
+        "DELETE FROM org_roles WHERE id = $1 AND organization_id = $2",
+        id,
+        org_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    if deleted.rows_affected() == 0 {
+        return Err(AppError::NotFound("Role not found".to_string()));
+    }
+
+    tx.commit().await?;
+
+    Ok((StatusCode::OK, Json(serde_json::json!({"status": "success"}))).into_response())
+}
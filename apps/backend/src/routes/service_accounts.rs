@@ -0,0 +1,311 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::{
+    auth::{
+        api_key,
+        permission::{self, OrgAccess},
+    },
+    db,
+    errors::AppError,
+    models::service_account::{ApiKey, ServiceAccount},
+    state::AppState,
+    utils::token::PrefixedToken,
+};
+
+// How long a rotated key keeps working when the caller does not say.
+const DEFAULT_ROTATION_OVERLAP: &str = "24h";
+const MAX_ROTATION_OVERLAP_DAYS: i64 = 30;
+
+#[derive(serde::Deserialize)]
+pub struct CreateServiceAccountPayload {
+    name: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct CreateApiKeyPayload {
+    name: String,
+    scopes: Vec<String>,
+    #[serde(default)]
+    allowed_ips: Vec<String>,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct RotateApiKeyPayload {
+    // How long the old key stays valid, e.g. "1h".
+    overlap: Option<String>,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+fn validate_name(name: &str) -> Result<&str, AppError> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > 100 {
+        return Err(AppError::BadRequest(
+            "Name must be between 1 and 100 characters".to_string(),
+        ));
+    }
+    Ok(name)
+}
+
+fn validate_expiration(expires_at: Option<DateTime<Utc>>) -> Result<(), AppError> {
+    if expires_at.is_some_and(|at| at <= Utc::now()) {
+        return Err(AppError::BadRequest(
+            "Expiration must be in the future".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+async fn service_account_in(
+    conn: &mut PgConnection,
+    org_id: Uuid,
+    id: Uuid,
+) -> Result<ServiceAccount, AppError> {
+    sqlx::query_as!(
+        ServiceAccount,
+        "SELECT * FROM service_accounts WHERE id = $1 AND organization_id = $2",
+        id,
+        org_id
+    )
+    .fetch_optional(conn)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Service account not found".to_string()))
+}
+
+// The key is only ever shown in this response.
+fn created_key(record: ApiKey, key: PrefixedToken) -> Result<Response, AppError> {
+    let mut body = serde_json::to_value(record).map_err(|_| AppError::InternalServerError)?;
+    body["key"] = key.token.into();
+    Ok((StatusCode::CREATED, Json(body)).into_response())
+}
+
+pub async fn create_service_account(
+    State(state): State<AppState>,
+    Path(org_id): Path<Uuid>,
+    Json(payload): Json<CreateServiceAccountPayload>,
+) -> Result<Response, AppError> {
+    let name = validate_name(&payload.name)?;
+
+    let mut tx = db::begin_for_org(&state.pool, org_id).await?;
+    let service_account = sqlx::query_as!(
+        ServiceAccount,
+        "INSERT INTO service_accounts (organization_id, name) VALUES ($1, $2) RETURNING *",
+        org_id,
+        name
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok((StatusCode::CREATED, Json(service_account)).into_response())
+}
+
+pub async fn list_service_accounts(
+    State(state): State<AppState>,
+    Path(org_id): Path<Uuid>,
+) -> Result<Response, AppError> {
+    let mut tx = db::begin_for_org(&state.pool, org_id).await?;
+    let service_accounts = sqlx::query_as!(
+        ServiceAccount,
+        "SELECT * FROM service_accounts WHERE organization_id = $1 ORDER BY created_at",
+        org_id
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok((StatusCode::OK, Json(service_accounts)).into_response())
+}
+
+pub async fn delete_service_account(
+    State(state): State<AppState>,
+    Path((org_id, id)): Path<(Uuid, Uuid)>,
+) -> Result<Response, AppError> {
+    let mut tx = db::begin_for_org(&state.pool, org_id).await?;
+    let deleted = sqlx::query!(
+        "DELETE FROM service_accounts WHERE id = $1 AND organization_id = $2",
+        id,
+        org_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    if deleted.rows_affected() == 0 {
+        return Err(AppError::NotFound("Service account not found".to_string()));
+    }
+
+    tx.commit().await?;
+
+    Ok((StatusCode::OK, Json(serde_json::json!({"status": "success"}))).into_response())
+}
+
+pub async fn create_api_key(
+    State(state): State<AppState>,
+    access: OrgAccess,
+    Path((org_id, id)): Path<(Uuid, Uuid)>,
+    Json(payload): Json<CreateApiKeyPayload>,
+) -> Result<Response, AppError> {
+    let mut tx = db::begin_for_org(&state.pool, org_id).await?;
+    let service_account = service_account_in(&mut tx, org_id, id).await?;
+    let name = validate_name(&payload.name)?;
+
+    let mut scopes = payload.scopes;
+    scopes.sort();
+    scopes.dedup();
+    if scopes.is_empty() {
+        return Err(AppError::BadRequest("At least one scope is required".to_string()));
+    }
+    if let Some(unknown) = permission::unknown(&scopes) {
+        return Err(AppError::BadRequest(format!("Unknown scope: {}", unknown)));
+    }
+    // A key cannot hand out more than the principal creating it holds.
+    access.require_all(&scopes)?;
+
+    if let Some(invalid) = payload
+        .allowed_ips
+        .iter()
+        .find(|network| !api_key::is_valid_network(network))
+    {
+        return Err(AppError::BadRequest(format!("Invalid network: {}", invalid)));
+    }
+    validate_expiration(payload.expires_at)?;
+
+    let key = api_key::generate();
+    let record = sqlx::query_as!(
+        ApiKey,
+        r#"INSERT INTO api_keys (service_account_id, name, key_hash, key_prefix, scopes, allowed_ips, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING *"#,
+        service_account.id,
+        name,
+        key.hash,
+        key.prefix,
+        &scopes,
+        &payload.allowed_ips,
+        payload.expires_at
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    created_key(record, key)
+}
+
+pub async fn list_api_keys(
+    State(state): State<AppState>,
+    Path((org_id, id)): Path<(Uuid, Uuid)>,
+) -> Result<Response, AppError> {
+    let mut tx = db::begin_for_org(&state.pool, org_id).await?;
+    let service_account = service_account_in(&mut tx, org_id, id).await?;
+
+    let keys = sqlx::query_as!(
+        ApiKey,
+        "SELECT * FROM api_keys WHERE service_account_id = $1 ORDER BY created_at",
+        service_account.id
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok((StatusCode::OK, Json(keys)).into_response())
+}
+
+/// Replaces a key with a new one holding the same scopes and networks. The
+/// old key keeps working for the overlap, so that it can be swapped without
+/// downtime.
+pub async fn rotate_api_key(
+    State(state): State<AppState>,
+    access: OrgAccess,
+    Path((org_id, id, key_id)): Path<(Uuid, Uuid, Uuid)>,
+    Json(payload): Json<RotateApiKeyPayload>,
+) -> Result<Response, AppError> {
+    let mut tx = db::begin_for_org(&state.pool, org_id).await?;
+    let service_account = service_account_in(&mut tx, org_id, id).await?;
+
+    let overlap = duration_str::parse(
+        payload.overlap.as_deref().unwrap_or(DEFAULT_ROTATION_OVERLAP),
+    )?;
+    let overlap = Duration::from_std(overlap)
+        .ok()
+        .filter(|overlap| *overlap <= Duration::days(MAX_ROTATION_OVERLAP_DAYS))
+        .ok_or_else(|| {
+            AppError::BadRequest(format!(
+                "Overlap must be at most {} days",
+                MAX_ROTATION_OVERLAP_DAYS
+            ))
+        })?;
+    validate_expiration(payload.expires_at)?;
+
+    // LEAST ignores NULL, keys without expiration get one.
+    let old = sqlx::query_as!(
+        ApiKey,
+        r#"UPDATE api_keys SET expires_at = LEAST(expires_at, $3)
+        WHERE id = $1 AND service_account_id = $2 AND (expires_at IS NULL OR expires_at > NOW())
+        RETURNING *"#,
+        key_id,
+        service_account.id,
+        Utc::now() + overlap
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| AppError::NotFound("API key not found".to_string()))?;
+    // Nor can rotating hand out what the principal does not hold.
+    access.require_all(&old.scopes)?;
+
+    let key = api_key::generate();
+    let record = sqlx::query_as!(
+        ApiKey,
+        r#"INSERT INTO api_keys (service_account_id, name, key_hash, key_prefix, scopes, allowed_ips, rotated_from, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        RETURNING *"#,
+        service_account.id,
+        old.name,
+        key.hash,
+        key.prefix,
+        &old.scopes,
+        &old.allowed_ips,
+        old.id,
+        payload.expires_at
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    created_key(record, key)
+}
+
+pub async fn delete_api_key(
+    State(state): State<AppState>,
+    Path((org_id, id, key_id)): Path<(Uuid, Uuid, Uuid)>,
+) -> Result<Response, AppError> {
+    let mut tx = db::begin_for_org(&state.pool, org_id).await?;
+    let service_account = service_account_in(&mut tx, org_id, id).await?;
+
+    let deleted = sqlx::query!(
+        "DELETE FROM api_keys WHERE id = $1 AND service_account_id = $2",
+        key_id,
+        service_account.id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    if deleted.rows_affected() == 0 {
+        return Err(AppError::NotFound("API key not found".to_string()));
+    }
+
+    tx.commit().await?;
+
+    Ok((StatusCode::OK, Json(serde_json::json!({"status": "success"}))).into_response())
+}
@@ -0,0 +1,117 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::{
+    auth::{extractor::AuthUser, personal_token},
+    errors::AppError,
+    models::personal_token::PersonalAccessToken,
+    state::AppState,
+};
+
+#[derive(serde::Deserialize)]
+pub struct CreateTokenPayload {
+    name: String,
+    scopes: Vec<String>,
+    // Never expires when absent.
+    expires_at: Option<DateTime<Utc>>,
+}
+
+pub async fn create_token(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Json(payload): Json<CreateTokenPayload>,
+) -> Result<Response, AppError> {
+    auth.require_session()?;
+
+    let name = payload.name.trim();
+    if name.is_empty() || name.len() > 100 {
+        return Err(AppError::BadRequest(
+            "Token name must be between 1 and 100 characters".to_string(),
+        ));
+    }
+
+    let mut scopes = payload.scopes;
+    scopes.sort();
+    scopes.dedup();
+    if scopes.is_empty() {
+        return Err(AppError::BadRequest("At least one scope is required".to_string()));
+    }
+    if let Some(unknown) = scopes
+        .iter()
+        .find(|s| !personal_token::SCOPES.contains(&s.as_str()))
+    {
+        return Err(AppError::BadRequest(format!("Unknown scope: {}", unknown)));
+    }
+
+    if payload.expires_at.is_some_and(|at| at <= Utc::now()) {
+        return Err(AppError::BadRequest(
+            "Expiration must be in the future".to_string(),
+        ));
+    }
+
+    let new_token = personal_token::generate();
+    let record = sqlx::query_as!(
+        PersonalAccessToken,
+        r#"INSERT INTO personal_access_tokens (user_id, name, token_hash, token_prefix, scopes, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING *"#,
+        auth.user.id,
+        name,
+        new_token.hash,
+        new_token.prefix,
+        &scopes,
+        payload.expires_at
+    )
+    .fetch_one(&state.pool)
+    .await?;
+
+    // The only time the token is shown.
+    let mut body = serde_json::to_value(record).map_err(|_| AppError::InternalServerError)?;
+    body["token"] = new_token.token.into();
+    Ok((StatusCode::CREATED, Json(body)).into_response())
+}
+
+pub async fn list_tokens(
+    State(state): State<AppState>,
+    auth: AuthUser,
+) -> Result<Response, AppError> {
+    auth.require_session()?;
+
+    let tokens = sqlx::query_as!(
+        PersonalAccessToken,
+        "SELECT * FROM personal_access_tokens WHERE user_id = $1 ORDER BY created_at",
+        auth.user.id
+    )
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok((StatusCode::OK, Json(tokens)).into_response())
+}
+
+pub async fn delete_token(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Response, AppError> {
+    auth.require_session()?;
+
+    let deleted = sqlx::query!(
+        "DELETE FROM personal_access_tokens WHERE id = $1 AND user_id = $2",
+        id,
+        auth.user.id
+    )
+    .execute(&state.pool)
+    .await?;
+
+    if deleted.rows_affected() == 0 {
+        return Err(AppError::NotFound("Token not found".to_string()));
+    }
+
+    Ok((StatusCode::OK, Json(serde_json::json!({"status": "success"}))).into_response())
+}
@@ -0,0 +1,250 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use redis::aio::MultiplexedConnection;
+use sqlx::types::Json as SqlJson;
+use uuid::Uuid;
+use webauthn_rs::prelude::{
+    CredentialID, PasskeyRegistration, PublicKeyCredential, RegisterPublicKeyCredential,
+};
+
+use crate::{
+    auth::{
+        extractor::{AuthUser, ScopedAuth},
+        personal_token,
+        session::ClientInfo,
+        webauthn,
+    },
+    config::EmailVerificationPolicy,
+    errors::AppError,
+    models::user::User,
+    routes::auth::start_session,
+    state::AppState,
+};
+
+#[derive(serde::Deserialize)]
+pub struct FinishRegistrationPayload {
+    name: Option<String>,
+    credential: RegisterPublicKeyCredential,
+}
+
+#[derive(serde::Deserialize)]
+pub struct StartLoginPayload {
+    email: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct FinishLoginPayload {
+    challenge_id: Uuid,
+    credential: PublicKeyCredential,
+}
+
+fn registration_key(user_id: Uuid) -> String {
+    format!("webauthn_registration:{}", user_id)
+}
+
+fn login_key(challenge_id: Uuid) -> String {
+    format!("webauthn_login:{}", challenge_id)
+}
+
+pub async fn start_registration(
+    State(state): State<AppState>,
+    auth: AuthUser,
+) -> Result<Response, AppError> {
+    auth.require_session()?;
+    let exclude: Vec<CredentialID> = webauthn::credentials_for(&state.pool, auth.user.id)
+        .await?
+        .into_iter()
+        .map(|c| c.passkey.0.cred_id().clone())
+        .collect();
+
+    let display_name = auth.user.name.as_deref().unwrap_or(&auth.user.email);
+    let (options, registration) = state
+        .webauthn
+        .start_passkey_registration(auth.user.id, &auth.user.email, display_name, Some(exclude))
+        .map_err(|e| {
+            tracing::error!("WebAuthn error: {:?}", e);
+            AppError::InternalServerError
+        })?;
+
+    let mut redis_conn: MultiplexedConnection = state
+        .redis
+        .get_multiplexed_async_connection()
+        .await
+        .map_err(AppError::Redis)?;
+    webauthn::store_state(&mut redis_conn, &registration_key(auth.user.id), &registration).await?;
+
+    Ok((StatusCode::OK, Json(options)).into_response())
+}
+
+pub async fn finish_registration(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Json(payload): Json<FinishRegistrationPayload>,
+) -> Result<Response, AppError> {
+    auth.require_session()?;
+    let name = payload
+        .name
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty());
+    if name.as_ref().is_some_and(|name| name.chars().count() > 100) {
+        return Err(AppError::BadRequest(
+            "Name must be at most 100 characters".to_string(),
+        ));
+    }
+
+    let mut redis_conn: MultiplexedConnection = state
+        .redis
+        .get_multiplexed_async_connection()
+        .await
+        .map_err(AppError::Redis)?;
+
+    let registration: PasskeyRegistration =
+        webauthn::take_state(&mut redis_conn, &registration_key(auth.user.id))
+            .await?
+            .ok_or_else(|| {
+                AppError::BadRequest("Passkey registration has not been started".to_string())
+            })?;
+
+    let passkey = state
+        .webauthn
+        .finish_passkey_registration(&payload.credential, &registration)
+        .map_err(|e| {
+            tracing::debug!("Passkey registration rejected: {:?}", e);
+            AppError::BadRequest("Passkey registration failed".to_string())
+        })?;
+
+    let credential = sqlx::query!(
+        r#"INSERT INTO webauthn_credentials (user_id, credential_id, passkey, name)
+        VALUES ($1, $2, $3, $4)
+        RETURNING id, created_at"#,
+        auth.user.id,
+        passkey.cred_id().as_ref(),
+        SqlJson(&passkey) as _,
+        name
+    )
+    .fetch_one(&state.pool)
+    .await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(serde_json::json!({
+            "id": credential.id,
+            "name": name,
+            "created_at": credential.created_at,
+        })),
+    )
+        .into_response())
+}
+
+pub async fn list_credentials(
+    State(state): State<AppState>,
+    auth: ScopedAuth,
+) -> Result<Response, AppError> {
+    let auth = auth.require_scope(personal_token::PROFILE_READ)?;
+    let credentials = webauthn::credentials_for(&state.pool, auth.user.id).await?;
+    Ok((StatusCode::OK, Json(credentials)).into_response())
+}
+
+pub async fn delete_credential(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Response, AppError> {
+    auth.require_session()?;
+    let deleted = sqlx::query!(
+        "DELETE FROM webauthn_credentials WHERE id = $1 AND user_id = $2",
+        id,
+        auth.user.id
+    )
+    .execute(&state.pool)
+    .await?;
+
+    if deleted.rows_affected() == 0 {
+        return Err(AppError::NotFound("Passkey not found".to_string()));
+    }
+
+    Ok((StatusCode::OK, Json(serde_json::json!({"status": "success"}))).into_response())
+}
+
+pub async fn start_login(
+    State(state): State<AppState>,
+    Json(payload): Json<StartLoginPayload>,
+) -> Result<Response, AppError> {
+    let user_id = sqlx::query_scalar!("SELECT id FROM users WHERE email = $1", payload.email)
+        .fetch_optional(&state.pool)
+        .await?
+        .ok_or_else(|| AppError::BadRequest("No passkey registered".to_string()))?;
+
+    let mut redis_conn: MultiplexedConnection = state
+        .redis
+        .get_multiplexed_async_connection()
+        .await
+        .map_err(AppError::Redis)?;
+
+    let challenge_id = Uuid::new_v4();
+    let options = webauthn::start_authentication(
+        &state.webauthn,
+        &mut redis_conn,
+        &state.pool,
+        user_id,
+        &login_key(challenge_id),
+    )
+    .await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(serde_json::json!({
+            "challenge_id": challenge_id,
+            "options": options,
+        })),
+    )
+        .into_response())
+}
+
+/// A passkey proves possession and user verification at once, so it opens a
+/// session without asking for another factor.
+pub async fn finish_login(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    Json(payload): Json<FinishLoginPayload>,
+) -> Result<Response, AppError> {
+    let mut redis_conn: MultiplexedConnection = state
+        .redis
+        .get_multiplexed_async_connection()
+        .await
+        .map_err(AppError::Redis)?;
+
+    let user_id = webauthn::finish_authentication(
+        &state.webauthn,
+        &mut redis_conn,
+        &state.pool,
+        &login_key(payload.challenge_id),
+        &payload.credential,
+    )
+    .await?;
+
+    let user = sqlx::query_as!(User, "SELECT * FROM users WHERE id = $1", user_id)
+        .fetch_optional(&state.pool)
+        .await?
+        .ok_or_else(|| AppError::Unauthorized("Passkey verification failed".to_string()))?;
+
+    if state.config.auth.email_verification == EmailVerificationPolicy::Required
+        && user.email_verified_at.is_none()
+    {
+        return Err(AppError::Forbidden("Email not verified".to_string()));
+    }
+
+    start_session(
+        &state,
+        &mut redis_conn,
+        user.id,
+        &client,
+        StatusCode::OK,
+        serde_json::json!({"status": "success"}),
+    )
+    .await
+}
@@ -1,14 +1,20 @@
 
-use crate::config::AppConfig;
+use crate::{auth::jwt::KeyRing, config::AppConfig, mail::Mailer};
 use axum::extract::FromRef;
 use redis::Client;
 use sqlx::PgPool;
+use webauthn_rs::Webauthn;
 
 #[derive(Clone)]
 pub struct AppState {
     pub pool: PgPool,
     pub config: AppConfig,
     pub redis: Client,
+    pub mailer: Mailer,
+    // Keys signing and verifying access tokens.
+    pub jwt: KeyRing,
+    pub webauthn: Webauthn,
+    pub http: reqwest::Client,
 }
 
 impl FromRef<AppState> for PgPool {
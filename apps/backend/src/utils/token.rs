@@ -1,15 +1,35 @@
 // saas-project/apps/backend/src/utils/token.rs
 
-use crate::auth::jwt;
-use crate::errors::AppError;
-
-pub fn create_jwt_token(
-    user_id: uuid::Uuid,
-    secret: &str,
-    expires_in: &str,
-) -> Result<String, AppError> {
-    jwt::create_token(user_id, secret, expires_in).map_err(|e| {
-        // adapte cette ligne selon la nature de l'erreur retournée par create_token
-        AppError::Unauthorized(format!("Token creation failed: {}", e))
-    })
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+/// Random URL-safe token for links sent by email and other opaque credentials.
+pub fn generate_opaque_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Opaque tokens are only ever stored hashed.
+pub fn hash_token(token: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(token.as_bytes()))
+}
+
+/// A new long-lived credential starting with `prefix`, with the hash and the
+/// visible start to store.
+pub struct PrefixedToken {
+    pub token: String,
+    pub hash: String,
+    pub prefix: String,
+}
+
+pub fn generate_prefixed_token(prefix: &str) -> PrefixedToken {
+    let token = format!("{}{}", prefix, generate_opaque_token());
+    PrefixedToken {
+        hash: hash_token(&token),
+        // Enough to recognize the token in listings.
+        prefix: token[..prefix.len() + 8].to_string(),
+        token,
+    }
 }
@@ -0,0 +1,208 @@
+mod common;
+
+use reqwest::{Client, StatusCode};
+use serde_json::json;
+
+use common::{latest_email, token_from};
+
+const API: &str = "http://localhost:8000";
+
+fn new_email() -> String {
+    format!("{}@example.com", uuid::Uuid::new_v4())
+}
+
+/// Inscription en mode bearer, renvoie l'id du compte et son access token.
+async fn sign_up(client: &Client, email: &str) -> (String, String) {
+    let res = client
+        .post(format!("{}/auth/register", API))
+        .header("X-Token-Delivery", "body")
+        .json(&json!({ "email": email, "password": "password123" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let body: serde_json::Value = res.json().await.unwrap();
+    (
+        body["user"]["id"].as_str().unwrap().to_string(),
+        body["access_token"].as_str().unwrap().to_string(),
+    )
+}
+
+async fn login(client: &Client, email: &str, password: &str) -> reqwest::Response {
+    client
+        .post(format!("{}/auth/login", API))
+        .header("X-Token-Delivery", "body")
+        .json(&json!({ "email": email, "password": password }))
+        .send()
+        .await
+        .unwrap()
+}
+
+/// Compte administrateur de la plateforme, promu en ligne de commande avec
+/// la configuration du serveur de test.
+async fn sign_up_admin(client: &Client) -> String {
+    let email = new_email();
+    let (_, token) = sign_up(client, &email).await;
+    let status = std::process::Command::new(env!("CARGO_BIN_EXE_backend"))
+        .args(["grant-admin", &email])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    token
+}
+
+async fn admin_post(client: &Client, token: &str, path: &str) -> reqwest::Response {
+    client
+        .post(format!("{}/admin/users/{}", API, path))
+        .bearer_auth(token)
+        .json(&json!({}))
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn audit_actions(client: &Client, token: &str, user_id: &str) -> Vec<serde_json::Value> {
+    let res = client
+        .get(format!("{}/admin/audit-log?user_id={}", API, user_id))
+        .bearer_auth(token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: serde_json::Value = res.json().await.unwrap();
+    body["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["action"].clone())
+        .collect()
+}
+
+#[tokio::test]
+async fn staff_search_and_suspend_accounts() {
+    let client = Client::new();
+    let admin = sign_up_admin(&client).await;
+    let email = new_email();
+    let (user_id, user_token) = sign_up(&client, &email).await;
+
+    // ⛔ Réservé aux administrateurs de la plateforme
+    let res = client
+        .get(format!("{}/admin/users", API))
+        .bearer_auth(&user_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+    // 🔎 RECHERCHE
+    let res = client
+        .get(format!("{}/admin/users?q={}&status=active&per_page=5", API, email.to_uppercase()))
+        .bearer_auth(&admin)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let found: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(found["total"], 1);
+    assert_eq!(found["per_page"], 5);
+    assert_eq!(found["users"][0]["id"], user_id.as_str());
+
+    let res = client
+        .get(format!("{}/admin/users/{}", API, user_id))
+        .bearer_auth(&admin)
+        .send()
+        .await
+        .unwrap();
+    let details: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(details["user"]["email"], email.as_str());
+    assert_eq!(details["organizations"].as_array().unwrap().len(), 1);
+
+    let res = client
+        .get(format!("{}/admin/users/{}/sessions", API, user_id))
+        .bearer_auth(&admin)
+        .send()
+        .await
+        .unwrap();
+    let sessions: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(sessions["sessions"].as_array().unwrap().len(), 1);
+
+    // 🚫 SUSPENSION : déconnecté partout et plus de connexion possible
+    let res = client
+        .post(format!("{}/admin/users/{}/suspend", API, user_id))
+        .bearer_auth(&admin)
+        .json(&json!({ "reason": "fraude" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = client.get(format!("{}/me", API)).bearer_auth(&user_token).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    let res = login(&client, &email, "password123").await;
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    assert_eq!(admin_post(&client, &admin, &format!("{}/suspend", user_id)).await.status(), StatusCode::CONFLICT);
+
+    let res = admin_post(&client, &admin, &format!("{}/unsuspend", user_id)).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = login(&client, &email, "password123").await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    // 📜 Tout est tracé, du plus récent au plus ancien
+    assert_eq!(audit_actions(&client, &admin, &user_id).await, vec![json!("unsuspend"), json!("suspend")]);
+}
+
+#[tokio::test]
+async fn staff_force_resets_logouts_and_deletions() {
+    let client = Client::new();
+    let admin = sign_up_admin(&client).await;
+    let email = new_email();
+    let (user_id, user_token) = sign_up(&client, &email).await;
+
+    // 🔌 DÉCONNEXION FORCÉE
+    let res = admin_post(&client, &admin, &format!("{}/logout", user_id)).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = client.get(format!("{}/me", API)).bearer_auth(&user_token).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    // 🔑 RÉINITIALISATION FORCÉE : l'ancien mot de passe ne suffit plus
+    let res = admin_post(&client, &admin, &format!("{}/password-reset", user_id)).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = login(&client, &email, "password123").await;
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+    let res = client
+        .post(format!("{}/auth/password/reset", API))
+        .json(&json!({ "token": token_from(&latest_email(&email)), "password": "new-password456" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = login(&client, &email, "new-password456").await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    // 🙅 Pas sur son propre compte
+    let res = client.get(format!("{}/me", API)).bearer_auth(&admin).send().await.unwrap();
+    let me: serde_json::Value = res.json().await.unwrap();
+    let admin_id = me["id"].as_str().unwrap();
+    let res = admin_post(&client, &admin, &format!("{}/password-reset", admin_id)).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    // 🗑️ SUPPRESSION : la trace survit au compte
+    let res = client
+        .delete(format!("{}/admin/users/{}", API, user_id))
+        .bearer_auth(&admin)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = client
+        .get(format!("{}/admin/users/{}", API, user_id))
+        .bearer_auth(&admin)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    assert_eq!(
+        audit_actions(&client, &admin, &user_id).await,
+        vec![json!("delete_user"), json!("force_password_reset"), json!("force_logout")]
+    );
+}
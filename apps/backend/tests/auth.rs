@@ -1,3 +1,5 @@
+mod common;
+
 use reqwest::{Client, StatusCode};
 use reqwest_cookie_store::CookieStoreMutex;
 use serde_json::json;
@@ -6,11 +8,11 @@ use std::sync::Arc;
 #[tokio::test]
 async fn register_login_refresh_logout() {
     // Crée un store de cookies thread-safe
-    let cookie_store = CookieStoreMutex::default();
+    let cookie_store = Arc::new(CookieStoreMutex::default());
 
     // Crée le client reqwest avec gestion des cookies
     let client = Client::builder()
-        .cookie_provider(Arc::new(cookie_store))
+        .cookie_provider(cookie_store.clone())
         .build()
         .unwrap();
 
@@ -41,6 +43,7 @@ async fn register_login_refresh_logout() {
     // 🔄 REFRESH
     let res = client
         .post("http://localhost:8000/auth/refresh")
+        .header("x-csrf-token", common::csrf_token(&cookie_store))
         .send()
         .await
         .unwrap();
@@ -49,8 +52,276 @@ async fn register_login_refresh_logout() {
     // 🚪 LOGOUT
     let res = client
         .post("http://localhost:8000/auth/logout")
+        .header("x-csrf-token", common::csrf_token(&cookie_store))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+fn set_cookie(res: &reqwest::Response, name: &str) -> String {
+    res.headers()
+        .get_all("set-cookie")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .find(|v| v.starts_with(&format!("{}=", name)))
+        .and_then(|v| v.split(';').next())
+        .unwrap()
+        .to_string()
+}
+
+fn refresh_cookie(res: &reqwest::Response) -> String {
+    set_cookie(res, "refresh_token")
+}
+
+// Valeur du cookie csrf_token, la même pendant toute la session.
+fn csrf_token(res: &reqwest::Response) -> String {
+    set_cookie(res, "csrf_token")["csrf_token=".len()..].to_string()
+}
+
+#[tokio::test]
+async fn refresh_token_reuse_revokes_family() {
+    let client = Client::new();
+    let email = format!("{}@example.com", uuid::Uuid::new_v4());
+
+    let res = client
+        .post("http://localhost:8000/auth/register")
+        .json(&json!({
+            "email": email,
+            "password": "password123"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let first = refresh_cookie(&res);
+    let csrf = csrf_token(&res);
+
+    // La rotation émet un nouveau refresh token
+    let res = client
+        .post("http://localhost:8000/auth/refresh")
+        .header("cookie", &first)
+        .header("x-csrf-token", &csrf)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let second = refresh_cookie(&res);
+    assert_ne!(first, second);
+
+    // Rejouer l'ancien token révoque toute la famille
+    let res = client
+        .post("http://localhost:8000/auth/refresh")
+        .header("cookie", &first)
+        .header("x-csrf-token", &csrf)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    let res = client
+        .post("http://localhost:8000/auth/refresh")
+        .header("cookie", &second)
+        .header("x-csrf-token", &csrf)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn list_and_revoke_sessions() {
+    let client = Client::new();
+    let email = format!("{}@example.com", uuid::Uuid::new_v4());
+
+    let res = client
+        .post("http://localhost:8000/auth/register")
+        .header("user-agent", "laptop")
+        // Le serveur de test n'est pas derrière un proxy, l'en-tête est ignoré
+        .header("x-forwarded-for", "203.0.113.7")
+        .json(&json!({
+            "email": email,
+            "password": "password123"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let laptop = refresh_cookie(&res);
+    let laptop_csrf = csrf_token(&res);
+
+    let res = client
+        .post("http://localhost:8000/auth/login")
+        .header("user-agent", "phone")
+        .json(&json!({
+            "email": email,
+            "password": "password123"
+        }))
         .send()
         .await
         .unwrap();
     assert_eq!(res.status(), StatusCode::OK);
+    let phone = refresh_cookie(&res);
+    let phone_csrf = csrf_token(&res);
+
+    let res = client
+        .get("http://localhost:8000/auth/sessions")
+        .header("cookie", &phone)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: serde_json::Value = res.json().await.unwrap();
+    let sessions = body["sessions"].as_array().unwrap();
+    assert_eq!(sessions.len(), 2);
+    let other = sessions
+        .iter()
+        .find(|s| s["current"] == false)
+        .unwrap();
+    assert_eq!(other["user_agent"], "laptop");
+    assert_eq!(other["ip"], "127.0.0.1");
+
+    // 🚪 Déconnecte le laptop depuis le téléphone
+    let res = client
+        .delete(format!(
+            "http://localhost:8000/auth/sessions/{}",
+            other["id"].as_str().unwrap()
+        ))
+        .header("cookie", &phone)
+        .header("x-csrf-token", &phone_csrf)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let res = client
+        .post("http://localhost:8000/auth/refresh")
+        .header("cookie", &laptop)
+        .header("x-csrf-token", &laptop_csrf)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    let res = client
+        .get("http://localhost:8000/auth/sessions")
+        .header("cookie", &phone)
+        .send()
+        .await
+        .unwrap();
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["sessions"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn logout_revokes_access_token() {
+    let cookie_store = Arc::new(CookieStoreMutex::default());
+    let client = Client::builder()
+        .cookie_provider(cookie_store.clone())
+        .build()
+        .unwrap();
+
+    let res = client
+        .post("http://localhost:8000/auth/register")
+        .json(&json!({
+            "email": format!("{}@example.com", uuid::Uuid::new_v4()),
+            "password": "password123"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+
+    let access_token = cookie_store
+        .lock()
+        .unwrap()
+        .get("localhost", "/", "access_token")
+        .unwrap()
+        .value()
+        .to_string();
+
+    let res = Client::new()
+        .get("http://localhost:8000/me")
+        .bearer_auth(&access_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let res = client
+        .post("http://localhost:8000/auth/logout")
+        .header("x-csrf-token", common::csrf_token(&cookie_store))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    // 🚫 Le token n'a pas expiré mais il est sur la liste de révocation
+    let res = Client::new()
+        .get("http://localhost:8000/me")
+        .bearer_auth(&access_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn protected_routes_accept_cookie_or_bearer_token() {
+    let cookie_store = Arc::new(CookieStoreMutex::default());
+    let client = Client::builder()
+        .cookie_provider(cookie_store.clone())
+        .build()
+        .unwrap();
+
+    let res = client
+        .post("http://localhost:8000/auth/register")
+        .json(&json!({
+            "email": format!("{}@example.com", uuid::Uuid::new_v4()),
+            "password": "password123"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+
+    // 🍪 Par le cookie du navigateur
+    let res = client.get("http://localhost:8000/me").send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    // 🔑 Ou par l'en-tête Authorization
+    let access_token = cookie_store
+        .lock()
+        .unwrap()
+        .get("localhost", "/", "access_token")
+        .unwrap()
+        .value()
+        .to_string();
+    let res = Client::new()
+        .get("http://localhost:8000/me")
+        .bearer_auth(&access_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    // 🚫 Sans token, ou avec un token invalide
+    let res = Client::new().get("http://localhost:8000/me").send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    let res = Client::new()
+        .get("http://localhost:8000/me")
+        .bearer_auth("pas-un-jwt")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    let res = Client::new()
+        .get("http://localhost:8000/me")
+        .header("cookie", "access_token=pas-un-jwt")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
 }
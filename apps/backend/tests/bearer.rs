@@ -0,0 +1,229 @@
+use reqwest::{Client, StatusCode};
+use serde_json::json;
+
+// Pas de cookies ici : les tokens passent par le corps des réponses et
+// l'en-tête Authorization, comme pour l'application mobile ou la CLI.
+
+#[tokio::test]
+async fn tokens_in_body_for_bearer_clients() {
+    let client = Client::new();
+    let email = format!("{}@example.com", uuid::Uuid::new_v4());
+
+    let res = client
+        .post("http://localhost:8000/auth/register")
+        .header("X-Token-Delivery", "body")
+        .json(&json!({
+            "email": email,
+            "password": "password123"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+    assert!(res.headers().get("set-cookie").is_none());
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["user"]["email"], email.as_str());
+    assert_eq!(body["token_type"], "Bearer");
+    assert!(body["expires_in"].as_u64().unwrap() > 0);
+
+    let res = client
+        .get("http://localhost:8000/me")
+        .bearer_auth(body["access_token"].as_str().unwrap())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    // 🔄 REFRESH avec le refresh token dans le corps
+    let res = client
+        .post("http://localhost:8000/auth/refresh")
+        .header("X-Token-Delivery", "body")
+        .json(&json!({ "refresh_token": body["refresh_token"] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(res.headers().get("set-cookie").is_none());
+    let tokens: serde_json::Value = res.json().await.unwrap();
+    assert_ne!(tokens["refresh_token"], body["refresh_token"]);
+
+    // 🚪 LOGOUT
+    let res = client
+        .post("http://localhost:8000/auth/logout")
+        .json(&json!({ "refresh_token": tokens["refresh_token"] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let res = client
+        .get("http://localhost:8000/me")
+        .bearer_auth(tokens["access_token"].as_str().unwrap())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    let res = client
+        .post("http://localhost:8000/auth/refresh")
+        .json(&json!({ "refresh_token": tokens["refresh_token"] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn token_delivery_configured_per_client() {
+    let client = Client::new();
+    let email = format!("{}@example.com", uuid::Uuid::new_v4());
+    let credentials = json!({
+        "email": email,
+        "password": "password123"
+    });
+
+    let res = client
+        .post("http://localhost:8000/auth/register")
+        .json(&credentials)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+    assert!(res.headers().get("set-cookie").is_some());
+
+    // AUTH__CLIENTS__MOBILE__TOKEN_DELIVERY=body
+    let res = client
+        .post("http://localhost:8000/auth/login")
+        .header("X-Client-Id", "mobile")
+        .json(&credentials)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert!(body["access_token"].is_string());
+
+    let res = client
+        .post("http://localhost:8000/auth/login")
+        .header("X-Token-Delivery", "carrier-pigeon")
+        .json(&credentials)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+async fn login(client: &Client, email: &str, password: &str) -> String {
+    let res = client
+        .post("http://localhost:8000/auth/login")
+        .header("X-Token-Delivery", "body")
+        .json(&json!({ "email": email, "password": password }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: serde_json::Value = res.json().await.unwrap();
+    body["access_token"].as_str().unwrap().to_string()
+}
+
+async fn me(client: &Client, token: &str) -> StatusCode {
+    client
+        .get("http://localhost:8000/me")
+        .bearer_auth(token)
+        .send()
+        .await
+        .unwrap()
+        .status()
+}
+
+#[tokio::test]
+async fn bearer_clients_manage_their_sessions() {
+    let client = Client::new();
+    let email = format!("{}@example.com", uuid::Uuid::new_v4());
+    let res = client
+        .post("http://localhost:8000/auth/register")
+        .header("X-Token-Delivery", "body")
+        .json(&json!({ "email": email, "password": "password123" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let phone = login(&client, &email, "password123").await;
+    let laptop = login(&client, &email, "password123").await;
+
+    // 📱 La session courante est celle de l'access token
+    let res = client
+        .get("http://localhost:8000/auth/sessions")
+        .bearer_auth(&phone)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: serde_json::Value = res.json().await.unwrap();
+    let sessions = body["sessions"].as_array().unwrap();
+    assert_eq!(sessions.len(), 3);
+    assert_eq!(sessions.iter().filter(|s| s["current"] == true).count(), 1);
+    let other = sessions
+        .iter()
+        .find(|s| s["current"] == false)
+        .unwrap()["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let res = client
+        .patch(format!("http://localhost:8000/auth/sessions/{}", other))
+        .bearer_auth(&phone)
+        .json(&json!({ "name": "Vieux portable" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    // 🔑 Changer de mot de passe ne déconnecte que les autres appareils
+    let res = client
+        .post("http://localhost:8000/me/password")
+        .bearer_auth(&phone)
+        .json(&json!({ "current_password": "password123", "new_password": "password456" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(me(&client, &phone).await, StatusCode::OK);
+    assert_eq!(me(&client, &laptop).await, StatusCode::UNAUTHORIZED);
+
+    // 🚪 Déconnexion des autres appareils, puis de la session elle-même
+    let tablet = login(&client, &email, "password456").await;
+    let res = client
+        .post("http://localhost:8000/auth/logout-all")
+        .bearer_auth(&phone)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(me(&client, &tablet).await, StatusCode::UNAUTHORIZED);
+    assert_eq!(me(&client, &phone).await, StatusCode::OK);
+
+    let res = client
+        .get("http://localhost:8000/auth/sessions")
+        .bearer_auth(&phone)
+        .send()
+        .await
+        .unwrap();
+    let body: serde_json::Value = res.json().await.unwrap();
+    let current = body["sessions"][0]["id"].as_str().unwrap().to_string();
+    let res = client
+        .delete(format!("http://localhost:8000/auth/sessions/{}", current))
+        .bearer_auth(&phone)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = client
+        .get("http://localhost:8000/auth/sessions")
+        .bearer_auth(&phone)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,74 @@
+// Chaque fichier de test n'utilise qu'une partie de ces fonctions.
+#![allow(dead_code)]
+
+use reqwest::{header, Client};
+use reqwest_cookie_store::CookieStoreMutex;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+// Le serveur de test doit tourner avec MAIL__OUTBOX_DIR pour que les emails
+// soient écrits sur disque plutôt qu'envoyés.
+fn outbox_dir() -> PathBuf {
+    std::env::var("MAIL__OUTBOX_DIR")
+        .unwrap_or_else(|_| "/tmp/saas-outbox".to_string())
+        .into()
+}
+
+/// Dernier email reçu par `to`.
+pub fn latest_email(to: &str) -> serde_json::Value {
+    let mut entries: Vec<_> = std::fs::read_dir(outbox_dir())
+        .unwrap()
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .collect();
+    entries.sort();
+
+    entries
+        .iter()
+        .rev()
+        .filter_map(|path| std::fs::read_to_string(path).ok())
+        .filter_map(|content| serde_json::from_str::<serde_json::Value>(&content).ok())
+        .find(|email| email["to"] == to)
+        .unwrap_or_else(|| panic!("no email sent to {}", to))
+}
+
+/// Extrait le paramètre `token` du lien contenu dans l'email.
+pub fn token_from(email: &serde_json::Value) -> String {
+    let body = email["body"].as_str().unwrap();
+    let start = body.find("token=").unwrap() + "token=".len();
+    body[start..]
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+        .collect()
+}
+
+/// Jeton CSRF de la session du navigateur, à renvoyer dans X-CSRF-Token.
+pub fn csrf_token(cookies: &CookieStoreMutex) -> String {
+    cookies
+        .lock()
+        .unwrap()
+        .get("localhost", "/", "csrf_token")
+        .unwrap()
+        .value()
+        .to_string()
+}
+
+/// Client partageant les cookies d'une session ouverte, qui renvoie son jeton
+/// CSRF comme le ferait le frontend.
+pub fn with_csrf(cookies: Arc<CookieStoreMutex>) -> Client {
+    let mut headers = header::HeaderMap::new();
+    headers.insert("x-csrf-token", csrf_token(&cookies).parse().unwrap());
+    Client::builder()
+        .cookie_provider(cookies)
+        .default_headers(headers)
+        .build()
+        .unwrap()
+}
+
+/// Connexion à la base avec la configuration du serveur de test, donc avec
+/// son rôle applicatif soumis à la sécurité au niveau des lignes.
+pub async fn app_pool() -> sqlx::PgPool {
+    dotenvy::dotenv().ok();
+    let config = backend::config::AppConfig::from_env().unwrap();
+    backend::db::create_pool(&config.database).await.unwrap()
+}
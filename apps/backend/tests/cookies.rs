@@ -0,0 +1,66 @@
+use reqwest::{Client, StatusCode};
+use serde_json::json;
+
+fn set_cookies(res: &reqwest::Response) -> Vec<String> {
+    res.headers()
+        .get_all("set-cookie")
+        .iter()
+        .map(|v| v.to_str().unwrap().to_string())
+        .collect()
+}
+
+fn find<'a>(cookies: &'a [String], name: &str) -> &'a str {
+    cookies
+        .iter()
+        .find(|c| c.starts_with(&format!("{}=", name)))
+        .unwrap_or_else(|| panic!("no {} cookie", name))
+}
+
+// Politique par défaut : Secure, SameSite=Strict, Path=/, sans domaine.
+#[tokio::test]
+async fn session_cookies_follow_the_cookie_policy() {
+    let client = Client::new();
+    let res = client
+        .post("http://localhost:8000/auth/register")
+        .json(&json!({
+            "email": format!("{}@example.com", uuid::Uuid::new_v4()),
+            "password": "password123"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let cookies = set_cookies(&res);
+
+    // ⏱️ La durée de vie suit jwt_access_expires_in et jwt_refresh_expires_in
+    let access = find(&cookies, "access_token");
+    assert!(access.contains("HttpOnly"));
+    assert!(access.contains("SameSite=Strict"));
+    assert!(access.contains("Secure"));
+    assert!(access.contains("Path=/"));
+    assert!(access.contains("Max-Age=900"));
+    assert!(!access.contains("Domain"));
+    assert!(find(&cookies, "refresh_token").contains("Max-Age=604800"));
+    assert!(!find(&cookies, "csrf_token").contains("HttpOnly"));
+
+    let refresh = find(&cookies, "refresh_token").split(';').next().unwrap().to_string();
+    let csrf = find(&cookies, "csrf_token").split(';').next().unwrap()["csrf_token=".len()..]
+        .to_string();
+
+    // 🚪 Le logout renvoie des cookies expirés
+    let res = client
+        .post("http://localhost:8000/auth/logout")
+        .header("cookie", refresh)
+        .header("x-csrf-token", csrf)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let cookies = set_cookies(&res);
+    for name in ["access_token", "refresh_token", "csrf_token"] {
+        let cookie = find(&cookies, name);
+        assert!(cookie.starts_with(&format!("{}=;", name)), "{}", cookie);
+        assert!(cookie.contains("Max-Age=0"), "{}", cookie);
+        assert!(cookie.contains("Path=/"), "{}", cookie);
+    }
+}
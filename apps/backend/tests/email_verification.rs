@@ -0,0 +1,67 @@
+mod common;
+
+use reqwest::{Client, StatusCode};
+use reqwest_cookie_store::CookieStoreMutex;
+use serde_json::json;
+use std::sync::Arc;
+
+#[tokio::test]
+async fn verify_email_with_emailed_token() {
+    let cookie_store = CookieStoreMutex::default();
+    let client = Client::builder()
+        .cookie_provider(Arc::new(cookie_store))
+        .build()
+        .unwrap();
+    let email = format!("{}@example.com", uuid::Uuid::new_v4());
+
+    let res = client
+        .post("http://localhost:8000/auth/register")
+        .json(&json!({
+            "email": email,
+            "password": "password123"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+
+    let res = client.get("http://localhost:8000/me").send().await.unwrap();
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["email_verified_at"], serde_json::Value::Null);
+
+    let token = common::token_from(&common::latest_email(&email));
+
+    let res = client
+        .post("http://localhost:8000/auth/verify-email")
+        .json(&json!({ "token": token }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let res = client.get("http://localhost:8000/me").send().await.unwrap();
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert!(body["email_verified_at"].is_string());
+
+    // Le token est à usage unique
+    let res = client
+        .post("http://localhost:8000/auth/verify-email")
+        .json(&json!({ "token": token }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn resend_verification_does_not_leak_accounts() {
+    let client = Client::new();
+
+    let res = client
+        .post("http://localhost:8000/auth/resend-verification")
+        .json(&json!({ "email": "nobody@example.com" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::ACCEPTED);
+}
@@ -0,0 +1,322 @@
+mod common;
+
+use reqwest::{Client, StatusCode};
+use serde_json::json;
+use wiremock::matchers::{method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use common::{latest_email, token_from};
+
+const API: &str = "http://localhost:8000";
+
+fn new_email(domain: &str) -> String {
+    format!("{}@{}", uuid::Uuid::new_v4(), domain)
+}
+
+/// Inscription en mode bearer, avec éventuellement un jeton d'invitation.
+async fn register(client: &Client, email: &str, invitation: Option<&str>) -> reqwest::Response {
+    client
+        .post(format!("{}/auth/register", API))
+        .header("X-Token-Delivery", "body")
+        .json(&json!({
+            "email": email,
+            "password": "password123",
+            "invitation_token": invitation
+        }))
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn sign_up(client: &Client, email: &str) -> String {
+    let res = register(client, email, None).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let body: serde_json::Value = res.json().await.unwrap();
+    body["access_token"].as_str().unwrap().to_string()
+}
+
+async fn create_org(client: &Client, token: &str) -> String {
+    let res = client
+        .post(format!("{}/orgs", API))
+        .bearer_auth(token)
+        .json(&json!({ "name": "Acme" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let org: serde_json::Value = res.json().await.unwrap();
+    org["id"].as_str().unwrap().to_string()
+}
+
+async fn invite(
+    client: &Client,
+    token: &str,
+    org_id: &str,
+    email: &str,
+    role: &str,
+) -> reqwest::Response {
+    client
+        .post(format!("{}/orgs/{}/invitations", API, org_id))
+        .bearer_auth(token)
+        .json(&json!({ "email": email, "role": role }))
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn accept(client: &Client, token: &str, invitation: &str) -> reqwest::Response {
+    client
+        .post(format!("{}/invitations/accept", API))
+        .bearer_auth(token)
+        .json(&json!({ "token": invitation }))
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn org_ids(client: &Client, token: &str) -> Vec<serde_json::Value> {
+    let res = client
+        .get(format!("{}/orgs", API))
+        .bearer_auth(token)
+        .send()
+        .await
+        .unwrap();
+    let orgs: serde_json::Value = res.json().await.unwrap();
+    orgs.as_array().unwrap().iter().map(|o| o["id"].clone()).collect()
+}
+
+#[tokio::test]
+async fn existing_users_accept_invitations() {
+    let client = Client::new();
+    let owner = sign_up(&client, &new_email("example.com")).await;
+    let org_id = create_org(&client, &owner).await;
+
+    let bob_email = new_email("example.com");
+    let bob = sign_up(&client, &bob_email).await;
+    let res = invite(&client, &owner, &org_id, &bob_email, "admin").await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let invitation = token_from(&latest_email(&bob_email));
+
+    // ⛔ Une seule invitation en attente par adresse
+    let res = invite(&client, &owner, &org_id, &bob_email, "member").await;
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+
+    // ⛔ Le lien ne sert qu'au destinataire
+    let eve = sign_up(&client, &new_email("example.com")).await;
+    let res = accept(&client, &eve, &invitation).await;
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+    // ✅ ACCEPTATION
+    let res = accept(&client, &bob, &invitation).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let membership: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(membership["organization_id"], org_id.as_str());
+    assert_eq!(membership["role"], "admin");
+    assert!(org_ids(&client, &bob).await.contains(&json!(org_id)));
+
+    // Usage unique
+    let res = accept(&client, &bob, &invitation).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    // ⛔ Un admin ne peut pas inviter de propriétaire, un membre ne peut pas inviter
+    let res = invite(&client, &bob, &org_id, &new_email("example.com"), "owner").await;
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    let res = invite(&client, &eve, &org_id, &new_email("example.com"), "member").await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn sign_up_consumes_the_invitation() {
+    let client = Client::new();
+    let owner = sign_up(&client, &new_email("example.com")).await;
+    let org_id = create_org(&client, &owner).await;
+
+    let email = new_email("example.com");
+    invite(&client, &owner, &org_id, &email, "member").await;
+    let invitation = token_from(&latest_email(&email));
+
+    // ⛔ Un jeton invalide annule l'inscription
+    let res = register(&client, &email, Some("invalid")).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    let res = register(&client, &email, Some(&invitation)).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let body: serde_json::Value = res.json().await.unwrap();
+    let orgs = org_ids(&client, body["access_token"].as_str().unwrap()).await;
+    assert_eq!(orgs.len(), 2);
+    assert!(orgs.contains(&json!(org_id)));
+
+    let res = client
+        .get(format!("{}/orgs/{}/invitations", API, org_id))
+        .bearer_auth(&owner)
+        .send()
+        .await
+        .unwrap();
+    let pending: serde_json::Value = res.json().await.unwrap();
+    assert!(pending.as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn invitations_can_be_resent_and_revoked() {
+    let client = Client::new();
+    let owner = sign_up(&client, &new_email("example.com")).await;
+    let org_id = create_org(&client, &owner).await;
+
+    let email = new_email("example.com");
+    let res = invite(&client, &owner, &org_id, &email, "billing").await;
+    let created: serde_json::Value = res.json().await.unwrap();
+    let first = token_from(&latest_email(&email));
+    let url = format!("{}/orgs/{}/invitations/{}", API, org_id, created["id"].as_str().unwrap());
+
+    let res = client
+        .get(format!("{}/orgs/{}/invitations", API, org_id))
+        .bearer_auth(&owner)
+        .send()
+        .await
+        .unwrap();
+    let pending: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(pending[0]["email"], email.as_str());
+    assert!(pending[0].get("token_hash").is_none());
+
+    // 🔁 RENVOI : le nouveau lien remplace l'ancien
+    let res = client
+        .post(format!("{}/resend", url))
+        .bearer_auth(&owner)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let second = token_from(&latest_email(&email));
+    assert_ne!(first, second);
+
+    let invitee = sign_up(&client, &email).await;
+    let res = accept(&client, &invitee, &first).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    // 🗑️ RÉVOCATION
+    let res = client.delete(&url).bearer_auth(&owner).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = accept(&client, &invitee, &second).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+/// Résolveur DNS-over-HTTPS du serveur de test, sans aucun enregistrement.
+async fn start_resolver() -> MockServer {
+    let listener = std::net::TcpListener::bind("127.0.0.1:8090").unwrap();
+    let server = MockServer::builder().listener(listener).start().await;
+    Mock::given(method("GET"))
+        .and(path("/dns-query"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "Status": 3 })))
+        .with_priority(10)
+        .mount(&server)
+        .await;
+    server
+}
+
+/// Compte à l'adresse vérifiée, renvoie son access token.
+async fn sign_up_verified(client: &Client, email: &str) -> String {
+    let token = sign_up(client, email).await;
+    verify_email(client, email).await;
+    token
+}
+
+async fn verify_email(client: &Client, email: &str) {
+    let res = client
+        .post(format!("{}/auth/verify-email", API))
+        .json(&json!({ "token": token_from(&latest_email(email)) }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn verified_addresses_auto_join_their_domain() {
+    let client = Client::new();
+    let domain = format!("{}.example.com", uuid::Uuid::new_v4());
+
+    let owner_email = new_email(&domain);
+    let owner = sign_up(&client, &owner_email).await;
+    let org_id = create_org(&client, &owner).await;
+    let set_domain = |token: String, org_id: String, domain: String| {
+        client
+            .patch(format!("{}/orgs/{}", API, org_id))
+            .bearer_auth(token)
+            .json(&json!({ "auto_join_domain": domain }))
+            .send()
+    };
+    let verify_domain = |token: String, org_id: String| {
+        client
+            .post(format!("{}/orgs/{}/domain/verify", API, org_id))
+            .bearer_auth(token)
+            .send()
+    };
+
+    // ⛔ Il faut d'abord prouver une adresse sur ce domaine
+    let res = set_domain(owner.clone(), org_id.clone(), domain.clone()).await.unwrap();
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    verify_email(&client, &owner_email).await;
+
+    let res = set_domain(owner.clone(), org_id.clone(), "gmail.com".to_string()).await.unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let res = set_domain(owner.clone(), org_id.clone(), domain.to_uppercase()).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let org: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(org["auto_join_domain"], domain.as_str());
+    assert!(org["auto_join_domain_verified_at"].is_null());
+
+    // 🏴 Un collègue peut revendiquer le même domaine, personne ne le garde
+    let squatter = sign_up_verified(&client, &new_email(&domain)).await;
+    let squatter_org = create_org(&client, &squatter).await;
+    let res = set_domain(squatter.clone(), squatter_org.clone(), domain.clone()).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    // ⏳ Tant que le domaine n'est pas vérifié, personne ne rejoint
+    let email = new_email(&domain);
+    let member = sign_up_verified(&client, &email).await;
+    assert!(!org_ids(&client, &member).await.contains(&json!(org_id)));
+    assert!(!org_ids(&client, &member).await.contains(&json!(squatter_org)));
+
+    // 🌐 Preuve par un enregistrement TXT, lu via le résolveur DNS-over-HTTPS
+    // du serveur de test (SERVER__DNS_RESOLVER_URL)
+    let res = client
+        .get(format!("{}/orgs/{}/domain", API, org_id))
+        .bearer_auth(&owner)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let claim: serde_json::Value = res.json().await.unwrap();
+    let record = &claim["record"];
+    assert_eq!(record["name"], format!("_saas-verification.{}", domain).as_str());
+
+    let resolver = start_resolver().await;
+    let res = verify_domain(owner.clone(), org_id.clone()).await.unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    Mock::given(method("GET"))
+        .and(path("/dns-query"))
+        .and(query_param("name", record["name"].as_str().unwrap()))
+        .and(query_param("type", "TXT"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "Status": 0,
+            "Answer": [{ "type": 16, "data": format!("\"{}\"", record["value"].as_str().unwrap()) }],
+        })))
+        .mount(&resolver)
+        .await;
+    let res = verify_domain(owner.clone(), org_id.clone()).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let org: serde_json::Value = res.json().await.unwrap();
+    assert!(!org["auto_join_domain_verified_at"].is_null());
+
+    // ⛔ L'enregistrement ne vaut que pour l'organisation qui l'a demandé
+    let res = verify_domain(squatter.clone(), squatter_org.clone()).await.unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    // Un nouveau compte rejoint l'organisation en vérifiant son adresse
+    let email = new_email(&domain);
+    let member = sign_up_verified(&client, &email).await;
+    let orgs = org_ids(&client, &member).await;
+    assert!(orgs.contains(&json!(org_id)));
+    assert!(!orgs.contains(&json!(squatter_org)));
+}
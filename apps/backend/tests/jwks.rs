@@ -0,0 +1,148 @@
+use jsonwebtoken::{
+    decode, decode_header, encode, jwk::JwkSet, Algorithm, DecodingKey, EncodingKey, Header,
+    Validation,
+};
+use reqwest::{Client, StatusCode};
+use reqwest_cookie_store::CookieStoreMutex;
+use serde_json::json;
+use std::sync::Arc;
+
+// Le serveur de test signe les access tokens avec une clé asymétrique
+// (AUTH__JWT_ALGORITHM et AUTH__JWT_PRIVATE_KEY_FILE) et accepte encore une
+// ancienne clé RS256 (AUTH__JWT_VERIFICATION_KEYS__PREVIOUS__*).
+
+async fn register(client: &Client) -> serde_json::Value {
+    let res = client
+        .post("http://localhost:8000/auth/register")
+        .json(&json!({
+            "email": format!("{}@example.com", uuid::Uuid::new_v4()),
+            "password": "password123"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+    res.json().await.unwrap()
+}
+
+async fn jwks() -> JwkSet {
+    let res = Client::new()
+        .get("http://localhost:8000/.well-known/jwks.json")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    res.json().await.unwrap()
+}
+
+// Émetteur et audience par défaut (AUTH__JWT_ISSUER, AUTH__JWT_AUDIENCE).
+const ISSUER: &str = "http://localhost:8000";
+const AUDIENCE: &str = "saas-api";
+
+fn validation(alg: Algorithm) -> Validation {
+    let mut validation = Validation::new(alg);
+    validation.set_issuer(&[ISSUER]);
+    validation.set_audience(&[AUDIENCE]);
+    validation
+}
+
+#[tokio::test]
+async fn access_tokens_verify_against_published_jwks() {
+    let cookie_store = Arc::new(CookieStoreMutex::default());
+    let client = Client::builder()
+        .cookie_provider(cookie_store.clone())
+        .build()
+        .unwrap();
+    let body = register(&client).await;
+
+    let access_token = cookie_store
+        .lock()
+        .unwrap()
+        .get("localhost", "/", "access_token")
+        .unwrap()
+        .value()
+        .to_string();
+
+    let jwks = jwks().await;
+
+    // 🔑 Un service tiers vérifie le token avec la seule clé publique
+    let header = decode_header(&access_token).unwrap();
+    let kid = header.kid.expect("kid header");
+    let jwk = jwks.find(&kid).expect("signing key published");
+    let claims = decode::<serde_json::Value>(
+        &access_token,
+        &DecodingKey::from_jwk(jwk).unwrap(),
+        &validation(header.alg),
+    )
+    .unwrap()
+    .claims;
+    assert_eq!(claims["sub"], body["user"]["id"]);
+    assert_eq!(claims["typ"], "access");
+    assert!(claims["jti"].is_string());
+}
+
+#[tokio::test]
+async fn verification_keys_are_accepted_until_removed() {
+    let user = register(&Client::new()).await;
+
+    // Token signé par l'ancienne clé, qui reste publiée
+    let jwks = jwks().await;
+    assert!(jwks.keys.len() >= 2);
+    let previous = jwks
+        .keys
+        .iter()
+        .find(|k| k.common.key_algorithm.map(|a| a.to_string()) == Some("RS256".to_string()))
+        .expect("previous key published");
+    let pem = std::fs::read(std::env::var("AUTH__JWT_VERIFICATION_KEYS__PREVIOUS__KEY_FILE").unwrap())
+        .unwrap();
+    let now = chrono::Utc::now().timestamp();
+    let claims = json!({
+        "iss": ISSUER,
+        "aud": AUDIENCE,
+        "sub": user["user"]["id"],
+        "iat": now,
+        "nbf": now,
+        "exp": now + 60,
+        "jti": uuid::Uuid::new_v4(),
+        "typ": "access",
+    });
+
+    let sign = |kid: &str, claims: &serde_json::Value| {
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some(kid.to_string());
+        encode(&header, claims, &EncodingKey::from_rsa_pem(&pem).unwrap()).unwrap()
+    };
+    let me = |token: String| async move {
+        Client::new()
+            .get("http://localhost:8000/me")
+            .bearer_auth(token)
+            .send()
+            .await
+            .unwrap()
+            .status()
+    };
+
+    let kid = previous.common.key_id.as_ref().unwrap();
+    assert_eq!(me(sign(kid, &claims)).await, StatusCode::OK);
+
+    // Un kid inconnu est refusé
+    assert_eq!(me(sign("unknown", &claims)).await, StatusCode::UNAUTHORIZED);
+
+    // 🚫 Un token émis pour un autre usage ou une autre audience est refusé
+    for (claim, value) in [
+        ("typ", "refresh"),
+        ("typ", "mfa"),
+        ("aud", "other-api"),
+        ("iss", "https://evil.example.com"),
+    ] {
+        let mut other = claims.clone();
+        other[claim] = json!(value);
+        assert_eq!(me(sign(kid, &other)).await, StatusCode::UNAUTHORIZED, "{}", claim);
+    }
+
+    // ⏳ Tout comme un token qui n'est pas encore valable
+    let mut early = claims.clone();
+    early["nbf"] = json!(now + 3600);
+    early["exp"] = json!(now + 7200);
+    assert_eq!(me(sign(kid, &early)).await, StatusCode::UNAUTHORIZED);
+}
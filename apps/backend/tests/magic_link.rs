@@ -0,0 +1,91 @@
+mod common;
+
+use reqwest::{redirect, Client, StatusCode};
+use reqwest_cookie_store::CookieStoreMutex;
+use serde_json::json;
+use std::sync::Arc;
+
+fn new_client() -> Client {
+    Client::builder()
+        .cookie_provider(Arc::new(CookieStoreMutex::default()))
+        .redirect(redirect::Policy::none())
+        .build()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn magic_link_signs_in_the_requesting_browser_only() {
+    let email = format!("{}@example.com", uuid::Uuid::new_v4());
+    let res = new_client()
+        .post("http://localhost:8000/auth/register")
+        .json(&json!({
+            "email": email,
+            "password": "password123"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+
+    // Aucune différence pour un compte inconnu
+    let res = new_client()
+        .post("http://localhost:8000/auth/magic-link")
+        .json(&json!({ "email": "nobody@example.com" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::ACCEPTED);
+
+    let browser = new_client();
+    let res = browser
+        .post("http://localhost:8000/auth/magic-link")
+        .json(&json!({ "email": email }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::ACCEPTED);
+
+    let email_sent = common::latest_email(&email);
+    let token = common::token_from(&email_sent);
+    let link = format!("http://localhost:8000/auth/magic-link/consume?token={}", token);
+
+    // 🔗 Un lien transféré ne fonctionne pas ailleurs, et n'est pas consommé
+    let res = new_client().get(&link).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+    let res = browser.get(&link).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::SEE_OTHER);
+    assert_eq!(res.headers()["location"], "http://localhost:3000/");
+
+    let res = browser.get("http://localhost:8000/me").send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let me: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(me["email"], email.as_str());
+    assert!(me["email_verified_at"].is_string());
+
+    // Le lien est à usage unique
+    let res = browser.get(&link).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn magic_link_is_accepted_when_the_email_cannot_be_sent() {
+    // 📭 Adresse que le mailer refuse, insérée directement en base
+    let email = format!("pas une adresse {}", uuid::Uuid::new_v4());
+    let pool = common::app_pool().await;
+    sqlx::query("INSERT INTO users (email, password_hash) VALUES ($1, 'x')")
+        .bind(&email)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    // 🤫 Même réponse, cookie compris, que pour un compte inconnu
+    let res = new_client()
+        .post("http://localhost:8000/auth/magic-link")
+        .json(&json!({ "email": email }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::ACCEPTED);
+    assert!(res.headers().get("set-cookie").is_some());
+}
@@ -0,0 +1,246 @@
+mod common;
+
+use reqwest::{Client, StatusCode};
+use reqwest_cookie_store::CookieStoreMutex;
+use serde_json::json;
+use std::sync::Arc;
+
+#[tokio::test]
+async fn get_and_update_profile() {
+    let cookie_store = Arc::new(CookieStoreMutex::default());
+    let client = Client::builder()
+        .cookie_provider(cookie_store.clone())
+        .build()
+        .unwrap();
+    let email = format!("{}@example.com", uuid::Uuid::new_v4());
+
+    // Sans cookie, la route est protégée
+    let res = Client::new()
+        .get("http://localhost:8000/me")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    let res = client
+        .post("http://localhost:8000/auth/register")
+        .json(&json!({
+            "email": email,
+            "password": "password123"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let client = common::with_csrf(cookie_store);
+
+    let res = client.get("http://localhost:8000/me").send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["email"], email.as_str());
+    assert_eq!(body["name"], serde_json::Value::Null);
+
+    let res = client
+        .patch("http://localhost:8000/me")
+        .json(&json!({
+            "name": "Ada Lovelace",
+            "locale": "fr-FR",
+            "timezone": "Europe/Paris"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["name"], "Ada Lovelace");
+    assert_eq!(body["locale"], "fr-FR");
+    assert_eq!(body["timezone"], "Europe/Paris");
+
+    let res = client
+        .patch("http://localhost:8000/me")
+        .json(&json!({ "avatar_url": "javascript:alert(1)" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    // Avec un Bearer token
+    let res = Client::new()
+        .get("http://localhost:8000/me")
+        .header("authorization", "Bearer not-a-token")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
+
+async fn register(email: &str) -> Client {
+    let cookie_store = Arc::new(CookieStoreMutex::default());
+    let client = Client::builder()
+        .cookie_provider(cookie_store.clone())
+        .build()
+        .unwrap();
+    let res = client
+        .post("http://localhost:8000/auth/register")
+        .json(&json!({
+            "email": email,
+            "password": "password123"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+    common::with_csrf(cookie_store)
+}
+
+#[tokio::test]
+async fn change_password_revokes_other_sessions() {
+    let email = format!("{}@example.com", uuid::Uuid::new_v4());
+    let client = register(&email).await;
+
+    let other_cookies = Arc::new(CookieStoreMutex::default());
+    let other = Client::builder()
+        .cookie_provider(other_cookies.clone())
+        .build()
+        .unwrap();
+    let res = other
+        .post("http://localhost:8000/auth/login")
+        .json(&json!({
+            "email": email,
+            "password": "password123"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let other = common::with_csrf(other_cookies);
+
+    let res = client
+        .post("http://localhost:8000/me/password")
+        .json(&json!({
+            "current_password": "wrong-password",
+            "new_password": "new-password456"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    let res = client
+        .post("http://localhost:8000/me/password")
+        .json(&json!({
+            "current_password": "password123",
+            "new_password": "new-password456"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let res = client
+        .post("http://localhost:8000/auth/refresh")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let res = other
+        .post("http://localhost:8000/auth/refresh")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    // L'access token de l'autre appareil est révoqué sans attendre son expiration
+    let res = other.get("http://localhost:8000/me").send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    let res = client.get("http://localhost:8000/me").send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn change_email_after_confirmation() {
+    let email = format!("{}@example.com", uuid::Uuid::new_v4());
+    let taken = format!("{}@example.com", uuid::Uuid::new_v4());
+    let new_email = format!("{}@example.com", uuid::Uuid::new_v4());
+    let client = register(&email).await;
+    register(&taken).await;
+
+    // Un email déjà utilisé renvoie 409, à l'inscription comme au changement
+    let res = Client::new()
+        .post("http://localhost:8000/auth/register")
+        .json(&json!({
+            "email": taken,
+            "password": "password123"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+
+    let res = client
+        .post("http://localhost:8000/me/email")
+        .json(&json!({ "new_email": taken, "password": "password123" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+
+    let res = client
+        .post("http://localhost:8000/me/email")
+        .json(&json!({ "new_email": new_email, "password": "password123" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::ACCEPTED);
+
+    // Rien ne change avant la confirmation
+    let res = client.get("http://localhost:8000/me").send().await.unwrap();
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["email"], email.as_str());
+
+    let token = common::token_from(&common::latest_email(&new_email));
+    let res = client
+        .post("http://localhost:8000/auth/email/confirm")
+        .json(&json!({ "token": token }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let res = client.get("http://localhost:8000/me").send().await.unwrap();
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["email"], new_email.as_str());
+    assert!(body["email_verified_at"].is_string());
+
+    // 🔁 Le lien ne sert qu'une fois
+    let res = client
+        .post("http://localhost:8000/auth/email/confirm")
+        .json(&json!({ "token": token }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    // ⚠️ Mais un échec ne le consomme pas
+    let later = format!("{}@example.com", uuid::Uuid::new_v4());
+    let res = client
+        .post("http://localhost:8000/me/email")
+        .json(&json!({ "new_email": later, "password": "password123" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::ACCEPTED);
+    let token = common::token_from(&common::latest_email(&later));
+    register(&later).await;
+    for _ in 0..2 {
+        let res = client
+            .post("http://localhost:8000/auth/email/confirm")
+            .json(&json!({ "token": token }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+    }
+}
@@ -0,0 +1,128 @@
+mod common;
+
+use reqwest::{Client, StatusCode};
+use reqwest_cookie_store::CookieStoreMutex;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use totp_rs::{Algorithm, Secret, TOTP};
+
+fn new_client() -> Client {
+    Client::builder()
+        .cookie_provider(Arc::new(CookieStoreMutex::default()))
+        .build()
+        .unwrap()
+}
+
+fn code_at(secret: &str, offset: u64) -> String {
+    let bytes = Secret::Encoded(secret.to_string()).to_bytes().unwrap();
+    let totp = TOTP::new_unchecked(Algorithm::SHA1, 6, 1, 30, bytes, None, String::new());
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    totp.generate(now + offset)
+}
+
+async fn login(client: &Client, email: &str) -> serde_json::Value {
+    let res = client
+        .post("http://localhost:8000/auth/login")
+        .json(&json!({
+            "email": email,
+            "password": "password123"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    res.json().await.unwrap()
+}
+
+#[tokio::test]
+async fn totp_enrollment_and_second_factor_login() {
+    let cookie_store = Arc::new(CookieStoreMutex::default());
+    let client = Client::builder()
+        .cookie_provider(cookie_store.clone())
+        .build()
+        .unwrap();
+    let email = format!("{}@example.com", uuid::Uuid::new_v4());
+
+    let res = client
+        .post("http://localhost:8000/auth/register")
+        .json(&json!({
+            "email": email,
+            "password": "password123"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let client = common::with_csrf(cookie_store);
+
+    // 🔐 ENROLLMENT
+    let res = client
+        .post("http://localhost:8000/me/mfa/totp/setup")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: serde_json::Value = res.json().await.unwrap();
+    let secret = body["secret"].as_str().unwrap().to_string();
+    assert!(body["otpauth_uri"]
+        .as_str()
+        .unwrap()
+        .starts_with("otpauth://totp/"));
+
+    let res = client
+        .post("http://localhost:8000/me/mfa/totp/confirm")
+        .json(&json!({ "code": code_at(&secret, 0) }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: serde_json::Value = res.json().await.unwrap();
+    let recovery_codes = body["recovery_codes"].as_array().unwrap().clone();
+    assert_eq!(recovery_codes.len(), 10);
+
+    // 🔐 LOGIN : le mot de passe seul ne suffit plus
+    let device = new_client();
+    let body = login(&device, &email).await;
+    assert_eq!(body["mfa_required"], true);
+    let mfa_token = body["mfa_token"].as_str().unwrap().to_string();
+
+    let res = device.get("http://localhost:8000/me").send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    let res = device
+        .post("http://localhost:8000/auth/mfa/verify")
+        .json(&json!({ "mfa_token": mfa_token, "code": "000000" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    let res = device
+        .post("http://localhost:8000/auth/mfa/verify")
+        .json(&json!({ "mfa_token": mfa_token, "code": code_at(&secret, 30) }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let res = device.get("http://localhost:8000/me").send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    // 🔑 RECOVERY CODE, utilisable une seule fois
+    let recovery_code = recovery_codes[0].as_str().unwrap();
+    for expected in [StatusCode::OK, StatusCode::UNAUTHORIZED] {
+        let device = new_client();
+        let body = login(&device, &email).await;
+        let res = device
+            .post("http://localhost:8000/auth/mfa/verify")
+            .json(&json!({
+                "mfa_token": body["mfa_token"],
+                "recovery_code": recovery_code
+            }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), expected);
+    }
+}
@@ -0,0 +1,221 @@
+mod common;
+
+use backend::utils::token::hash_token;
+use reqwest::{redirect, Client, StatusCode, Url};
+use reqwest_cookie_store::CookieStoreMutex;
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Arc;
+use wiremock::matchers::{body_string_contains, header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+// Le serveur de test est configuré avec un fournisseur `mock` dont l'issuer
+// est http://127.0.0.1:8089 (OAUTH__MOCK__ISSUER).
+const ISSUER: &str = "http://127.0.0.1:8089";
+
+fn new_client() -> Client {
+    Client::builder()
+        .cookie_provider(Arc::new(CookieStoreMutex::default()))
+        .redirect(redirect::Policy::none())
+        .build()
+        .unwrap()
+}
+
+async fn start_provider() -> MockServer {
+    let listener = std::net::TcpListener::bind("127.0.0.1:8089").unwrap();
+    let server = MockServer::builder().listener(listener).start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/.well-known/openid-configuration"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "issuer": ISSUER,
+            "authorization_endpoint": format!("{}/authorize", ISSUER),
+            "token_endpoint": format!("{}/token", ISSUER),
+            "userinfo_endpoint": format!("{}/userinfo", ISSUER),
+        })))
+        .mount(&server)
+        .await;
+
+    server
+}
+
+/// Le fournisseur échange `code` contre un access token qui décrit `userinfo`.
+async fn mock_user(server: &MockServer, code: &str, userinfo: serde_json::Value) {
+    let access_token = format!("at-{}", code);
+    Mock::given(method("POST"))
+        .and(path("/token"))
+        .and(body_string_contains(format!("code={}&", code)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "access_token": access_token,
+            "token_type": "Bearer",
+        })))
+        .mount(server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/userinfo"))
+        .and(header("authorization", format!("Bearer {}", access_token)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(userinfo))
+        .mount(server)
+        .await;
+}
+
+/// Démarre le flow et renvoie les paramètres de l'URL d'autorisation.
+async fn authorize(client: &Client) -> HashMap<String, String> {
+    let res = client
+        .get("http://localhost:8000/auth/oauth/mock/start?redirect_to=/dashboard")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::SEE_OTHER);
+    let location = Url::parse(res.headers()["location"].to_str().unwrap()).unwrap();
+    assert!(location.as_str().starts_with(&format!("{}/authorize", ISSUER)));
+    location.query_pairs().into_owned().collect()
+}
+
+async fn callback(client: &Client, code: &str, state: &str) -> reqwest::Response {
+    client
+        .get(format!(
+            "http://localhost:8000/auth/oauth/mock/callback?code={}&state={}",
+            code, state
+        ))
+        .send()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn oidc_sign_up_sign_in_and_account_linking() {
+    let server = start_provider().await;
+
+    let res = new_client()
+        .get("http://localhost:8000/auth/oauth/unknown/start")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+    // 🌐 SIGN UP via le fournisseur
+    let email = format!("{}@example.com", uuid::Uuid::new_v4());
+    let subject = uuid::Uuid::new_v4().to_string();
+    mock_user(
+        &server,
+        &subject,
+        json!({ "sub": subject, "email": email, "email_verified": true, "name": "Ada" }),
+    )
+    .await;
+
+    let client = new_client();
+    let params = authorize(&client).await;
+    assert_eq!(params["client_id"], "saas-client");
+    assert_eq!(params["code_challenge_method"], "S256");
+    assert_eq!(
+        params["redirect_uri"],
+        "http://localhost:8000/auth/oauth/mock/callback"
+    );
+
+    // Le state est lié au navigateur qui a démarré le flow
+    let res = callback(&new_client(), &subject, &params["state"]).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    let res = callback(&client, &subject, &params["state"]).await;
+    assert_eq!(res.status(), StatusCode::SEE_OTHER);
+    assert_eq!(res.headers()["location"], "http://localhost:3000/dashboard");
+
+    let res = client.get("http://localhost:8000/me").send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let me: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(me["email"], email.as_str());
+    assert_eq!(me["name"], "Ada");
+    assert!(me["email_verified_at"].is_string());
+
+    // Le state est à usage unique
+    let res = callback(&client, &subject, &params["state"]).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    // 🔐 PKCE : le verifier envoyé correspond au challenge
+    let requests = server.received_requests().await.unwrap();
+    let token_request = requests
+        .iter()
+        .find(|r| {
+            r.url.path() == "/token"
+                && String::from_utf8_lossy(&r.body).contains(&format!("code={}&", subject))
+        })
+        .unwrap();
+    let form: HashMap<String, String> =
+        Url::parse(&format!("http://x/?{}", String::from_utf8_lossy(&token_request.body)))
+            .unwrap()
+            .query_pairs()
+            .into_owned()
+            .collect();
+    assert_eq!(hash_token(&form["code_verifier"]), params["code_challenge"]);
+
+    // Une nouvelle connexion retrouve le même compte
+    let device = new_client();
+    let params = authorize(&device).await;
+    callback(&device, &subject, &params["state"]).await;
+    let res = device.get("http://localhost:8000/me").send().await.unwrap();
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["id"], me["id"]);
+
+    let res = device
+        .get("http://localhost:8000/me/identities")
+        .send()
+        .await
+        .unwrap();
+    let identities: Vec<serde_json::Value> = res.json().await.unwrap();
+    assert_eq!(identities.len(), 1);
+    assert_eq!(identities[0]["provider"], "mock");
+
+    // 🔗 LIAISON avec un compte existant dont l'email est vérifié
+    let existing = new_client();
+    let email = format!("{}@example.com", uuid::Uuid::new_v4());
+    let res = existing
+        .post("http://localhost:8000/auth/register")
+        .json(&json!({
+            "email": email,
+            "password": "password123"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let registered: serde_json::Value = res.json().await.unwrap();
+
+    // Tant que le fournisseur ne garantit pas l'email, pas de liaison
+    let unverified = uuid::Uuid::new_v4().to_string();
+    mock_user(
+        &server,
+        &unverified,
+        json!({ "sub": unverified, "email": email, "email_verified": false }),
+    )
+    .await;
+    let client = new_client();
+    let params = authorize(&client).await;
+    let res = callback(&client, &unverified, &params["state"]).await;
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+
+    let token = common::token_from(&common::latest_email(&email));
+    let res = existing
+        .post("http://localhost:8000/auth/verify-email")
+        .json(&json!({ "token": token }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let verified = uuid::Uuid::new_v4().to_string();
+    mock_user(
+        &server,
+        &verified,
+        json!({ "sub": verified, "email": email, "email_verified": true }),
+    )
+    .await;
+    let client = new_client();
+    let params = authorize(&client).await;
+    let res = callback(&client, &verified, &params["state"]).await;
+    assert_eq!(res.status(), StatusCode::SEE_OTHER);
+
+    let res = client.get("http://localhost:8000/me").send().await.unwrap();
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["id"], registered["user"]["id"]);
+}
@@ -0,0 +1,173 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use reqwest::{Client, StatusCode};
+use serde_json::json;
+
+const API: &str = "http://localhost:8000";
+
+/// Nouveau compte en mode bearer, renvoie le corps de la réponse avec les tokens.
+async fn register(client: &Client) -> serde_json::Value {
+    let res = client
+        .post(format!("{}/auth/register", API))
+        .header("X-Token-Delivery", "body")
+        .json(&json!({
+            "email": format!("{}@example.com", uuid::Uuid::new_v4()),
+            "password": "password123"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+    res.json().await.unwrap()
+}
+
+/// Claims d'un access token, sans vérifier la signature.
+fn claims(token: &serde_json::Value) -> serde_json::Value {
+    let payload = token.as_str().unwrap().split('.').nth(1).unwrap();
+    serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload).unwrap()).unwrap()
+}
+
+async fn refresh(client: &Client, refresh_token: &serde_json::Value) -> serde_json::Value {
+    let res = client
+        .post(format!("{}/auth/refresh", API))
+        .header("X-Token-Delivery", "body")
+        .json(&json!({ "refresh_token": refresh_token }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    res.json().await.unwrap()
+}
+
+#[tokio::test]
+async fn register_creates_a_personal_organization() {
+    let client = Client::new();
+    let session = register(&client).await;
+    let token = session["access_token"].as_str().unwrap();
+
+    let res = client
+        .get(format!("{}/orgs", API))
+        .bearer_auth(token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let orgs: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(orgs.as_array().unwrap().len(), 1);
+    assert_eq!(orgs[0]["role"], "owner");
+    assert_eq!(orgs[0]["personal_user_id"], session["user"]["id"]);
+
+    // L'organisation personnelle est active dès l'inscription
+    let claims = claims(&session["access_token"]);
+    assert_eq!(claims["org"], orgs[0]["id"]);
+    assert_eq!(claims["roles"], json!(["owner"]));
+
+    // ⛔ Elle ne peut pas être supprimée
+    let res = client
+        .delete(format!("{}/orgs/{}", API, orgs[0]["id"].as_str().unwrap()))
+        .bearer_auth(token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn active_organization_follows_the_session() {
+    let client = Client::new();
+    let session = register(&client).await;
+    let token = session["access_token"].as_str().unwrap();
+    let personal = claims(&session["access_token"])["org"].clone();
+
+    let res = client
+        .post(format!("{}/orgs", API))
+        .bearer_auth(token)
+        .json(&json!({ "name": "Acme" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let org: serde_json::Value = res.json().await.unwrap();
+    let org_url = format!("{}/orgs/{}", API, org["id"].as_str().unwrap());
+
+    let res = client
+        .patch(&org_url)
+        .bearer_auth(token)
+        .json(&json!({ "name": "Acme Inc" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let res = client.get(&org_url).bearer_auth(token).send().await.unwrap();
+    let fetched: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(fetched["name"], "Acme Inc");
+    assert_eq!(fetched["role"], "owner");
+
+    // 🔀 SWITCH
+    let res = client
+        .post(format!("{}/switch", org_url))
+        .header("X-Token-Delivery", "body")
+        .bearer_auth(token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let switched: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(claims(&switched["access_token"])["org"], org["id"]);
+
+    // Le choix survit au refresh
+    let tokens = refresh(&client, &session["refresh_token"]).await;
+    assert_eq!(claims(&tokens["access_token"])["org"], org["id"]);
+
+    // 🗑️ Une fois supprimée, la session revient à l'organisation personnelle
+    let res = client.delete(&org_url).bearer_auth(token).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let tokens = refresh(&client, &tokens["refresh_token"]).await;
+    assert_eq!(claims(&tokens["access_token"])["org"], personal);
+}
+
+#[tokio::test]
+async fn organizations_are_private_to_their_members() {
+    let client = Client::new();
+    let owner = register(&client).await;
+    let owner_token = owner["access_token"].as_str().unwrap();
+    let stranger = register(&client).await;
+    let org_id = claims(&owner["access_token"])["org"].clone();
+    let org_url = format!("{}/orgs/{}", API, org_id.as_str().unwrap());
+
+    for res in [
+        client.get(&org_url),
+        client.post(format!("{}/switch", org_url)),
+        client.get(format!("{}/members", org_url)),
+    ] {
+        let res = res
+            .bearer_auth(stranger["access_token"].as_str().unwrap())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    let res = client
+        .get(format!("{}/members", org_url))
+        .bearer_auth(owner_token)
+        .send()
+        .await
+        .unwrap();
+    let members: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(members.as_array().unwrap().len(), 1);
+    assert_eq!(members[0]["user_id"], owner["user"]["id"]);
+
+    // ⛔ Le dernier propriétaire ne peut ni se rétrograder ni partir
+    let member_url = format!("{}/members/{}", org_url, owner["user"]["id"].as_str().unwrap());
+    let res = client
+        .patch(&member_url)
+        .bearer_auth(owner_token)
+        .json(&json!({ "role": "member" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let res = client.delete(&member_url).bearer_auth(owner_token).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,118 @@
+mod common;
+
+use reqwest::{Client, StatusCode};
+use reqwest_cookie_store::CookieStoreMutex;
+use serde_json::json;
+use std::sync::Arc;
+
+#[tokio::test]
+async fn reset_password_with_emailed_token() {
+    let cookie_store = Arc::new(CookieStoreMutex::default());
+    let client = Client::builder()
+        .cookie_provider(cookie_store.clone())
+        .build()
+        .unwrap();
+    let email = format!("{}@example.com", uuid::Uuid::new_v4());
+
+    let res = client
+        .post("http://localhost:8000/auth/register")
+        .json(&json!({
+            "email": email,
+            "password": "password123"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let client = common::with_csrf(cookie_store);
+
+    let res = client
+        .post("http://localhost:8000/auth/password/forgot")
+        .json(&json!({ "email": email }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::ACCEPTED);
+
+    let email_sent = common::latest_email(&email);
+    assert_eq!(email_sent["subject"], "Reset your password");
+    let token = common::token_from(&email_sent);
+
+    let res = client
+        .post("http://localhost:8000/auth/password/reset")
+        .json(&json!({ "token": token, "password": "new-password456" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    // Les sessions existantes sont révoquées
+    let res = client
+        .post("http://localhost:8000/auth/refresh")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    let res = client
+        .post("http://localhost:8000/auth/login")
+        .json(&json!({
+            "email": email,
+            "password": "password123"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    let res = client
+        .post("http://localhost:8000/auth/login")
+        .json(&json!({
+            "email": email,
+            "password": "new-password456"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let res = client
+        .post("http://localhost:8000/auth/password/reset")
+        .json(&json!({ "token": token, "password": "another-password789" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn forgot_password_for_unknown_email_is_accepted() {
+    let res = Client::new()
+        .post("http://localhost:8000/auth/password/forgot")
+        .json(&json!({ "email": "nobody@example.com" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::ACCEPTED);
+}
+
+#[tokio::test]
+async fn forgot_password_is_accepted_when_the_email_cannot_be_sent() {
+    // 📭 Adresse que le mailer refuse, insérée directement en base
+    let email = format!("pas une adresse {}", uuid::Uuid::new_v4());
+    let pool = common::app_pool().await;
+    sqlx::query("INSERT INTO users (email, password_hash) VALUES ($1, 'x')")
+        .bind(&email)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    // 🤫 Même réponse que pour un compte inconnu
+    let res = Client::new()
+        .post("http://localhost:8000/auth/password/forgot")
+        .json(&json!({ "email": email }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::ACCEPTED);
+}
@@ -0,0 +1,154 @@
+use reqwest::{Client, StatusCode};
+use serde_json::json;
+
+// Session en mode bearer pour créer les tokens, puis appels avec le token
+// personnel comme le ferait un script ou une intégration.
+
+async fn register(client: &Client) -> String {
+    let res = client
+        .post("http://localhost:8000/auth/register")
+        .header("X-Token-Delivery", "body")
+        .json(&json!({
+            "email": format!("{}@example.com", uuid::Uuid::new_v4()),
+            "password": "password123"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let body: serde_json::Value = res.json().await.unwrap();
+    body["access_token"].as_str().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn personal_token_lifecycle() {
+    let client = Client::new();
+    let session = register(&client).await;
+
+    // 🔑 CRÉATION
+    let res = client
+        .post("http://localhost:8000/me/tokens")
+        .bearer_auth(&session)
+        .json(&json!({ "name": "ci", "scopes": ["profile:read"] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let created: serde_json::Value = res.json().await.unwrap();
+    let token = created["token"].as_str().unwrap().to_string();
+    assert!(token.starts_with("pat_"));
+    assert!(token.starts_with(created["token_prefix"].as_str().unwrap()));
+
+    // Le token n'est plus jamais affiché
+    let res = client
+        .get("http://localhost:8000/me/tokens")
+        .bearer_auth(&session)
+        .send()
+        .await
+        .unwrap();
+    let tokens: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(tokens.as_array().unwrap().len(), 1);
+    assert!(tokens[0].get("token").is_none());
+    assert!(tokens[0].get("token_hash").is_none());
+    assert!(tokens[0]["last_used_at"].is_null());
+
+    let res = client
+        .get("http://localhost:8000/me")
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let res = client
+        .get("http://localhost:8000/me/tokens")
+        .bearer_auth(&session)
+        .send()
+        .await
+        .unwrap();
+    let tokens: serde_json::Value = res.json().await.unwrap();
+    assert!(!tokens[0]["last_used_at"].is_null());
+
+    // ⛔ Hors des scopes du token
+    let res = client
+        .patch("http://localhost:8000/me")
+        .bearer_auth(&token)
+        .json(&json!({ "name": "Script" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+    // ⛔ Actions sensibles réservées aux sessions
+    for (path, body) in [
+        ("/me/password", json!({ "current_password": "password123", "new_password": "password456" })),
+        ("/me/tokens", json!({ "name": "escalade", "scopes": ["profile:write"] })),
+    ] {
+        let res = client
+            .post(format!("http://localhost:8000{}", path))
+            .bearer_auth(&token)
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::FORBIDDEN, "{}", path);
+    }
+
+    // ⛔ Comme toute route qui ne demande pas de scope
+    for path in ["/me/tokens", "/orgs", "/auth/sessions"] {
+        let res = client
+            .get(format!("http://localhost:8000{}", path))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::FORBIDDEN, "{}", path);
+    }
+
+    // 🗑️ RÉVOCATION
+    let res = client
+        .delete(format!("http://localhost:8000/me/tokens/{}", created["id"].as_str().unwrap()))
+        .bearer_auth(&session)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let res = client
+        .get("http://localhost:8000/me")
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn invalid_token_requests_are_rejected() {
+    let client = Client::new();
+    let session = register(&client).await;
+
+    for body in [
+        json!({ "name": "", "scopes": ["profile:read"] }),
+        json!({ "name": "ci", "scopes": [] }),
+        json!({ "name": "ci", "scopes": ["admin"] }),
+        json!({ "name": "ci", "scopes": ["profile:read"], "expires_at": "2020-01-01T00:00:00Z" }),
+    ] {
+        let res = client
+            .post("http://localhost:8000/me/tokens")
+            .bearer_auth(&session)
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{}", body);
+    }
+
+    let res = client
+        .get("http://localhost:8000/me")
+        .bearer_auth("pat_unknown")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,186 @@
+mod common;
+
+use reqwest::{Client, StatusCode};
+use serde_json::json;
+
+use common::{latest_email, token_from};
+
+const API: &str = "http://localhost:8000";
+
+fn new_email() -> String {
+    format!("{}@example.com", uuid::Uuid::new_v4())
+}
+
+async fn sign_up(client: &Client, email: &str, invitation: Option<&str>) -> String {
+    let res = client
+        .post(format!("{}/auth/register", API))
+        .header("X-Token-Delivery", "body")
+        .json(&json!({
+            "email": email,
+            "password": "password123",
+            "invitation_token": invitation
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let body: serde_json::Value = res.json().await.unwrap();
+    body["access_token"].as_str().unwrap().to_string()
+}
+
+async fn invite(client: &Client, token: &str, org_id: &str, email: &str, role: &str) -> StatusCode {
+    client
+        .post(format!("{}/orgs/{}/invitations", API, org_id))
+        .bearer_auth(token)
+        .json(&json!({ "email": email, "role": role }))
+        .send()
+        .await
+        .unwrap()
+        .status()
+}
+
+/// Organisation avec un propriétaire et un membre, renvoie leurs tokens.
+async fn org_with_member(client: &Client, role: &str) -> (String, String, String, String) {
+    let owner = sign_up(client, &new_email(), None).await;
+    let res = client
+        .post(format!("{}/orgs", API))
+        .bearer_auth(&owner)
+        .json(&json!({ "name": "Acme" }))
+        .send()
+        .await
+        .unwrap();
+    let org: serde_json::Value = res.json().await.unwrap();
+    let org_id = org["id"].as_str().unwrap().to_string();
+
+    let email = new_email();
+    assert_eq!(invite(client, &owner, &org_id, &email, role).await, StatusCode::CREATED);
+    let member = sign_up(client, &email, Some(&token_from(&latest_email(&email)))).await;
+    let me: serde_json::Value = client
+        .get(format!("{}/me", API))
+        .bearer_auth(&member)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    (owner, org_id, member, me["id"].as_str().unwrap().to_string())
+}
+
+async fn create_role(
+    client: &Client,
+    token: &str,
+    org_id: &str,
+    permissions: serde_json::Value,
+) -> reqwest::Response {
+    client
+        .post(format!("{}/orgs/{}/roles", API, org_id))
+        .bearer_auth(token)
+        .json(&json!({ "name": format!("role-{}", uuid::Uuid::new_v4()), "permissions": permissions }))
+        .send()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn built_in_roles_grant_their_permissions() {
+    let client = Client::new();
+    let (owner, org_id, member, member_id) = org_with_member(&client, "member").await;
+
+    // ✅ Un membre voit les autres membres
+    let res = client
+        .get(format!("{}/orgs/{}/members", API, org_id))
+        .bearer_auth(&member)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    // ⛔ Mais n'invite pas, et l'erreur nomme la permission manquante
+    let res = client
+        .post(format!("{}/orgs/{}/invitations", API, org_id))
+        .bearer_auth(&member)
+        .json(&json!({ "email": new_email(), "role": "member" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["error"], "Missing permission: members:invite");
+
+    // ⛔ Un admin ne peut pas supprimer l'organisation
+    let member_url = format!("{}/orgs/{}/members/{}", API, org_id, member_id);
+    let res = client
+        .patch(&member_url)
+        .bearer_auth(&owner)
+        .json(&json!({ "role": "admin" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let res = client
+        .delete(format!("{}/orgs/{}", API, org_id))
+        .bearer_auth(&member)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+    // ⛔ Ni créer un rôle avec une permission qu'il n'a pas
+    let res = create_role(&client, &member, &org_id, json!(["billing:write"])).await;
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    let res = create_role(&client, &member, &org_id, json!(["billing:read"])).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+}
+
+#[tokio::test]
+async fn custom_roles_add_permissions() {
+    let client = Client::new();
+    let (owner, org_id, member, member_id) = org_with_member(&client, "member").await;
+
+    let res = create_role(&client, &owner, &org_id, json!(["unknown:permission"])).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let res = create_role(&client, &owner, &org_id, json!(["members:invite", "members:invite"])).await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let role: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(role["permissions"], json!(["members:invite"]));
+    let role_id = role["id"].as_str().unwrap();
+
+    // 🎭 Le rôle personnalisé s'ajoute au rôle de base
+    let member_url = format!("{}/orgs/{}/members/{}", API, org_id, member_id);
+    let res = client
+        .patch(&member_url)
+        .bearer_auth(&owner)
+        .json(&json!({ "custom_role_id": role_id }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let updated: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(updated["role"], "member");
+    assert_eq!(updated["custom_role_id"], role_id);
+    assert_eq!(invite(&client, &member, &org_id, &new_email(), "member").await, StatusCode::CREATED);
+
+    // ⛔ Pas assez pour inviter un admin
+    assert_eq!(invite(&client, &member, &org_id, &new_email(), "admin").await, StatusCode::FORBIDDEN);
+
+    // 🗑️ Supprimer le rôle retire ses permissions
+    let res = client
+        .delete(format!("{}/orgs/{}/roles/{}", API, org_id, role_id))
+        .bearer_auth(&owner)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(invite(&client, &member, &org_id, &new_email(), "member").await, StatusCode::FORBIDDEN);
+
+    let res = client
+        .get(format!("{}/orgs/{}/roles", API, org_id))
+        .bearer_auth(&member)
+        .send()
+        .await
+        .unwrap();
+    let roles: serde_json::Value = res.json().await.unwrap();
+    assert!(roles.as_array().unwrap().is_empty());
+}
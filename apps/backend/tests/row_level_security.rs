@@ -0,0 +1,87 @@
+mod common;
+
+use reqwest::{Client, StatusCode};
+use serde_json::json;
+use uuid::Uuid;
+
+use backend::db;
+
+// Isolation des organisations assurée par Postgres : les requêtes ci-dessous
+// ne filtrent volontairement pas sur l'organisation.
+
+const API: &str = "http://localhost:8000";
+
+/// Nouvel utilisateur et l'organisation qu'il crée, renvoie son id.
+async fn create_org(client: &Client) -> Uuid {
+    let res = client
+        .post(format!("{}/auth/register", API))
+        .header("X-Token-Delivery", "body")
+        .json(&json!({
+            "email": format!("{}@example.com", Uuid::new_v4()),
+            "password": "password123"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let body: serde_json::Value = res.json().await.unwrap();
+    let token = body["access_token"].as_str().unwrap();
+
+    let res = client
+        .post(format!("{}/orgs", API))
+        .bearer_auth(token)
+        .json(&json!({ "name": "Acme" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let org: serde_json::Value = res.json().await.unwrap();
+    org["id"].as_str().unwrap().parse().unwrap()
+}
+
+#[tokio::test]
+async fn tenant_transactions_only_see_their_organization() {
+    let client = Client::new();
+    let org_a = create_org(&client).await;
+    let org_b = create_org(&client).await;
+    let pool = common::app_pool().await;
+
+    // 🏢 Dans la transaction de A, B n'existe pas
+    let mut tx = db::begin_for_org(&pool, org_a).await.unwrap();
+    let orgs: Vec<Uuid> = sqlx::query_scalar("SELECT organization_id FROM memberships")
+        .fetch_all(&mut *tx)
+        .await
+        .unwrap();
+    assert_eq!(orgs, vec![org_a]);
+    let orgs: Vec<Uuid> = sqlx::query_scalar("SELECT id FROM organizations")
+        .fetch_all(&mut *tx)
+        .await
+        .unwrap();
+    assert_eq!(orgs, vec![org_a]);
+
+    // ⛔ Et on ne peut pas y écrire
+    let res = sqlx::query("INSERT INTO service_accounts (organization_id, name) VALUES ($1, 'intrus')")
+        .bind(org_b)
+        .execute(&mut *tx)
+        .await;
+    assert!(res.is_err());
+    tx.rollback().await.unwrap();
+
+    // 🔒 Sans organisation, aucune ligne n'est visible
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM memberships")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(count, 0);
+
+    // 🌐 Sauf à le demander explicitement
+    let mut tx = db::begin_cross_tenant(&pool).await.unwrap();
+    let count: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM memberships WHERE organization_id = ANY($1)",
+    )
+    .bind(vec![org_a, org_b])
+    .fetch_one(&mut *tx)
+    .await
+    .unwrap();
+    assert_eq!(count, 2);
+}
@@ -0,0 +1,316 @@
+use reqwest::{Client, StatusCode};
+use serde_json::json;
+
+// Comptes de service d'une organisation et leurs clés d'API, utilisées en
+// bearer comme le ferait une intégration serveur à serveur.
+
+const API: &str = "http://localhost:8000";
+
+async fn register(client: &Client) -> String {
+    let res = client
+        .post(format!("{}/auth/register", API))
+        .header("X-Token-Delivery", "body")
+        .json(&json!({
+            "email": format!("{}@example.com", uuid::Uuid::new_v4()),
+            "password": "password123"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let body: serde_json::Value = res.json().await.unwrap();
+    body["access_token"].as_str().unwrap().to_string()
+}
+
+/// Organisation créée par un nouvel utilisateur, avec un compte de service.
+/// Renvoie (session, org_id, service_account_id).
+async fn org_with_service_account(client: &Client) -> (String, String, String) {
+    let session = register(client).await;
+    let res = client
+        .post(format!("{}/orgs", API))
+        .bearer_auth(&session)
+        .json(&json!({ "name": "Acme" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let org: serde_json::Value = res.json().await.unwrap();
+    let org_id = org["id"].as_str().unwrap().to_string();
+
+    let res = client
+        .post(format!("{}/orgs/{}/service-accounts", API, org_id))
+        .bearer_auth(&session)
+        .json(&json!({ "name": "billing-sync" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let account: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(account["organization_id"], org_id.as_str());
+
+    (session, org_id, account["id"].as_str().unwrap().to_string())
+}
+
+async fn create_key(
+    client: &Client,
+    credential: &str,
+    org_id: &str,
+    account_id: &str,
+    body: serde_json::Value,
+) -> reqwest::Response {
+    client
+        .post(format!("{}/orgs/{}/service-accounts/{}/keys", API, org_id, account_id))
+        .bearer_auth(credential)
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn list_accounts(client: &Client, credential: &str, org_id: &str) -> StatusCode {
+    client
+        .get(format!("{}/orgs/{}/service-accounts", API, org_id))
+        .bearer_auth(credential)
+        .send()
+        .await
+        .unwrap()
+        .status()
+}
+
+#[tokio::test]
+async fn api_keys_authenticate_service_accounts() {
+    let client = Client::new();
+    let (session, org_id, account_id) = org_with_service_account(&client).await;
+
+    let res = create_key(
+        &client,
+        &session,
+        &org_id,
+        &account_id,
+        json!({ "name": "lecture", "scopes": ["service_accounts:read"] }),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let created: serde_json::Value = res.json().await.unwrap();
+    let key = created["key"].as_str().unwrap().to_string();
+    assert!(key.starts_with("sk_"));
+
+    // 🤖 Le compte de service lit son organisation
+    assert_eq!(list_accounts(&client, &key, &org_id).await, StatusCode::OK);
+
+    // ⛔ Mais ne peut pas écrire, ni se donner plus de droits
+    let res = client
+        .post(format!("{}/orgs/{}/service-accounts", API, org_id))
+        .bearer_auth(&key)
+        .json(&json!({ "name": "autre" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+    // ⛔ Ni accéder à une autre organisation
+    let (_, other_org, _) = org_with_service_account(&client).await;
+    assert_eq!(list_accounts(&client, &key, &other_org).await, StatusCode::NOT_FOUND);
+
+    // ⛔ Ni aux routes réservées aux utilisateurs
+    let res = client
+        .get(format!("{}/me", API))
+        .bearer_auth(&key)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    // Les clés ne sont plus jamais affichées
+    let res = client
+        .get(format!("{}/orgs/{}/service-accounts/{}/keys", API, org_id, account_id))
+        .bearer_auth(&session)
+        .send()
+        .await
+        .unwrap();
+    let keys: serde_json::Value = res.json().await.unwrap();
+    assert!(keys[0].get("key").is_none());
+    assert!(keys[0].get("key_hash").is_none());
+    assert!(!keys[0]["last_used_at"].is_null());
+
+    // 🗑️ RÉVOCATION
+    let res = client
+        .delete(format!(
+            "{}/orgs/{}/service-accounts/{}/keys/{}",
+            API,
+            org_id,
+            account_id,
+            created["id"].as_str().unwrap()
+        ))
+        .bearer_auth(&session)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(list_accounts(&client, &key, &org_id).await, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn api_keys_are_limited_to_their_networks() {
+    let client = Client::new();
+    let (session, org_id, account_id) = org_with_service_account(&client).await;
+
+    let mut keys = Vec::new();
+    for networks in [json!(["10.0.0.0/8"]), json!(["127.0.0.1", "::1/128"])] {
+        let res = create_key(
+            &client,
+            &session,
+            &org_id,
+            &account_id,
+            json!({ "name": "ci", "scopes": ["service_accounts:read"], "allowed_ips": networks }),
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::CREATED);
+        let created: serde_json::Value = res.json().await.unwrap();
+        keys.push(created["key"].as_str().unwrap().to_string());
+    }
+
+    assert_eq!(list_accounts(&client, &keys[0], &org_id).await, StatusCode::FORBIDDEN);
+    assert_eq!(list_accounts(&client, &keys[1], &org_id).await, StatusCode::OK);
+
+    for body in [
+        json!({ "name": "ci", "scopes": ["service_accounts:read"], "allowed_ips": ["10.0.0.0/33"] }),
+        json!({ "name": "ci", "scopes": ["service_accounts:read"], "allowed_ips": ["localhost"] }),
+        json!({ "name": "ci", "scopes": ["admin"] }),
+    ] {
+        let res = create_key(&client, &session, &org_id, &account_id, body.clone()).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{}", body);
+    }
+}
+
+#[tokio::test]
+async fn rotated_keys_overlap() {
+    let client = Client::new();
+    let (session, org_id, account_id) = org_with_service_account(&client).await;
+
+    let res = create_key(
+        &client,
+        &session,
+        &org_id,
+        &account_id,
+        json!({ "name": "deploy", "scopes": ["service_accounts:read", "service_accounts:write"] }),
+    )
+    .await;
+    let first: serde_json::Value = res.json().await.unwrap();
+    let rotate = |key_id: &str, credential: &str, body: serde_json::Value| {
+        client
+            .post(format!(
+                "{}/orgs/{}/service-accounts/{}/keys/{}/rotate",
+                API, org_id, account_id, key_id
+            ))
+            .bearer_auth(credential.to_string())
+            .json(&body)
+            .send()
+    };
+
+    // 🔄 Le compte de service fait tourner sa propre clé
+    let first_key = first["key"].as_str().unwrap();
+    let res = rotate(first["id"].as_str().unwrap(), first_key, json!({ "overlap": "1h" }))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let second: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(second["rotated_from"], first["id"]);
+    assert_eq!(second["scopes"], first["scopes"]);
+
+    // Les deux clés fonctionnent pendant le chevauchement
+    let second_key = second["key"].as_str().unwrap();
+    assert_eq!(list_accounts(&client, first_key, &org_id).await, StatusCode::OK);
+    assert_eq!(list_accounts(&client, second_key, &org_id).await, StatusCode::OK);
+
+    // Sans chevauchement, l'ancienne clé cesse immédiatement de fonctionner
+    let res = rotate(second["id"].as_str().unwrap(), &session, json!({ "overlap": "0s" }))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let third: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(list_accounts(&client, second_key, &org_id).await, StatusCode::UNAUTHORIZED);
+    assert_eq!(
+        list_accounts(&client, third["key"].as_str().unwrap(), &org_id).await,
+        StatusCode::OK
+    );
+
+    let res = rotate(third["id"].as_str().unwrap(), &session, json!({ "overlap": "90d" }))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn rotation_keeps_scopes_within_reach() {
+    let client = Client::new();
+    let (session, org_id, account_id) = org_with_service_account(&client).await;
+
+    let res = create_key(
+        &client,
+        &session,
+        &org_id,
+        &account_id,
+        json!({ "name": "owner", "scopes": ["org:delete"] }),
+    )
+    .await;
+    let owner_key: serde_json::Value = res.json().await.unwrap();
+    let res = create_key(
+        &client,
+        &session,
+        &org_id,
+        &account_id,
+        json!({ "name": "rotator", "scopes": ["service_accounts:write"] }),
+    )
+    .await;
+    let rotator: serde_json::Value = res.json().await.unwrap();
+
+    // ⛔ Une clé restreinte ne récupère pas les permissions d'une autre en la faisant tourner
+    let res = client
+        .post(format!(
+            "{}/orgs/{}/service-accounts/{}/keys/{}/rotate",
+            API,
+            org_id,
+            account_id,
+            owner_key["id"].as_str().unwrap()
+        ))
+        .bearer_auth(rotator["key"].as_str().unwrap())
+        .json(&json!({}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["error"], "Missing permission: org:delete");
+
+    // La clé visée n'a pas été raccourcie
+    let res = client
+        .get(format!("{}/orgs/{}/service-accounts/{}/keys", API, org_id, account_id))
+        .bearer_auth(&session)
+        .send()
+        .await
+        .unwrap();
+    let keys: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(keys.as_array().unwrap().len(), 2);
+    assert!(keys[0]["expires_at"].is_null());
+}
+
+#[tokio::test]
+async fn personal_tokens_need_org_scopes() {
+    let client = Client::new();
+    let (session, org_id, _) = org_with_service_account(&client).await;
+
+    let res = client
+        .post(format!("{}/me/tokens", API))
+        .bearer_auth(&session)
+        .json(&json!({ "name": "script", "scopes": ["profile:read"] }))
+        .send()
+        .await
+        .unwrap();
+    let token: serde_json::Value = res.json().await.unwrap();
+
+    assert_eq!(
+        list_accounts(&client, token["token"].as_str().unwrap(), &org_id).await,
+        StatusCode::FORBIDDEN
+    );
+}
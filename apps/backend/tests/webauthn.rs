@@ -0,0 +1,164 @@
+mod common;
+
+use reqwest::{Client, StatusCode};
+use reqwest_cookie_store::CookieStoreMutex;
+use serde_json::json;
+use std::sync::Arc;
+use webauthn_authenticator_rs::prelude::{
+    CreationChallengeResponse, RequestChallengeResponse, Url, WebauthnAuthenticator,
+};
+use webauthn_authenticator_rs::softpasskey::SoftPasskey;
+
+fn new_client() -> Client {
+    Client::builder()
+        .cookie_provider(Arc::new(CookieStoreMutex::default()))
+        .build()
+        .unwrap()
+}
+
+fn origin() -> Url {
+    Url::parse("http://localhost:3000").unwrap()
+}
+
+#[tokio::test]
+async fn passkey_registration_login_and_second_factor() {
+    let cookie_store = Arc::new(CookieStoreMutex::default());
+    let client = Client::builder()
+        .cookie_provider(cookie_store.clone())
+        .build()
+        .unwrap();
+    let email = format!("{}@example.com", uuid::Uuid::new_v4());
+    let mut authenticator = WebauthnAuthenticator::new(SoftPasskey::new(true));
+
+    let res = client
+        .post("http://localhost:8000/auth/register")
+        .json(&json!({
+            "email": email,
+            "password": "password123"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+    let client = common::with_csrf(cookie_store);
+
+    // 🔑 ENREGISTREMENT
+    let res = client
+        .post("http://localhost:8000/me/webauthn/register/start")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let options: CreationChallengeResponse = res.json().await.unwrap();
+    let credential = authenticator.do_registration(origin(), options).unwrap();
+
+    let res = client
+        .post("http://localhost:8000/me/webauthn/register/finish")
+        .json(&json!({ "name": "Laptop", "credential": credential }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::CREATED);
+
+    let res = client
+        .get("http://localhost:8000/me/webauthn/credentials")
+        .send()
+        .await
+        .unwrap();
+    let credentials: Vec<serde_json::Value> = res.json().await.unwrap();
+    assert_eq!(credentials.len(), 1);
+    assert_eq!(credentials[0]["name"], "Laptop");
+    let credential_id = credentials[0]["id"].as_str().unwrap().to_string();
+
+    // 🔑 LOGIN sans mot de passe
+    let device = new_client();
+    let res = device
+        .post("http://localhost:8000/auth/webauthn/login/start")
+        .json(&json!({ "email": email }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: serde_json::Value = res.json().await.unwrap();
+    let options: RequestChallengeResponse =
+        serde_json::from_value(body["options"].clone()).unwrap();
+    let assertion = authenticator.do_authentication(origin(), options).unwrap();
+
+    let res = device
+        .post("http://localhost:8000/auth/webauthn/login/finish")
+        .json(&json!({ "challenge_id": body["challenge_id"], "credential": assertion }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    // Le challenge ne peut servir qu'une fois
+    let res = device
+        .post("http://localhost:8000/auth/webauthn/login/finish")
+        .json(&json!({ "challenge_id": body["challenge_id"], "credential": assertion }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    let res = device.get("http://localhost:8000/me").send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    // 🔑 SECOND FACTEUR après le mot de passe
+    let device = new_client();
+    let res = device
+        .post("http://localhost:8000/auth/login")
+        .json(&json!({
+            "email": email,
+            "password": "password123"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(body["mfa_required"], true);
+    assert_eq!(body["methods"], json!(["webauthn"]));
+    let mfa_token = body["mfa_token"].as_str().unwrap().to_string();
+
+    let res = device
+        .post("http://localhost:8000/auth/mfa/webauthn/start")
+        .json(&json!({ "mfa_token": mfa_token }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let options: RequestChallengeResponse = res.json().await.unwrap();
+    let assertion = authenticator.do_authentication(origin(), options).unwrap();
+
+    let res = device
+        .post("http://localhost:8000/auth/mfa/verify")
+        .json(&json!({ "mfa_token": mfa_token, "credential": assertion }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    // 🗑️ SUPPRESSION : le mot de passe suffit de nouveau
+    let res = client
+        .delete(format!(
+            "http://localhost:8000/me/webauthn/credentials/{}",
+            credential_id
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let res = new_client()
+        .post("http://localhost:8000/auth/login")
+        .json(&json!({
+            "email": email,
+            "password": "password123"
+        }))
+        .send()
+        .await
+        .unwrap();
+    let body: serde_json::Value = res.json().await.unwrap();
+    assert!(body.get("mfa_required").is_none());
+}
@@ -0,0 +1,4 @@
+-- Exécuté à la création du volume. Les migrations donnent ses droits au rôle
+-- et le backend s'y connecte : contrairement à postgres, il est soumis à la
+-- sécurité au niveau des lignes.
+CREATE ROLE saas_app LOGIN PASSWORD 'password';
@@ -3,6 +3,12 @@ export interface User {
   id: string;
   email: string;
   name: string | null;
+  locale: string | null;
+  timezone: string | null;
+  avatar_url: string | null;
+  email_verified_at: string | null;
+  created_at: string;
+  updated_at: string;
 }
 
 // Exemple pour la réponse d'authentification